@@ -0,0 +1,168 @@
+//! A small language server for the tests of the client. It keeps the text
+//! of the open documents, reports the lines containing "error", and answers
+//! hover, definition and completion requests with the words of the text, or
+//! with an error for a document not open.
+
+use std::{
+    collections::HashMap,
+    io::{self, BufReader},
+};
+
+use serde_json::{json, Value};
+
+#[path = "../src/lsp/transport.rs"]
+mod transport;
+
+fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+/// Returns the word at or before the given character of `line`, with its
+/// start.
+fn word_at(line: &str, c: usize) -> (usize, String) {
+    let chars: Vec<char> = line.chars().collect();
+    let c = c.min(chars.len());
+    let start = c - chars[..c]
+        .iter()
+        .rev()
+        .take_while(|ch| is_word_char(**ch))
+        .count();
+    let end = c + chars[c..]
+        .iter()
+        .take_while(|ch| is_word_char(**ch))
+        .count();
+    (start, chars[start..end].iter().collect())
+}
+
+/// Character index of a position, the test texts having no characters
+/// outside the basic plane.
+fn offset(text: &str, position: &Value) -> usize {
+    let l = position["line"].as_u64().unwrap() as usize;
+    let c = position["character"].as_u64().unwrap() as usize;
+    text.split('\n')
+        .take(l)
+        .map(|line| line.chars().count() + 1)
+        .sum::<usize>()
+        + c
+}
+
+fn apply_change(text: &mut String, change: &Value) {
+    let new_text = change["text"].as_str().unwrap_or_default();
+    if change["range"].is_null() {
+        *text = new_text.to_string();
+        return;
+    }
+    let start = offset(text, &change["range"]["start"]);
+    let end = offset(text, &change["range"]["end"]);
+    let mut chars: Vec<char> = text.chars().collect();
+    chars.splice(start..end, new_text.chars());
+    *text = chars.into_iter().collect();
+}
+
+fn diagnostics(uri: &str, text: &str) -> Value {
+    let diagnostics: Vec<Value> = text
+        .split('\n')
+        .enumerate()
+        .filter_map(|(l, line)| {
+            let c = line.find("error")?;
+            Some(json!({
+                "range": {
+                    "start": { "line": l, "character": c },
+                    "end": { "line": l, "character": c + 5 },
+                },
+                "severity": 1,
+                "message": "unexpected error",
+            }))
+        })
+        .collect();
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+fn main() -> io::Result<()> {
+    let mut input = BufReader::new(io::stdin().lock());
+    let mut output = io::stdout().lock();
+    let mut documents: HashMap<String, String> = HashMap::new();
+    while let Some(message) = transport::read_message(&mut input)? {
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let result = match message["method"].as_str().unwrap_or_default() {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 2,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "completionProvider": {},
+                },
+            }),
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                documents.insert(uri.to_string(), text.to_string());
+                transport::write_message(&mut output, &diagnostics(uri, text))?;
+                continue;
+            }
+            "textDocument/didChange" => {
+                let text = documents.entry(uri.to_string()).or_default();
+                for change in params["contentChanges"].as_array().unwrap() {
+                    apply_change(text, change);
+                }
+                transport::write_message(&mut output, &diagnostics(uri, text))?;
+                continue;
+            }
+            "textDocument/didClose" => {
+                documents.remove(uri);
+                continue;
+            }
+            "textDocument/hover" | "textDocument/definition" | "textDocument/completion" => {
+                let Some(text) = documents.get(uri).cloned() else {
+                    let error = json!({ "code": -32602, "message": "unknown document" });
+                    let response = json!({ "jsonrpc": "2.0", "id": message["id"], "error": error });
+                    transport::write_message(&mut output, &response)?;
+                    continue;
+                };
+                let l = params["position"]["line"].as_u64().unwrap() as usize;
+                let c = params["position"]["character"].as_u64().unwrap() as usize;
+                let line = text.split('\n').nth(l).unwrap_or_default();
+                let (start, word) = word_at(line, c);
+                match message["method"].as_str().unwrap() {
+                    "textDocument/hover" => json!({ "contents": word }),
+                    "textDocument/definition" => text
+                        .split('\n')
+                        .enumerate()
+                        .find_map(|(l, line)| {
+                            let c = line.find(&word)?;
+                            Some(json!({
+                                "uri": uri,
+                                "range": {
+                                    "start": { "line": l, "character": c },
+                                    "end": { "line": l, "character": c + word.len() },
+                                },
+                            }))
+                        })
+                        .unwrap_or(Value::Null),
+                    _ => {
+                        let prefix: String = line.chars().skip(start).take(c - start).collect();
+                        let mut words: Vec<&str> = text
+                            .split(|ch: char| !is_word_char(ch))
+                            .filter(|w| w.starts_with(&prefix) && *w != prefix)
+                            .collect();
+                        words.sort();
+                        words.dedup();
+                        let items: Vec<Value> =
+                            words.iter().map(|w| json!({ "label": w })).collect();
+                        json!({ "isIncomplete": false, "items": items })
+                    }
+                }
+            }
+            "shutdown" => Value::Null,
+            "exit" => break,
+            _ => continue,
+        };
+        let response = json!({ "jsonrpc": "2.0", "id": message["id"], "result": result });
+        transport::write_message(&mut output, &response)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,171 @@
+use sdl2::keyboard::{Keycode, Mod};
+
+use crate::text_zone::TextContent;
+
+type Command = fn(&mut TextContent);
+
+/// Editing commands that can be bound to keys by name.
+const COMMANDS: &[(&str, Command)] = &[
+    ("upper_case", TextContent::upper_case),
+    ("lower_case", TextContent::lower_case),
+    ("title_case", TextContent::title_case),
+    ("snake_case", TextContent::snake_case),
+    ("camel_case", TextContent::camel_case),
+    ("kebab_case", TextContent::kebab_case),
+    ("transpose_characters", TextContent::transpose_characters),
+    ("base64_encode", TextContent::base64_encode),
+    ("base64_decode", TextContent::base64_decode),
+    ("url_encode", TextContent::url_encode),
+    ("url_decode", TextContent::url_decode),
+    ("increment_number", TextContent::increment_number),
+    ("decrement_number", TextContent::decrement_number),
+    ("duplicate_lines", TextContent::duplicate_lines),
+    ("delete_lines", TextContent::delete_lines),
+    ("join_lines", TextContent::join_lines),
+    ("sort_lines", TextContent::sort_lines),
+    ("reverse_lines", TextContent::reverse_lines),
+    ("unique_lines", TextContent::unique_lines),
+    (
+        "trim_trailing_whitespace",
+        TextContent::trim_trailing_whitespace,
+    ),
+    ("move_line_up", TextContent::move_line_up),
+    ("move_line_down", TextContent::move_line_down),
+    ("indent", TextContent::indent),
+    ("outdent_lines", TextContent::outdent_lines),
+    ("fold", TextContent::fold),
+    ("toggle_bookmark", TextContent::toggle_bookmark),
+    ("next_bookmark", TextContent::next_bookmark),
+    ("previous_bookmark", TextContent::previous_bookmark),
+    ("unfold", TextContent::unfold),
+    ("toggle_line_comment", TextContent::toggle_line_comment),
+    ("toggle_block_comment", TextContent::toggle_block_comment),
+    (
+        "jump_to_matching_bracket",
+        TextContent::jump_to_matching_bracket,
+    ),
+    ("add_cursor_above", TextContent::add_cursor_above),
+    ("add_cursor_below", TextContent::add_cursor_below),
+    (
+        "add_cursor_at_next_occurrence",
+        TextContent::add_cursor_at_next_occurrence,
+    ),
+];
+
+/// Commands run by the editor with more than the text, that can be bound to
+/// keys by name as well.
+const EDITOR_COMMANDS: &[&str] = &["go_to_definition", "hover", "complete"];
+
+const DEFAULT_BINDINGS: &[(&str, &str)] = &[
+    ("ctrl+alt+u", "upper_case"),
+    ("ctrl+alt+l", "lower_case"),
+    ("ctrl+alt+t", "title_case"),
+    ("ctrl+alt+s", "snake_case"),
+    ("ctrl+alt+c", "camel_case"),
+    ("ctrl+alt+k", "kebab_case"),
+    ("ctrl+t", "transpose_characters"),
+    ("ctrl+alt+b", "base64_encode"),
+    ("ctrl+alt+shift+b", "base64_decode"),
+    ("ctrl+alt+e", "url_encode"),
+    ("ctrl+alt+shift+e", "url_decode"),
+    ("ctrl+alt+a", "increment_number"),
+    ("ctrl+alt+x", "decrement_number"),
+    ("ctrl+f2", "toggle_bookmark"),
+    ("f2", "next_bookmark"),
+    ("shift+f2", "previous_bookmark"),
+    ("f12", "go_to_definition"),
+    ("alt+h", "hover"),
+    ("ctrl+space", "complete"),
+];
+
+/// Runs the command called `name` on `content`, returns false if there is
+/// no such command.
+pub fn run(name: &str, content: &mut TextContent) -> bool {
+    match COMMANDS.iter().find(|(n, _)| *n == name) {
+        Some((_, command)) => {
+            command(content);
+            true
+        }
+        None => false,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Modifiers {
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+}
+
+impl Modifiers {
+    fn of(keymod: Mod) -> Self {
+        Modifiers {
+            ctrl: keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+            alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+            shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+        }
+    }
+}
+
+struct KeyBinding {
+    keycode: Keycode,
+    modifiers: Modifiers,
+    command: String,
+}
+
+/// Key combinations bound to named commands.
+pub(crate) struct Keymap {
+    bindings: Vec<KeyBinding>,
+}
+
+impl Keymap {
+    pub fn new() -> Self {
+        let mut keymap = Keymap {
+            bindings: Vec::new(),
+        };
+        for (keys, command) in DEFAULT_BINDINGS {
+            keymap.bind(keys, command).unwrap();
+        }
+        keymap
+    }
+
+    /// Binds `keys`, written like `ctrl+alt+u`, to `command`, replacing any
+    /// previous binding of the same keys.
+    pub fn bind(&mut self, keys: &str, command: &str) -> Result<(), String> {
+        if !COMMANDS.iter().any(|(n, _)| *n == command) && !EDITOR_COMMANDS.contains(&command) {
+            return Err(format!("Unknown command: {}", command));
+        }
+        let mut modifiers = Modifiers {
+            ctrl: false,
+            alt: false,
+            shift: false,
+        };
+        let mut keycode = None;
+        for key in keys.split('+') {
+            match key.trim().to_lowercase().as_str() {
+                "ctrl" => modifiers.ctrl = true,
+                "alt" => modifiers.alt = true,
+                "shift" => modifiers.shift = true,
+                name => keycode = Keycode::from_name(name),
+            }
+        }
+        let keycode = keycode.ok_or(format!("Invalid key combination: {}", keys))?;
+        self.bindings
+            .retain(|b| b.keycode != keycode || b.modifiers != modifiers);
+        self.bindings.push(KeyBinding {
+            keycode,
+            modifiers,
+            command: command.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Returns the name of the command bound to the pressed keys.
+    pub fn command(&self, keycode: Keycode, keymod: Mod) -> Option<&str> {
+        let modifiers = Modifiers::of(keymod);
+        self.bindings
+            .iter()
+            .find(|b| b.keycode == keycode && b.modifiers == modifiers)
+            .map(|b| b.command.as_str())
+    }
+}
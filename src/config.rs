@@ -0,0 +1,169 @@
+use std::{collections::HashMap, env, fs, path::PathBuf};
+
+use crate::commands::Keymap;
+use crate::text_zone::{IndentStyle, TextContent, DEFAULT_TAB_WIDTH};
+use crate::vue::LineNumberMode;
+
+/// The keys editing the text.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Keys {
+    Default,
+    /// The modes and commands of vim.
+    Vim,
+    /// The keys of emacs, with its mark and kill ring.
+    Emacs,
+}
+
+/// User settings, read from `config` in the configuration directory as
+/// `key = value` lines.
+pub(crate) struct Config {
+    pub tab_width: usize,
+    pub indent_style: IndentStyle,
+    pub auto_close: bool,
+    pub wrap: bool,
+    pub inline_blame: bool,
+    /// `None` when the line numbers are hidden.
+    pub line_numbers: Option<LineNumberMode>,
+    /// Command running the language server of each language, by name.
+    pub language_servers: HashMap<String, String>,
+    pub keymap: Keymap,
+    pub keys: Keys,
+    /// What was wrong in the configuration file, to tell the user.
+    pub errors: Vec<String>,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Config {
+            tab_width: DEFAULT_TAB_WIDTH,
+            indent_style: IndentStyle::Spaces(DEFAULT_TAB_WIDTH),
+            auto_close: true,
+            wrap: false,
+            inline_blame: false,
+            line_numbers: Some(LineNumberMode::Absolute),
+            language_servers: HashMap::new(),
+            keymap: Keymap::new(),
+            keys: Keys::Default,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Reads the configuration file, keeping the defaults for anything
+    /// missing or invalid.
+    pub fn load() -> Self {
+        let text = config_dir().and_then(|dir| fs::read_to_string(dir.join("config")).ok());
+        Self::parse(&text.unwrap_or_default())
+    }
+
+    fn parse(text: &str) -> Self {
+        let mut config = Self::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once('=') {
+                Some((key, value)) => config.set(key.trim(), value.trim()),
+                None => config.errors.push(format!("Invalid line: {}", line)),
+            }
+        }
+        config
+    }
+
+    fn set(&mut self, key: &str, value: &str) {
+        let invalid = format!("Invalid {}: {}", key.replace('_', " "), value);
+        match key {
+            "tab_width" => match value.parse::<usize>() {
+                Ok(n) => self.tab_width = n.max(1),
+                Err(_) => self.errors.push(invalid),
+            },
+            "indent" => match (value, value.parse::<usize>()) {
+                ("tabs", _) => self.indent_style = IndentStyle::Tabs,
+                (_, Ok(n)) => self.indent_style = IndentStyle::Spaces(n.max(1)),
+                _ => self.errors.push(invalid),
+            },
+            "auto_close" | "wrap" | "inline_blame" => match value.parse::<bool>() {
+                Ok(b) if key == "auto_close" => self.auto_close = b,
+                Ok(b) if key == "wrap" => self.wrap = b,
+                Ok(b) => self.inline_blame = b,
+                Err(_) => self.errors.push(invalid),
+            },
+            "line_numbers" => match value {
+                "absolute" => self.line_numbers = Some(LineNumberMode::Absolute),
+                "relative" => self.line_numbers = Some(LineNumberMode::Relative),
+                "hybrid" => self.line_numbers = Some(LineNumberMode::Hybrid),
+                "off" => self.line_numbers = None,
+                _ => self.errors.push(invalid),
+            },
+            "lsp" => match value.split_once(' ') {
+                Some((language, command)) => {
+                    let command = command.trim().to_string();
+                    self.language_servers.insert(language.to_string(), command);
+                }
+                None => {
+                    let error = format!("Missing language server command: {}", value);
+                    self.errors.push(error)
+                }
+            },
+            "keys" => match value {
+                "default" => self.keys = Keys::Default,
+                "vim" => self.keys = Keys::Vim,
+                "emacs" => self.keys = Keys::Emacs,
+                _ => self.errors.push(invalid),
+            },
+            "bind" => {
+                let (keys, command) = value.split_once(' ').unwrap_or((value, ""));
+                if let Err(e) = self.keymap.bind(keys, command.trim()) {
+                    self.errors.push(e);
+                }
+            }
+            _ => self.errors.push(format!("Unknown setting: {}", key)),
+        }
+    }
+
+    /// Applies the settings to `content`, keeping the indentation already
+    /// used by the text if any.
+    pub fn configure(&self, content: &mut TextContent) {
+        content.set_auto_close(self.auto_close);
+        content.set_tab_width(self.tab_width);
+        content.set_indent_style(content.detect_indent_style().unwrap_or(self.indent_style));
+    }
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("text_editor"))
+}
+
+/// The directory keeping what the editor remembers between runs, apart from
+/// the settings.
+pub fn state_dir() -> Option<PathBuf> {
+    env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .map(|dir| dir.join("text_editor"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_settings() {
+        let text = "# comment\n\ntab_width = 2\nwrap = yes\nindent = some\nkeys = vi\nwrap\n";
+        let config = Config::parse(text);
+        assert_eq!(config.tab_width, 2);
+        assert!(!config.wrap);
+        assert_eq!(
+            config.errors,
+            vec![
+                "Invalid wrap: yes",
+                "Invalid indent: some",
+                "Invalid keys: vi",
+                "Invalid line: wrap",
+            ]
+        );
+    }
+}
@@ -0,0 +1,235 @@
+/// Beyond this many differing lines, the changed part is taken as a whole
+/// instead of being compared line by line.
+const MAX_EDITS: usize = 4000;
+
+/// A run of changed lines: `old_len` lines at `old_start` in the old text
+/// replaced by `new_len` lines at `new_start` in the new one.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Hunk {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+}
+
+/// Returns the hunks turning `old` into `new`, in order.
+pub fn diff<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Hunk> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+    let mut hunks = Vec::new();
+    let (mut x, mut y) = (0, 0);
+    for (mx, my) in common_lines(a, b).into_iter().chain([(a.len(), b.len())]) {
+        if mx > x || my > y {
+            hunks.push(Hunk {
+                old_start: prefix + x,
+                old_len: mx - x,
+                new_start: prefix + y,
+                new_len: my - y,
+            });
+        }
+        (x, y) = (mx + 1, my + 1);
+    }
+    hunks
+}
+
+/// Returns the positions in `a` and `b` of a longest common subsequence,
+/// using the linear space algorithm of Myers. Empty when more than
+/// `MAX_EDITS` lines differ.
+fn common_lines<T: PartialEq>(a: &[T], b: &[T]) -> Vec<(usize, usize)> {
+    let mut matches = Vec::new();
+    if a.is_empty() || b.is_empty() {
+        return matches;
+    }
+    if let Some(snake) = middle_snake(a, b, MAX_EDITS) {
+        split_at_snake(a, b, snake, (0, 0), &mut matches);
+    }
+    matches
+}
+
+/// Adds to `matches` the positions, shifted by `at`, of a longest common
+/// subsequence of `a` and `b`.
+fn add_common_lines<T: PartialEq>(
+    a: &[T],
+    b: &[T],
+    at: (usize, usize),
+    matches: &mut Vec<(usize, usize)>,
+) {
+    if a.is_empty() || b.is_empty() {
+        return;
+    }
+    if let Some(snake) = middle_snake(a, b, a.len() + b.len()) {
+        split_at_snake(a, b, snake, at, matches);
+    }
+}
+
+/// Adds the common lines before the `snake` found in `a` and `b`, those of
+/// the snake, then those after it.
+fn split_at_snake<T: PartialEq>(
+    a: &[T],
+    b: &[T],
+    (d, (x, y), (u, v)): Snake,
+    at: (usize, usize),
+    matches: &mut Vec<(usize, usize)>,
+) {
+    if d <= 1 {
+        // At most one line is added or removed, the others are common.
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() && j < b.len() {
+            if a[i] == b[j] {
+                matches.push((at.0 + i, at.1 + j));
+                (i, j) = (i + 1, j + 1);
+            } else if a.len() > b.len() {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        return;
+    }
+    add_common_lines(&a[..x], &b[..y], at, matches);
+    matches.extend((0..u - x).map(|i| (at.0 + x + i, at.1 + y + i)));
+    add_common_lines(&a[u..], &b[v..], (at.0 + u, at.1 + v), matches);
+}
+
+/// The number of edits turning one text into the other, and the start and
+/// end of the common lines met halfway through them.
+type Snake = (usize, (usize, usize), (usize, usize));
+
+/// Finds the middle snake of `a` and `b`, searching from both ends at once
+/// with only the furthest points of the current diagonals. None when more
+/// than `max_edits` lines differ.
+fn middle_snake<T: PartialEq>(a: &[T], b: &[T], max_edits: usize) -> Option<Snake> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+    let max = (n + m + 1) / 2;
+    let offset = max + 1;
+    // Furthest x on each diagonal, from the start and from the end.
+    let mut forward = vec![0isize; 2 * max as usize + 3];
+    let mut backward = vec![0isize; 2 * max as usize + 3];
+    for d in 0..=max {
+        if (2 * d - 1).max(0) as usize > max_edits {
+            return None;
+        }
+        for k in (-d..=d).step_by(2) {
+            let i = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && forward[i - 1] < forward[i + 1]) {
+                forward[i + 1]
+            } else {
+                forward[i - 1] + 1
+            };
+            let start = (x, x - k);
+            while x < n && x - k < m && a[x as usize] == b[(x - k) as usize] {
+                x += 1;
+            }
+            forward[i] = x;
+            let back = delta - k;
+            if odd
+                && (-(d - 1)..=d - 1).contains(&back)
+                && x + backward[(back + offset) as usize] >= n
+            {
+                let (x0, y0) = (start.0 as usize, start.1 as usize);
+                return Some((
+                    (2 * d - 1) as usize,
+                    (x0, y0),
+                    (x as usize, (x - k) as usize),
+                ));
+            }
+        }
+        for k in (-d..=d).step_by(2) {
+            let i = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && backward[i - 1] < backward[i + 1]) {
+                backward[i + 1]
+            } else {
+                backward[i - 1] + 1
+            };
+            let start = (x, x - k);
+            while x < n && x - k < m && a[(n - 1 - x) as usize] == b[(m - 1 - x + k) as usize] {
+                x += 1;
+            }
+            backward[i] = x;
+            let ahead = delta - k;
+            if !odd && (-d..=d).contains(&ahead) && x + forward[(ahead + offset) as usize] >= n {
+                let (u, v) = ((n - start.0) as usize, (m - start.1) as usize);
+                return Some((
+                    (2 * d) as usize,
+                    ((n - x) as usize, (m - x + k) as usize),
+                    (u, v),
+                ));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(old_start: usize, old_len: usize, new_start: usize, new_len: usize) -> Hunk {
+        Hunk {
+            old_start,
+            old_len,
+            new_start,
+            new_len,
+        }
+    }
+
+    #[test]
+    fn diff_lines() {
+        assert_eq!(diff(&["a", "b"], &["a", "b"]), vec![]);
+        assert_eq!(diff(&["a", "b"], &["a", "x", "b"]), vec![hunk(1, 0, 1, 1)]);
+        assert_eq!(diff(&["a", "b", "c"], &["a", "c"]), vec![hunk(1, 1, 1, 0)]);
+        assert_eq!(
+            diff(&["a", "b", "c", "d", "e"], &["x", "b", "c", "y", "z", "e"]),
+            vec![hunk(0, 1, 0, 1), hunk(3, 1, 3, 2)]
+        );
+        assert_eq!(
+            diff(&["a", "b", "a", "c"], &["b", "a", "c", "a"]),
+            vec![hunk(0, 1, 0, 0), hunk(4, 0, 3, 1)]
+        );
+        assert_eq!(diff::<&str>(&[], &["a"]), vec![hunk(0, 0, 0, 1)]);
+    }
+
+    #[test]
+    fn longest_common_lines() {
+        let mut seed = 7u32;
+        let mut text = |len: usize| -> Vec<u32> {
+            (0..len)
+                .map(|_| {
+                    seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                    (seed >> 16) % 4
+                })
+                .collect()
+        };
+        for (n, m) in [(1, 1), (5, 3), (12, 17), (40, 33), (60, 60)] {
+            let (a, b) = (text(n), text(m));
+            let mut lengths = vec![vec![0; m + 1]; n + 1];
+            for i in (0..n).rev() {
+                for j in (0..m).rev() {
+                    lengths[i][j] = if a[i] == b[j] {
+                        lengths[i + 1][j + 1] + 1
+                    } else {
+                        lengths[i + 1][j].max(lengths[i][j + 1])
+                    };
+                }
+            }
+            let matches = common_lines(&a, &b);
+            assert_eq!(matches.len(), lengths[0][0]);
+            assert!(matches.iter().all(|(x, y)| a[*x] == b[*y]));
+            assert!(matches
+                .windows(2)
+                .all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+        }
+        let (a, b): (Vec<usize>, Vec<usize>) = ((0..5000).collect(), (5000..10000).collect());
+        assert!(common_lines(&a, &b).is_empty());
+        assert_eq!(diff(&a, &b), vec![hunk(0, 5000, 0, 5000)]);
+    }
+}
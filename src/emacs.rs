@@ -0,0 +1,188 @@
+use crate::text_zone::{Region, TextContent};
+
+/// Most kills kept in the kill ring, older ones are forgotten.
+const MAX_KILLS: usize = 120;
+
+/// The keys the emacs keys read, a character typed with Ctrl or Meta, or
+/// anything else.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Key {
+    Ctrl(char),
+    Meta(char),
+    Other,
+}
+
+/// What the editor still has to do with a key given to the emacs keys.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Action {
+    /// The key is not used by the emacs keys.
+    Pass,
+    Handled,
+    Save,
+    Open,
+}
+
+/// What the previous key did, for the keys continuing it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Last {
+    Other,
+    Kill,
+    Yank(Region),
+}
+
+/// The keys of emacs: moving with the mark setting a region, and a kill
+/// ring holding the killed texts to yank them back.
+pub(crate) struct Emacs {
+    /// The killed texts, the most recent last. Consecutive kills make one.
+    kills: Vec<String>,
+    /// Index in `kills` of the text last yanked.
+    yanked: usize,
+    last: Last,
+    /// Whether `C-x` was typed, waiting for the rest of the command.
+    prefix: bool,
+}
+
+impl Emacs {
+    pub fn new() -> Self {
+        Emacs {
+            kills: Vec::new(),
+            yanked: 0,
+            last: Last::Other,
+            prefix: false,
+        }
+    }
+
+    /// Describes the command being typed.
+    pub fn status(&self) -> Option<String> {
+        self.prefix.then(|| "C-x-".to_string())
+    }
+
+    pub fn key(&mut self, key: Key, content: &mut TextContent) -> Action {
+        let last = std::mem::replace(&mut self.last, Last::Other);
+        if std::mem::take(&mut self.prefix) {
+            return match key {
+                Key::Ctrl('s') => Action::Save,
+                Key::Ctrl('f') => Action::Open,
+                Key::Other => Action::Pass,
+                _ => Action::Handled,
+            };
+        }
+        match key {
+            Key::Ctrl('f') => content.move_point(|c| c.move_cursor_right(1)),
+            Key::Ctrl('b') => content.move_point(|c| c.move_cursor_left(1)),
+            Key::Ctrl('n') => content.move_point(|c| c.move_cursor_down(1)),
+            Key::Ctrl('p') => content.move_point(|c| c.move_cursor_up(1)),
+            Key::Ctrl('a') => content.move_point(TextContent::snap_cursor_start_of_line),
+            Key::Ctrl('e') => content.move_point(TextContent::snap_cursor_end_of_line),
+            Key::Ctrl(' ') => content.set_mark(),
+            Key::Ctrl('g') => content.clear_selection(),
+            Key::Ctrl('k') => {
+                let text = content.kill_line();
+                self.kill(text, last == Last::Kill);
+            }
+            Key::Ctrl('w') => {
+                if let Some(text) = content.cut() {
+                    self.kill(text, last == Last::Kill);
+                }
+            }
+            Key::Meta('w') => {
+                if let Some(text) = content.get_selected_text() {
+                    content.clear_selection();
+                    self.kill(text, last == Last::Kill);
+                }
+            }
+            Key::Ctrl('y') => {
+                if let Some(text) = self.kills.last() {
+                    self.yanked = self.kills.len() - 1;
+                    self.last = Last::Yank(content.yank(text));
+                }
+            }
+            // Replaces the text just yanked by the kill before it.
+            Key::Meta('y') => {
+                if let Last::Yank(region) = last {
+                    self.yanked = self.yanked.checked_sub(1).unwrap_or(self.kills.len() - 1);
+                    self.last = Last::Yank(content.replace_yank(region, &self.kills[self.yanked]));
+                }
+            }
+            Key::Ctrl('x') => self.prefix = true,
+            _ => return Action::Pass,
+        }
+        Action::Handled
+    }
+
+    /// Adds `text` to the kill ring, at the end of the last kill when
+    /// `append` is set.
+    fn kill(&mut self, text: String, append: bool) {
+        self.last = Last::Kill;
+        match self.kills.last_mut() {
+            Some(last) if append => last.push_str(&text),
+            _ if text.is_empty() => {}
+            _ => {
+                self.kills.push(text);
+                if self.kills.len() > MAX_KILLS {
+                    self.kills.remove(0);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn content(text: &str, cursor: (usize, usize)) -> TextContent {
+        let mut content = TextContent::new();
+        content.paste(text.to_string());
+        content.set_cursor(cursor);
+        content
+    }
+
+    #[test]
+    fn kill_ring() {
+        let mut emacs = Emacs::new();
+        let mut c = content("one\ntwo\nthree", (0, 0));
+        for _ in 0..4 {
+            emacs.key(Key::Ctrl('k'), &mut c);
+        }
+        assert_eq!(c.get_text(), vec!["three"]);
+        assert_eq!(emacs.kills, vec!["one\ntwo\n"]);
+        emacs.key(Key::Ctrl('f'), &mut c);
+        emacs.key(Key::Ctrl('k'), &mut c);
+        assert_eq!(emacs.kills, vec!["one\ntwo\n", "hree"]);
+        emacs.key(Key::Ctrl('y'), &mut c);
+        assert_eq!(c.get_text(), vec!["three"]);
+        emacs.key(Key::Meta('y'), &mut c);
+        assert_eq!(c.get_text(), vec!["tone", "two", ""]);
+        assert_eq!(c.get_cursor(), (2, 0));
+        emacs.key(Key::Meta('y'), &mut c);
+        assert_eq!(c.get_text(), vec!["three"]);
+        emacs.key(Key::Other, &mut c);
+        emacs.key(Key::Meta('y'), &mut c);
+        assert_eq!(c.get_text(), vec!["three"]);
+    }
+
+    #[test]
+    fn mark_and_region() {
+        let mut emacs = Emacs::new();
+        let mut c = content("hello world", (0, 0));
+        emacs.key(Key::Ctrl(' '), &mut c);
+        for _ in 0..5 {
+            emacs.key(Key::Ctrl('f'), &mut c);
+        }
+        emacs.key(Key::Meta('w'), &mut c);
+        assert!(!c.has_mark());
+        emacs.key(Key::Ctrl('e'), &mut c);
+        emacs.key(Key::Ctrl('y'), &mut c);
+        assert_eq!(c.get_text(), vec!["hello worldhello"]);
+        emacs.key(Key::Ctrl(' '), &mut c);
+        emacs.key(Key::Ctrl('a'), &mut c);
+        emacs.key(Key::Ctrl('w'), &mut c);
+        assert_eq!(c.get_text(), vec![""]);
+        assert_eq!(emacs.kills, vec!["hello", "hello worldhello"]);
+        assert_eq!(emacs.key(Key::Ctrl('x'), &mut c), Action::Handled);
+        assert_eq!(emacs.status().as_deref(), Some("C-x-"));
+        assert_eq!(emacs.key(Key::Ctrl('s'), &mut c), Action::Save);
+        assert_eq!(emacs.key(Key::Ctrl('z'), &mut c), Action::Pass);
+    }
+}
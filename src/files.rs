@@ -1,49 +1,571 @@
-use crate::text_zone::TextContent;
+use std::{fs, path::Path};
+
+use crate::{
+    diff::{diff, Hunk},
+    git::{self, Blame, Status},
+    language::Language,
+    lsp::Diagnostic,
+    text_zone::{CursorState, TextContent},
+    words::{self, WordIndex},
+};
 
 pub(crate) struct File {
     pub path: Option<String>,
     pub content: TextContent,
+    /// The lines as last loaded or saved, `None` for a new file.
+    pub saved: Option<Vec<String>>,
+    /// The lines in the last commit, when the file is tracked by git.
+    head: Option<Vec<String>>,
+    /// The commit of each line in `head`.
+    /// Read only once the blame is shown, `None` until then.
+    blame: Option<Vec<Blame>>,
+    /// The commit `head` and `blame` were read at.
+    head_commit: Option<String>,
+    /// Whether the repository may have changed since it was last read.
+    git_outdated: bool,
+    /// Status of the repository holding the file.
+    status: Option<Status>,
+    /// Changes from the committed lines, or else the saved ones, to the
+    /// text, with the text they were computed for and its revision.
+    changes: Vec<Hunk>,
+    diffed: Vec<String>,
+    diffed_revision: Option<u64>,
+    /// Problems reported by the language server of the file.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The words of the text, to complete words with.
+    words: WordIndex,
+    /// The scroll offset restored from the session, to show the file at
+    /// once it is shown.
+    pub scroll: Option<(f32, f32)>,
+}
+
+/// A pane showing one of the files. The cursors of the current view live in
+/// the content of its file, those of the other views are kept here.
+struct View {
+    file: usize,
+    cursors: CursorState,
 }
 
 pub(crate) struct FileContext {
     files: Vec<File>,
+    views: Vec<View>,
     current: usize,
 }
 
+/// Returns the hunks turning `base` into `new`, from the `hunks` turning it
+/// into `old`, by comparing again only the lines differing between `old` and
+/// `new` and the hunks touching them.
+fn update_hunks(base: &[String], old: &[String], new: &[String], hunks: Vec<Hunk>) -> Vec<Hunk> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = (old[prefix..].iter().rev())
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (mut start, mut end) = (prefix, old.len() - suffix);
+    let first = hunks.partition_point(|h| h.new_start + h.new_len < start);
+    let last = hunks.partition_point(|h| h.new_start <= end);
+    let shift = |hunks: &[Hunk]| -> isize {
+        (hunks.iter())
+            .map(|h| h.old_len as isize - h.new_len as isize)
+            .sum()
+    };
+    if let (Some(a), Some(b)) = (hunks[first..last].first(), hunks[first..last].last()) {
+        start = start.min(a.new_start);
+        end = end.max(b.new_start + b.new_len);
+    }
+    let base_start = (start as isize + shift(&hunks[..first])) as usize;
+    let base_end = (end as isize + shift(&hunks[..last])) as usize;
+    let grown = new.len() as isize - old.len() as isize;
+    let new_end = (end as isize + grown) as usize;
+    let mut res = hunks[..first].to_vec();
+    res.extend(
+        diff(&base[base_start..base_end], &new[start..new_end])
+            .into_iter()
+            .map(|h| Hunk {
+                old_start: base_start + h.old_start,
+                new_start: start + h.new_start,
+                ..h
+            }),
+    );
+    res.extend(hunks[last..].iter().map(|h| Hunk {
+        new_start: (h.new_start as isize + grown) as usize,
+        ..h.clone()
+    }));
+    res
+}
+
 impl File {
     pub fn new() -> Self {
         File {
             path: None,
             content: TextContent::new(),
+            saved: None,
+            head: None,
+            blame: None,
+            head_commit: None,
+            git_outdated: false,
+            status: None,
+            changes: Vec::new(),
+            diffed: Vec::new(),
+            diffed_revision: None,
+            diagnostics: Vec::new(),
+            words: WordIndex::new(),
+            scroll: None,
+        }
+    }
+
+    /// Returns a file loaded from `path`.
+    pub fn loaded(path: String, content: TextContent) -> Self {
+        let mut file = File::new();
+        file.path = Some(path);
+        file.content = content;
+        file.mark_saved();
+        file
+    }
+
+    /// Returns the name of the file without its directory, empty for a new
+    /// file.
+    pub fn file_name(&self) -> String {
+        let path = self.path.as_deref().map(Path::new);
+        path.and_then(|path| path.file_name())
+            .map_or(String::new(), |name| name.to_string_lossy().to_string())
+    }
+
+    /// Remembers the current text as the one on disk. The committed version
+    /// is read again only when the last commit changed.
+    pub fn mark_saved(&mut self) {
+        self.saved = Some(self.content.get_text());
+        self.diffed_revision = None;
+        self.git_outdated = true;
+        self.refresh_git();
+    }
+
+    /// Returns whether the text differs from the one on disk, or is not empty
+    /// for a new file.
+    pub fn is_modified(&self) -> bool {
+        match &self.saved {
+            Some(saved) => *saved != self.content.get_text(),
+            None => !self.content.get_string().is_empty(),
+        }
+    }
+
+    /// Reads the committed version of the file and the status of its
+    /// repository again.
+    pub fn reload_git(&mut self) {
+        let path = self.path.as_deref();
+        self.head = path
+            .and_then(git::head_content)
+            .map(|text| TextContent::from_string(text).get_text());
+        self.blame = None;
+        self.head_commit = path.and_then(git::head_commit);
+        self.status = path.and_then(git::status);
+        self.git_outdated = false;
+        self.diffed_revision = None;
+    }
+
+    /// Reads the status of the repository again if it may have changed, and
+    /// the committed version of the file only when the last commit changed.
+    fn refresh_git(&mut self) {
+        if !self.git_outdated {
+            return;
+        }
+        let path = self.path.as_deref();
+        if path.and_then(git::head_commit) != self.head_commit {
+            self.reload_git();
+            return;
+        }
+        self.status = path.and_then(git::status);
+        self.git_outdated = false;
+    }
+
+    /// Reads the blame of the committed version, if not read yet.
+    pub fn load_blame(&mut self) {
+        if self.blame.is_none() && self.head.is_some() {
+            self.blame = Some(
+                self.path
+                    .as_deref()
+                    .and_then(git::blame)
+                    .unwrap_or_default(),
+            );
         }
     }
+
+    pub fn git_status(&self) -> Option<&Status> {
+        self.status.as_ref()
+    }
+
+    /// Describes the commit that last changed line `l`, following the
+    /// changes made since.
+    pub fn blame_at(&self, l: usize) -> Option<String> {
+        self.head.as_ref()?;
+        let mut shift = 0;
+        for hunk in self.changes.iter() {
+            if l < hunk.new_start {
+                break;
+            }
+            if l < hunk.new_start + hunk.new_len {
+                return Some("Not committed yet".to_string());
+            }
+            shift += hunk.old_len as isize - hunk.new_len as isize;
+        }
+        let blame = self.blame.as_ref()?.get((l as isize + shift) as usize)?;
+        Some(format!(
+            "{}, {}, {}: {}",
+            blame.author,
+            git::format_date(blame.time),
+            blame.commit,
+            blame.summary
+        ))
+    }
+
+    /// The lines the changes are relative to.
+    pub fn base(&self) -> Option<&Vec<String>> {
+        self.head.as_ref().or(self.saved.as_ref())
+    }
+
+    /// Updates the changes if the text changed since the last time. Only the
+    /// lines edited since, with the changes they touch, are compared again.
+    pub fn update_changes(&mut self) {
+        let revision = self.content.revision();
+        if self.diffed_revision == Some(revision) {
+            return;
+        }
+        let text = self.content.get_text();
+        let base = self.head.as_ref().or(self.saved.as_ref());
+        self.changes = match (base, self.diffed_revision) {
+            (None, _) => Vec::new(),
+            (Some(base), None) => diff(base, &text),
+            (Some(base), Some(_)) => {
+                let changes = std::mem::take(&mut self.changes);
+                update_hunks(base, &self.diffed, &text, changes)
+            }
+        };
+        self.diffed = text;
+        self.diffed_revision = Some(revision);
+    }
+
+    pub fn changes(&self) -> &[Hunk] {
+        &self.changes
+    }
+
+    /// Returns the line where `hunk` is shown, the one after the removed
+    /// lines for a removal.
+    pub fn change_line(&self, hunk: &Hunk) -> usize {
+        hunk.new_start.min(self.content.line_count() - 1)
+    }
+
+    /// Moves the cursor to the start of the next change, going back to the
+    /// first one after the last.
+    pub fn next_change(&mut self) {
+        let (l, _) = self.content.get_cursor();
+        let lines: Vec<usize> = self.changes.iter().map(|h| self.change_line(h)).collect();
+        let next = lines.iter().find(|start| **start > l);
+        if let Some(start) = next.or(lines.first()).copied() {
+            self.content.set_cursor((start, 0));
+        }
+    }
+
+    /// Moves the cursor to the start of the previous change, going to the
+    /// last one before the first.
+    pub fn previous_change(&mut self) {
+        let (l, _) = self.content.get_cursor();
+        let lines: Vec<usize> = self.changes.iter().map(|h| self.change_line(h)).collect();
+        let previous = lines.iter().rev().find(|start| **start < l);
+        if let Some(start) = previous.or(lines.last()).copied() {
+            self.content.set_cursor((start, 0));
+        }
+    }
+
+    /// Puts back the lines of the base in place of the change on the cursor
+    /// line.
+    pub fn revert_change(&mut self) {
+        self.update_changes();
+        let (l, _) = self.content.get_cursor();
+        let hunk = self.changes.iter().find(|h| {
+            (h.new_start..h.new_start + h.new_len).contains(&l)
+                || (h.new_len == 0 && self.change_line(h) == l)
+        });
+        let (Some(hunk), Some(base)) = (hunk.cloned(), self.base()) else {
+            return;
+        };
+        let lines = base[hunk.old_start..hunk.old_start + hunk.old_len].to_vec();
+        self.content
+            .replace_lines(hunk.new_start, hunk.new_len, &lines);
+    }
 }
 
 impl FileContext {
     pub fn new() -> Self {
+        let file = File::new();
+        let cursors = file.content.cursor_state();
+        FileContext {
+            files: vec![file],
+            views: vec![View { file: 0, cursors }],
+            current: 0,
+        }
+    }
+
+    /// Returns the files restored from a session, showing the one at
+    /// `current`, or a new file when there are none.
+    pub fn with_files(files: Vec<File>, current: usize) -> Self {
+        if files.is_empty() {
+            return Self::new();
+        }
+        let current = current.min(files.len() - 1);
+        let cursors = files[current].content.cursor_state();
         FileContext {
-            files: vec![File::new()],
+            files,
+            views: vec![View {
+                file: current,
+                cursors,
+            }],
             current: 0,
         }
     }
 
     pub fn current(&mut self) -> &mut File {
-        &mut self.files[self.current]
+        &mut self.files[self.views[self.current].file]
+    }
+
+    /// Returns the index of the file of the current view.
+    pub fn current_file(&self) -> usize {
+        self.views[self.current].file
     }
 
     pub fn _set_current_content(&mut self, content: TextContent) {
-        self.files[self.current].content = content;
+        self.current().content = content;
     }
 
     pub fn set_current_path(&mut self, path: String) {
-        self.files[self.current].path = Some(path);
+        let file = self.current();
+        file.content.set_language(Language::from_path(Some(&path)));
+        file.path = Some(path);
+        // The committed version is read again for the new path.
+        file.head_commit = None;
+    }
+
+    pub fn files(&self) -> &[File] {
+        &self.files
+    }
+
+    pub fn files_mut(&mut self) -> &mut [File] {
+        &mut self.files
+    }
+
+    /// Returns the index of the file loaded from `path`.
+    pub fn find(&self, path: &str) -> Option<usize> {
+        let path = fs::canonicalize(path).ok()?;
+        self.files.iter().position(|file| {
+            file.path
+                .as_ref()
+                .and_then(|p| fs::canonicalize(p).ok())
+                .is_some_and(|p| p == path)
+        })
+    }
+
+    /// Returns the view showing the file at `index`, the current one first.
+    pub fn view_of(&self, index: usize) -> Option<usize> {
+        if self.views[self.current].file == index {
+            return Some(self.current);
+        }
+        self.views.iter().position(|view| view.file == index)
+    }
+
+    /// Shows the file at `index` in the current view.
+    pub fn select(&mut self, index: usize) {
+        self.views[self.current].file = index;
+        self.current().refresh_git();
     }
 
     pub fn add_file(&mut self, file: File) {
         self.files.push(file);
     }
 
+    /// Shows the last file in the current view.
     pub fn select_last(&mut self) {
-        self.current = self.files.len() - 1;
+        self.select(self.files.len() - 1);
+    }
+
+    /// Moves the cursors kept for the views along the lines replaced in their
+    /// file since the last time.
+    fn follow_edits(&mut self) {
+        for (i, file) in self.files.iter_mut().enumerate() {
+            let shifts = file.content.take_line_shifts();
+            for (_, view) in (self.views.iter_mut().enumerate())
+                .filter(|(v, view)| *v != self.current && view.file == i)
+            {
+                for (at, removed, inserted) in shifts.iter() {
+                    view.cursors.shift_lines(*at, *removed, *inserted);
+                }
+            }
+        }
+    }
+
+    /// Adds a view of the current file with the same cursors, and returns it.
+    pub fn split(&mut self) -> usize {
+        self.follow_edits();
+        let view = View {
+            file: self.views[self.current].file,
+            cursors: self.current().content.cursor_state(),
+        };
+        self.views.push(view);
+        self.views.len() - 1
+    }
+
+    /// Makes `view` the current view, swapping the cursors of the views.
+    pub fn focus(&mut self, view: usize) {
+        if view == self.current {
+            return;
+        }
+        self.follow_edits();
+        let cursors = self.current().content.cursor_state();
+        self.views[self.current].cursors = cursors;
+        self.current = view;
+        let cursors = self.views[view].cursors.clone();
+        self.current().content.set_cursor_state(cursors);
+        self.current().refresh_git();
+    }
+
+    /// Removes the current view, making `focus` the current one. `focus` is
+    /// numbered as after the removal.
+    pub fn close_view(&mut self, focus: usize) {
+        self.follow_edits();
+        self.views.remove(self.current);
+        self.current = focus;
+        let cursors = self.views[focus].cursors.clone();
+        self.current().content.set_cursor_state(cursors);
+        self.current().refresh_git();
+    }
+
+    /// Takes the repositories of the files as changed, reading them again
+    /// for the current file now and for the others once they are shown.
+    pub fn reload_git(&mut self) {
+        for file in self.files.iter_mut() {
+            file.git_outdated = true;
+        }
+        self.current().refresh_git();
+    }
+
+    /// Reads the blame of the files of the views where `shown` tells it is
+    /// shown, the views being numbered like `shown`.
+    pub fn load_blame(&mut self, shown: &[bool]) {
+        for (view, _) in (self.views.iter()).zip(shown).filter(|(_, shown)| **shown) {
+            self.files[view.file].load_blame();
+        }
+    }
+
+    pub fn update_changes(&mut self) {
+        for file in self.files.iter_mut() {
+            file.update_changes();
+        }
+    }
+
+    /// Returns the words of the open files completing the word before the
+    /// cursor, the best ones first.
+    pub fn word_completions(&mut self) -> Vec<String> {
+        for file in self.files.iter_mut() {
+            file.words.update(&file.content.get_text());
+        }
+        let current = self.views[self.current].file;
+        let content = &self.files[current].content;
+        let (prefix, (l, _)) = (content.word_before_cursor(), content.get_cursor());
+        let others: Vec<&WordIndex> = (self.files.iter().enumerate())
+            .filter(|(i, _)| *i != current)
+            .map(|(_, file)| &file.words)
+            .collect();
+        words::complete(&prefix, l, &self.files[current].words, &others)
+    }
+
+    /// Returns the result of `f` on the file of each view, with the cursors
+    /// of that view.
+    pub fn map_views<T, F: FnMut(&File) -> T>(&mut self, mut f: F) -> Vec<T> {
+        self.follow_edits();
+        let mut res = Vec::new();
+        for (i, view) in self.views.iter().enumerate() {
+            let file = &mut self.files[view.file];
+            if i == self.current {
+                res.push(f(file));
+                continue;
+            }
+            let own = file.content.cursor_state();
+            file.content.set_cursor_state(view.cursors.clone());
+            res.push(f(file));
+            file.content.set_cursor_state(own);
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_follow_edits() {
+        let mut file = File::new();
+        file.content = TextContent::from_string("a\nb\nc\nd\ne\nf\ng".to_string());
+        file.saved = Some(file.content.get_text());
+        file.update_changes();
+        assert!(file.changes().is_empty());
+        let check = |file: &mut File, edit: fn(&mut TextContent)| {
+            edit(&mut file.content);
+            file.update_changes();
+            let expected = diff(file.base().unwrap(), &file.content.get_text());
+            assert_eq!(file.changes(), expected);
+        };
+        check(&mut file, |c| {
+            c.set_cursor((1, 1));
+            c.append("x".to_string());
+        });
+        check(&mut file, |c| {
+            c.set_cursor((5, 1));
+            c.append("\ny".to_string());
+        });
+        check(&mut file, |c| {
+            c.set_cursor((3, 0));
+            c.delete_lines();
+        });
+        check(&mut file, |c| {
+            c.set_cursor((1, 2));
+            c.remove();
+        });
+        check(&mut file, |c| {
+            c.set_cursor((0, 0));
+            c.append("z\n".to_string());
+        });
+        check(&mut file, |c| {
+            c.set_cursor((7, 1));
+            c.remove();
+            c.remove();
+        });
+        check(&mut file, |_| {});
+        assert_eq!(file.changes().len(), 3);
+    }
+
+    #[test]
+    fn views_follow_edits() {
+        let mut files = FileContext::new();
+        files.current().content = TextContent::from_string("a\nb\nc\nd".to_string());
+        files.current().content.set_cursor((3, 1));
+        let other = files.split();
+        files.current().content.set_cursor((0, 0));
+        files.current().content.delete_lines();
+        files.current().content.set_cursor((1, 0));
+        files.focus(other);
+        assert_eq!(files.current().content.get_cursor(), (2, 1));
+        files.current().content.set_cursor((0, 0));
+        files.current().content.paste("x\ny\n".to_string());
+        files.current().content.set_cursor((2, 1));
+        files.focus(0);
+        assert_eq!(files.current().content.get_cursor(), (3, 0));
+        assert_eq!(
+            files.current().content.get_text(),
+            ["x", "y", "b", "c", "d"]
+        );
+        // Cursors left past their line by edits are brought back into it.
+        files.current().content.set_cursor((2, 1));
+        files.current().content.remove();
+        files.focus(other);
+        assert_eq!(files.current().content.get_cursor(), (2, 0));
     }
 }
@@ -0,0 +1,185 @@
+use std::{collections::HashMap, path::Path, process::Command};
+
+/// Runs the local `git` in the directory of `path`, and returns its output,
+/// or `None` when git is missing or fails.
+fn git(path: &Path, args: &[&str]) -> Option<String> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
+/// Returns the content of the file at `path` in the last commit, or `None`
+/// when it is not tracked by a git repository.
+pub fn head_content(path: &str) -> Option<String> {
+    let path = Path::new(path);
+    let name = path.file_name()?.to_str()?;
+    git(path, &["show", &format!("HEAD:./{}", name)])
+}
+
+/// Returns the hash of the last commit of the repository holding `path`.
+pub fn head_commit(path: &str) -> Option<String> {
+    let output = git(Path::new(path), &["rev-parse", "HEAD"])?;
+    Some(output.trim().to_string())
+}
+
+/// Branch of a repository, and whether its working tree has changes.
+#[derive(Clone)]
+pub(crate) struct Status {
+    /// The short commit hash when no branch is checked out.
+    pub branch: String,
+    pub dirty: bool,
+}
+
+/// Returns the status of the repository holding `path`.
+pub fn status(path: &str) -> Option<Status> {
+    let output = git(Path::new(path), &["status", "--porcelain=v2", "--branch"])?;
+    let mut branch = None;
+    let mut commit = None;
+    let mut dirty = false;
+    for line in output.lines() {
+        if let Some(head) = line.strip_prefix("# branch.head ") {
+            branch = Some(head.to_string()).filter(|head| head != "(detached)");
+        } else if let Some(oid) = line.strip_prefix("# branch.oid ") {
+            commit = Some(oid.chars().take(7).collect());
+        } else if !line.starts_with('#') {
+            dirty = true;
+        }
+    }
+    Some(Status {
+        branch: branch.or(commit)?,
+        dirty,
+    })
+}
+
+/// The last commit that changed a line.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Blame {
+    /// Short hash of the commit.
+    pub commit: String,
+    pub author: String,
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    pub summary: String,
+}
+
+/// Returns the commit of each line of the file at `path` in the last
+/// commit.
+pub fn blame(path: &str) -> Option<Vec<Blame>> {
+    let name = Path::new(path).file_name()?.to_str()?;
+    let output = git(
+        Path::new(path),
+        &["blame", "--porcelain", "HEAD", "--", name],
+    )?;
+    Some(parse_blame(&output))
+}
+
+/// Reads the output of `git blame --porcelain`, where the details of a
+/// commit are only given the first time it appears.
+fn parse_blame(output: &str) -> Vec<Blame> {
+    let mut commits: HashMap<String, Blame> = HashMap::new();
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    let mut current: Option<(String, usize)> = None;
+    for line in output.lines() {
+        if line.starts_with('\t') {
+            if let Some((hash, l)) = current.take() {
+                lines.push((l, hash));
+            }
+            continue;
+        }
+        match &current {
+            None => {
+                let mut fields = line.split(' ');
+                let hash = fields.next().unwrap_or_default().to_string();
+                let l = fields.nth(1).and_then(|l| l.parse().ok()).unwrap_or(1);
+                commits.entry(hash.clone()).or_insert_with(|| Blame {
+                    commit: hash.chars().take(7).collect(),
+                    author: String::new(),
+                    time: 0,
+                    summary: String::new(),
+                });
+                current = Some((hash, l));
+            }
+            Some((hash, _)) => {
+                let commit = commits.get_mut(hash).unwrap();
+                let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+                match key {
+                    "author" => commit.author = value.to_string(),
+                    "author-time" => commit.time = value.parse().unwrap_or(0),
+                    "summary" => commit.summary = value.to_string(),
+                    _ => {}
+                }
+            }
+        }
+    }
+    lines.sort();
+    lines
+        .into_iter()
+        .map(|(_, hash)| commits[&hash].clone())
+        .collect()
+}
+
+/// Formats a time in seconds since the Unix epoch as `YYYY-MM-DD`.
+pub fn format_date(time: u64) -> String {
+    // Days to civil date, counting in eras of 400 years from March 1st, 0.
+    let days = time / 86400 + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blame_output() {
+        let output = "\
+4f242279ccd3dde2e0bfa1ed7ead3a13df6fbc97 1 1 2
+author Ada
+author-time 1700000000
+summary First
+boundary
+filename f.txt
+\ta
+4f242279ccd3dde2e0bfa1ed7ead3a13df6fbc97 2 2
+\tb
+9d91be73557b81bb83dfe8e373df340a67cd5dd4 3 3 1
+author Bob
+author-time 0
+summary Second
+previous 4f242279ccd3dde2e0bfa1ed7ead3a13df6fbc97 f.txt
+filename f.txt
+\tc
+";
+        let blame = parse_blame(output);
+        assert_eq!(blame.len(), 3);
+        assert_eq!(blame[1].commit, "4f24227");
+        assert_eq!(blame[1].author, "Ada");
+        assert_eq!(blame[2].summary, "Second");
+        assert_eq!(format_date(blame[0].time), "2023-11-14");
+        assert_eq!(format_date(blame[2].time), "1970-01-01");
+        assert_eq!(format_date(951782400), "2000-02-29");
+    }
+}
@@ -0,0 +1,158 @@
+use crate::files::File;
+
+/// Most lines of the old text shown when hovering a change.
+const MAX_SHOWN_LINES: usize = 5;
+
+/// What a gutter marker tells about its line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum MarkerKind {
+    Error,
+    Warning,
+    Bookmark,
+    Added,
+    Modified,
+    /// Lines were removed just above this one.
+    Removed,
+}
+
+#[derive(Clone)]
+pub(crate) struct Marker {
+    pub line: usize,
+    pub kind: MarkerKind,
+    /// Shown when hovering the marker.
+    pub message: Option<String>,
+}
+
+impl Marker {
+    pub fn new(line: usize, kind: MarkerKind) -> Self {
+        Marker {
+            line,
+            kind,
+            message: None,
+        }
+    }
+
+    pub fn with_message(line: usize, kind: MarkerKind, message: String) -> Self {
+        Marker {
+            line,
+            kind,
+            message: Some(message),
+        }
+    }
+}
+
+/// Something giving markers to show in the gutter of a file.
+pub(crate) trait MarkerProvider {
+    fn markers(&mut self, file: &File) -> Vec<Marker>;
+}
+
+/// The bookmarks of the text.
+pub(crate) struct Bookmarks;
+
+impl MarkerProvider for Bookmarks {
+    fn markers(&mut self, file: &File) -> Vec<Marker> {
+        file.content
+            .bookmarks()
+            .iter()
+            .map(|l| Marker::new(*l, MarkerKind::Bookmark))
+            .collect()
+    }
+}
+
+/// Brackets left unclosed as errors, and indentation mixing tabs and
+/// spaces as warnings.
+pub(crate) struct CodeChecks;
+
+impl MarkerProvider for CodeChecks {
+    fn markers(&mut self, file: &File) -> Vec<Marker> {
+        let content = &file.content;
+        let brackets = content.unmatched_brackets().into_iter().map(|(l, c)| {
+            let bracket = content.char_at((l, c)).unwrap_or_default();
+            let message = format!("Unmatched bracket '{}'", bracket);
+            Marker::with_message(l, MarkerKind::Error, message)
+        });
+        let indentation = content.mixed_indentation_lines().into_iter().map(|l| {
+            let message = "Indentation mixes tabs and spaces".to_string();
+            Marker::with_message(l, MarkerKind::Warning, message)
+        });
+        brackets.chain(indentation).collect()
+    }
+}
+
+/// The lines changed since the last commit when the file is tracked by git,
+/// or else since it was last loaded or saved. Hovering them shows the lines
+/// they replaced.
+pub(crate) struct Changes;
+
+impl MarkerProvider for Changes {
+    fn markers(&mut self, file: &File) -> Vec<Marker> {
+        let Some(base) = file.base() else {
+            return Vec::new();
+        };
+        let mut markers = Vec::new();
+        for hunk in file.changes() {
+            let old = &base[hunk.old_start..hunk.old_start + hunk.old_len];
+            let mut message = old
+                .iter()
+                .take(MAX_SHOWN_LINES)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join("\n");
+            if old.len() > MAX_SHOWN_LINES {
+                message.push_str("\n...");
+            }
+            if hunk.new_len == 0 {
+                let line = file.change_line(hunk);
+                markers.push(Marker::with_message(line, MarkerKind::Removed, message));
+                continue;
+            }
+            for l in hunk.new_start..hunk.new_start + hunk.new_len {
+                markers.push(match hunk.old_len {
+                    0 => Marker::new(l, MarkerKind::Added),
+                    _ => Marker::with_message(l, MarkerKind::Modified, message.clone()),
+                });
+            }
+        }
+        markers
+    }
+}
+
+/// The diagnostics of the language server, on the line where they start.
+pub(crate) struct Diagnostics;
+
+impl MarkerProvider for Diagnostics {
+    fn markers(&mut self, file: &File) -> Vec<Marker> {
+        file.diagnostics
+            .iter()
+            .map(|d| Marker::with_message(d.start.0, d.kind, d.message.clone()))
+            .collect()
+    }
+}
+
+/// Collects the markers of every provider.
+pub(crate) struct Gutter {
+    providers: Vec<Box<dyn MarkerProvider>>,
+}
+
+impl Gutter {
+    pub fn new() -> Self {
+        Gutter {
+            providers: Vec::new(),
+        }
+    }
+
+    pub fn add_provider(&mut self, provider: Box<dyn MarkerProvider>) {
+        self.providers.push(provider);
+    }
+
+    /// Returns the markers of `file`, sorted by line.
+    pub fn markers(&mut self, file: &File) -> Vec<Marker> {
+        let mut markers: Vec<Marker> = self
+            .providers
+            .iter_mut()
+            .flat_map(|p| p.markers(file))
+            .collect();
+        markers.sort_by_key(|m| m.line);
+        markers
+    }
+}
@@ -0,0 +1,183 @@
+use std::path::Path;
+
+/// Editing rules for a kind of file, chosen from its extension.
+pub(crate) struct Language {
+    pub name: &'static str,
+    pub extensions: &'static [&'static str],
+    /// Pairs of opening and closing brackets.
+    pub brackets: &'static [(char, char)],
+    /// Characters that indent the next line when they end a line.
+    pub indent_after: &'static [char],
+    pub line_comment: Option<&'static str>,
+    pub block_comment: Option<(&'static str, &'static str)>,
+    /// Characters delimiting strings, which end with the line.
+    pub string_delimiters: &'static [char],
+    /// Whether `'` starts a character literal, or a lifetime when it is not
+    /// closed right after one character.
+    pub char_literals: bool,
+    /// Pairs closed automatically when their first character is typed.
+    pub auto_pairs: &'static [(char, char)],
+}
+
+const C_LIKE_BRACKETS: &[(char, char)] = &[('{', '}'), ('(', ')'), ('[', ']')];
+const C_LIKE_AUTO_PAIRS: &[(char, char)] =
+    &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+
+pub const PLAIN_TEXT: Language = Language {
+    name: "Plain Text",
+    extensions: &[],
+    brackets: C_LIKE_BRACKETS,
+    indent_after: &[],
+    line_comment: None,
+    block_comment: None,
+    string_delimiters: &[],
+    char_literals: false,
+    auto_pairs: &[('(', ')'), ('[', ']'), ('{', '}')],
+};
+
+const LANGUAGES: &[Language] = &[
+    Language {
+        name: "Rust",
+        extensions: &["rs"],
+        brackets: C_LIKE_BRACKETS,
+        indent_after: &['{', '(', '['],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        string_delimiters: &['"'],
+        char_literals: true,
+        auto_pairs: &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"')],
+    },
+    Language {
+        name: "C",
+        extensions: &["c", "h", "cpp", "hpp", "cc", "cxx"],
+        brackets: C_LIKE_BRACKETS,
+        indent_after: &['{', '(', '['],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        string_delimiters: &['"', '\''],
+        char_literals: false,
+        auto_pairs: C_LIKE_AUTO_PAIRS,
+    },
+    Language {
+        name: "Java",
+        extensions: &["java", "kt", "cs", "scala"],
+        brackets: C_LIKE_BRACKETS,
+        indent_after: &['{', '(', '['],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        string_delimiters: &['"', '\''],
+        char_literals: false,
+        auto_pairs: C_LIKE_AUTO_PAIRS,
+    },
+    Language {
+        name: "JavaScript",
+        extensions: &["js", "jsx", "ts", "tsx", "mjs", "json"],
+        brackets: C_LIKE_BRACKETS,
+        indent_after: &['{', '(', '['],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        string_delimiters: &['"', '\'', '`'],
+        char_literals: false,
+        auto_pairs: C_LIKE_AUTO_PAIRS,
+    },
+    Language {
+        name: "Go",
+        extensions: &["go"],
+        brackets: C_LIKE_BRACKETS,
+        indent_after: &['{', '(', '['],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        string_delimiters: &['"', '\'', '`'],
+        char_literals: false,
+        auto_pairs: C_LIKE_AUTO_PAIRS,
+    },
+    Language {
+        name: "CSS",
+        extensions: &["css", "scss"],
+        brackets: C_LIKE_BRACKETS,
+        indent_after: &['{', '('],
+        line_comment: None,
+        block_comment: Some(("/*", "*/")),
+        string_delimiters: &['"', '\''],
+        char_literals: false,
+        auto_pairs: C_LIKE_AUTO_PAIRS,
+    },
+    Language {
+        name: "Python",
+        extensions: &["py", "pyw"],
+        brackets: C_LIKE_BRACKETS,
+        indent_after: &[':', '{', '(', '['],
+        line_comment: Some("#"),
+        block_comment: Some(("\"\"\"", "\"\"\"")),
+        string_delimiters: &['"', '\''],
+        char_literals: false,
+        auto_pairs: C_LIKE_AUTO_PAIRS,
+    },
+    Language {
+        name: "Shell",
+        extensions: &["sh", "bash", "zsh"],
+        brackets: C_LIKE_BRACKETS,
+        indent_after: &['{', '('],
+        line_comment: Some("#"),
+        block_comment: None,
+        string_delimiters: &['"', '\''],
+        char_literals: false,
+        auto_pairs: C_LIKE_AUTO_PAIRS,
+    },
+    Language {
+        name: "YAML",
+        extensions: &["yml", "yaml"],
+        brackets: &[('{', '}'), ('[', ']')],
+        indent_after: &[':'],
+        line_comment: Some("#"),
+        block_comment: None,
+        string_delimiters: &['"', '\''],
+        char_literals: false,
+        auto_pairs: &[('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')],
+    },
+    Language {
+        name: "TOML",
+        extensions: &["toml"],
+        brackets: &[('{', '}'), ('[', ']')],
+        indent_after: &['{', '['],
+        line_comment: Some("#"),
+        block_comment: None,
+        string_delimiters: &['"', '\''],
+        char_literals: false,
+        auto_pairs: &[('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')],
+    },
+];
+
+impl Language {
+    pub fn from_path(path: Option<&str>) -> &'static Language {
+        let extension = path
+            .and_then(|p| Path::new(p).extension())
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+        match extension {
+            Some(extension) => LANGUAGES
+                .iter()
+                .find(|l| l.extensions.contains(&extension.as_str()))
+                .unwrap_or(&PLAIN_TEXT),
+            None => &PLAIN_TEXT,
+        }
+    }
+
+    pub fn closing_bracket(&self, open: char) -> Option<char> {
+        self.brackets
+            .iter()
+            .find(|(o, _)| *o == open)
+            .map(|(_, c)| *c)
+    }
+
+    pub fn opening_bracket(&self, close: char) -> Option<char> {
+        self.brackets
+            .iter()
+            .find(|(_, c)| *c == close)
+            .map(|(o, _)| *o)
+    }
+
+    pub fn is_closing_bracket(&self, ch: char) -> bool {
+        self.opening_bracket(ch).is_some()
+    }
+}
@@ -0,0 +1,811 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    diff::diff,
+    files::{File, FileContext},
+    gutter::MarkerKind,
+};
+
+mod transport;
+
+/// Time given to a server to exit before it is killed.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// A problem reported by a language server, in character positions.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Diagnostic {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    /// `Error`, or `Warning` for any lesser severity.
+    pub kind: MarkerKind,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Returns the part of each line it covers as `(line, start, end)`.
+    pub fn ranges(&self, lines: &[String]) -> Vec<(usize, usize, usize)> {
+        let (first, last) = (self.start.0, self.end.0.min(lines.len().saturating_sub(1)));
+        (first..=last)
+            .map(|l| {
+                let start = if l == first { self.start.1 } else { 0 };
+                let end = if l == self.end.0 {
+                    self.end.1
+                } else {
+                    lines[l].chars().count()
+                };
+                (l, start, end.max(start))
+            })
+            .collect()
+    }
+}
+
+/// Something a language server sent.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Event {
+    /// The diagnostics of the file at the given path, replacing the previous
+    /// ones.
+    Diagnostics(String, Vec<Diagnostic>),
+    Hover(String),
+    /// Path and position of a definition.
+    Definition(String, (usize, usize)),
+    /// Texts to complete the word before the cursor with.
+    Completion(Vec<String>),
+    /// What went wrong with a server, to tell the user.
+    Error(String),
+}
+
+enum Request {
+    Initialize,
+    Shutdown,
+    Hover,
+    Definition,
+    Completion,
+}
+
+/// The text of a file as last sent to the server.
+struct Document {
+    version: i64,
+    lines: Vec<String>,
+}
+
+/// A language server running in its own process, talking over its standard
+/// input and output. Messages are written by a thread of their own, not to
+/// wait for a server which stopped reading.
+pub(crate) struct LanguageServer {
+    process: Child,
+    outgoing: Sender<Value>,
+    /// Errors of the writing thread.
+    write_errors: Receiver<String>,
+    messages: Receiver<Value>,
+    next_id: u64,
+    pending: HashMap<u64, Request>,
+    initialized: bool,
+    /// Messages waiting for the server to be initialized.
+    queue: Vec<Value>,
+    documents: HashMap<String, Document>,
+    /// The URI of each path, not to look for the file every time.
+    uris: HashMap<String, String>,
+    /// Errors not reported yet.
+    errors: Vec<String>,
+}
+
+/// Returns the identifier of the language called `name` in the protocol.
+fn language_id(name: &str) -> String {
+    match name {
+        "Shell" => "shellscript".to_string(),
+        name => name.to_lowercase(),
+    }
+}
+
+fn absolute_path(path: &str) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| {
+        env::current_dir()
+            .map(|dir| dir.join(path))
+            .unwrap_or(PathBuf::from(path))
+    })
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let mut uri = "file://".to_string();
+    for ch in path.to_string_lossy().chars() {
+        match ch {
+            ' ' | '%' | '#' | '?' => uri.push_str(&format!("%{:02X}", ch as u32)),
+            ch => uri.push(ch),
+        }
+    }
+    uri
+}
+
+fn uri_to_path(uri: &str) -> Option<String> {
+    let path = uri.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::new();
+    let mut i = 0;
+    while i < path.len() {
+        let escaped = path
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (path[i], escaped) {
+            (b'%', Some(byte)) => {
+                bytes.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                bytes.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Converts a column in UTF-16 code units, as used by the protocol, to a
+/// character index in `line`.
+fn utf16_to_char(line: &str, column: usize) -> usize {
+    let mut units = 0;
+    for (c, ch) in line.chars().enumerate() {
+        if units >= column {
+            return c;
+        }
+        units += ch.len_utf16();
+    }
+    line.chars().count()
+}
+
+fn char_to_utf16(line: &str, column: usize) -> usize {
+    line.chars().take(column).map(char::len_utf16).sum()
+}
+
+fn position(lines: &[String], position: (usize, usize)) -> Value {
+    let (l, c) = position;
+    let line = lines.get(l).map_or("", |line| line.as_str());
+    json!({ "line": l, "character": char_to_utf16(line, c) })
+}
+
+/// Reads a position of the protocol as a line and a character index.
+fn read_position(lines: &[String], position: &Value) -> Option<(usize, usize)> {
+    let l = position["line"].as_u64()? as usize;
+    let c = position["character"].as_u64()? as usize;
+    let line = lines.get(l).map_or("", |line| line.as_str());
+    Some((l, utf16_to_char(line, c)))
+}
+
+/// Returns the changes turning `old` into `new`, last ones first so that
+/// each keeps the positions of the next ones valid.
+fn content_changes(old: &[String], new: &[String]) -> Vec<Value> {
+    let end_of = |l: usize| (l, old[l].chars().count());
+    diff(old, new)
+        .into_iter()
+        .rev()
+        .map(|hunk| {
+            let inserted = new[hunk.new_start..hunk.new_start + hunk.new_len].join("\n");
+            let end = hunk.old_start + hunk.old_len;
+            let (start, end, text) = if end < old.len() {
+                let text = if hunk.new_len > 0 {
+                    inserted + "\n"
+                } else {
+                    inserted
+                };
+                ((hunk.old_start, 0), (end, 0), text)
+            } else if hunk.old_start > 0 {
+                // Lines at the end of the text have no following line to end
+                // on, the change starts at the end of the previous line.
+                let text = if hunk.new_len > 0 {
+                    "\n".to_string() + &inserted
+                } else {
+                    inserted
+                };
+                (end_of(hunk.old_start - 1), end_of(old.len() - 1), text)
+            } else {
+                ((0, 0), end_of(old.len() - 1), inserted)
+            };
+            json!({
+                "range": { "start": position(old, start), "end": position(old, end) },
+                "text": text,
+            })
+        })
+        .collect()
+}
+
+/// Returns the text of hover contents, which may be markup, a string, a
+/// code block or a list of them.
+fn hover_text(contents: &Value) -> String {
+    match contents {
+        Value::String(text) => text.clone(),
+        Value::Array(contents) => contents
+            .iter()
+            .map(hover_text)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        contents => contents["value"].as_str().unwrap_or_default().to_string(),
+    }
+}
+
+impl LanguageServer {
+    /// Starts the server run by `command`, working on the files under
+    /// `root`.
+    pub fn start(command: &str, root: &Path) -> io::Result<Self> {
+        let mut words = command.split_whitespace();
+        let program = words
+            .next()
+            .ok_or(io::Error::new(io::ErrorKind::InvalidInput, "Empty command"))?;
+        let mut process = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let mut stdin = process.stdin.take().unwrap();
+        let (outgoing, to_write) = mpsc::channel::<Value>();
+        let (error_sender, write_errors) = mpsc::channel();
+        thread::spawn(move || {
+            for message in to_write {
+                if let Err(e) = transport::write_message(&mut stdin, &message) {
+                    let _ = error_sender.send(format!("Language server error: {}", e));
+                    break;
+                }
+            }
+        });
+        let mut stdout = BufReader::new(process.stdout.take().unwrap());
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || {
+            while let Ok(Some(message)) = transport::read_message(&mut stdout) {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut server = LanguageServer {
+            process,
+            outgoing,
+            write_errors,
+            messages,
+            next_id: 0,
+            pending: HashMap::new(),
+            initialized: false,
+            queue: Vec::new(),
+            documents: HashMap::new(),
+            uris: HashMap::new(),
+            errors: Vec::new(),
+        };
+        let params = json!({
+            "processId": std::process::id(),
+            "rootUri": path_to_uri(root),
+            "capabilities": {
+                "textDocument": {
+                    "synchronization": { "dynamicRegistration": false },
+                    "hover": { "contentFormat": ["plaintext"] },
+                    "definition": {},
+                    "completion": { "completionItem": { "snippetSupport": false } },
+                    "publishDiagnostics": {},
+                },
+            },
+        });
+        server.request("initialize", params, Request::Initialize);
+        Ok(server)
+    }
+
+    /// Hands `message` to the writing thread. Once it stopped on an error,
+    /// the messages are dropped, the error being reported.
+    fn write(&mut self, message: &Value) {
+        let _ = self.outgoing.send(message.clone());
+    }
+
+    /// Sends `message`, or keeps it for later if the server is not
+    /// initialized yet.
+    fn send(&mut self, message: Value) {
+        if self.initialized || message["method"] == "initialize" {
+            self.write(&message);
+        } else {
+            self.queue.push(message);
+        }
+    }
+
+    fn request(&mut self, method: &str, params: Value, request: Request) {
+        self.next_id += 1;
+        self.pending.insert(self.next_id, request);
+        let message = json!({
+            "jsonrpc": "2.0",
+            "id": self.next_id,
+            "method": method,
+            "params": params,
+        });
+        self.send(message);
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn uri(&mut self, path: &str) -> String {
+        (self.uris.entry(path.to_string()))
+            .or_insert_with(|| path_to_uri(&absolute_path(path)))
+            .clone()
+    }
+
+    /// Sends the text of the file at `path` when it was not sent yet, or
+    /// the changes made to it since the last time.
+    pub fn sync(&mut self, path: &str, language: &str, lines: &[String]) {
+        let uri = self.uri(path);
+        let Some(document) = self.documents.get_mut(&uri) else {
+            let params = json!({
+                "textDocument": {
+                    "uri": uri,
+                    "languageId": language_id(language),
+                    "version": 1,
+                    "text": lines.join("\n"),
+                },
+            });
+            self.documents.insert(
+                uri,
+                Document {
+                    version: 1,
+                    lines: lines.to_vec(),
+                },
+            );
+            self.notify("textDocument/didOpen", params);
+            return;
+        };
+        if document.lines == lines {
+            return;
+        }
+        document.version += 1;
+        let params = json!({
+            "textDocument": { "uri": uri, "version": document.version },
+            "contentChanges": content_changes(&document.lines, lines),
+        });
+        document.lines = lines.to_vec();
+        self.notify("textDocument/didChange", params);
+    }
+
+    /// Tells the server the file at `path` is not open anymore, if it was
+    /// sent.
+    pub fn close(&mut self, path: &str) {
+        let uri = self.uri(path);
+        if self.documents.remove(&uri).is_some() {
+            self.notify(
+                "textDocument/didClose",
+                json!({ "textDocument": { "uri": uri } }),
+            );
+        }
+    }
+
+    /// Returns the parameters of a request about `cursor` in the file at
+    /// `path`.
+    fn position_params(&mut self, path: &str, cursor: (usize, usize)) -> Value {
+        let uri = self.uri(path);
+        let lines = self.documents.get(&uri).map_or(&[][..], |d| &d.lines);
+        json!({
+            "textDocument": { "uri": uri },
+            "position": position(lines, cursor),
+        })
+    }
+
+    pub fn hover(&mut self, path: &str, cursor: (usize, usize)) {
+        let params = self.position_params(path, cursor);
+        self.request("textDocument/hover", params, Request::Hover);
+    }
+
+    pub fn definition(&mut self, path: &str, cursor: (usize, usize)) {
+        let params = self.position_params(path, cursor);
+        self.request("textDocument/definition", params, Request::Definition);
+    }
+
+    pub fn completion(&mut self, path: &str, cursor: (usize, usize)) {
+        let params = self.position_params(path, cursor);
+        self.request("textDocument/completion", params, Request::Completion);
+    }
+
+    /// Returns the lines of the file at `uri`, as sent to the server or else
+    /// as on disk.
+    fn lines_of(&self, uri: &str) -> Vec<String> {
+        match self.documents.get(uri) {
+            Some(document) => document.lines.clone(),
+            None => uri_to_path(uri)
+                .and_then(|path| fs::read_to_string(path).ok())
+                .map(|text| text.split('\n').map(String::from).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn diagnostics(&self, params: &Value) -> Option<Event> {
+        let uri = params["uri"].as_str()?;
+        let lines = self.lines_of(uri);
+        let diagnostics = params["diagnostics"]
+            .as_array()?
+            .iter()
+            .filter_map(|d| {
+                Some(Diagnostic {
+                    start: read_position(&lines, &d["range"]["start"])?,
+                    end: read_position(&lines, &d["range"]["end"])?,
+                    kind: match d["severity"].as_u64() {
+                        Some(1) | None => MarkerKind::Error,
+                        _ => MarkerKind::Warning,
+                    },
+                    message: d["message"].as_str().unwrap_or_default().to_string(),
+                })
+            })
+            .collect();
+        Some(Event::Diagnostics(uri_to_path(uri)?, diagnostics))
+    }
+
+    fn definition_event(&self, result: &Value) -> Option<Event> {
+        let location = match result {
+            Value::Array(locations) => locations.first()?,
+            location => location,
+        };
+        let uri = location["uri"]
+            .as_str()
+            .or(location["targetUri"].as_str())?;
+        let start = if location["range"].is_object() {
+            &location["range"]["start"]
+        } else {
+            &location["targetSelectionRange"]["start"]
+        };
+        let position = read_position(&self.lines_of(uri), start)?;
+        Some(Event::Definition(uri_to_path(uri)?, position))
+    }
+
+    fn completion_event(result: &Value) -> Option<Event> {
+        let items = match result {
+            Value::Array(items) => items,
+            list => list["items"].as_array()?,
+        };
+        let texts = items
+            .iter()
+            .filter_map(|item| {
+                item["textEdit"]["newText"]
+                    .as_str()
+                    .or(item["insertText"].as_str())
+                    .or(item["label"].as_str())
+                    .map(String::from)
+            })
+            .collect();
+        Some(Event::Completion(texts))
+    }
+
+    fn response(&mut self, request: Request, result: &Value) -> Option<Event> {
+        match request {
+            Request::Initialize => {
+                self.initialized = true;
+                self.write(&json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }));
+                for message in std::mem::take(&mut self.queue) {
+                    self.write(&message);
+                }
+                None
+            }
+            Request::Shutdown => None,
+            Request::Hover => match hover_text(&result["contents"]) {
+                text if text.trim().is_empty() => None,
+                text => Some(Event::Hover(text)),
+            },
+            Request::Definition => self.definition_event(result),
+            Request::Completion => Self::completion_event(result),
+        }
+    }
+
+    /// Handles the messages received since the last call, and returns what
+    /// they tell.
+    pub fn poll(&mut self) -> Vec<Event> {
+        let mut events: Vec<Event> = self.errors.drain(..).map(Event::Error).collect();
+        events.extend(self.write_errors.try_iter().map(Event::Error));
+        while let Ok(message) = self.messages.try_recv() {
+            let method = message["method"].as_str();
+            let event = match (message["id"].as_u64(), method) {
+                (Some(id), None) => match self.pending.remove(&id) {
+                    Some(_) if message["error"].is_object() => Some(Event::Error(format!(
+                        "Language server error: {}",
+                        message["error"]["message"]
+                            .as_str()
+                            .unwrap_or("unknown error")
+                    ))),
+                    Some(request) => self.response(request, &message["result"]),
+                    None => None,
+                },
+                // Requests from the server are answered without doing
+                // anything.
+                (_, Some(_)) if !message["id"].is_null() => {
+                    let id = message["id"].clone();
+                    self.write(&json!({ "jsonrpc": "2.0", "id": id, "result": null }));
+                    None
+                }
+                (_, Some("textDocument/publishDiagnostics")) => {
+                    self.diagnostics(&message["params"])
+                }
+                _ => None,
+            };
+            events.extend(event);
+        }
+        events
+    }
+
+    /// Asks the server to exit, and kills it if it takes too long.
+    pub fn shutdown(&mut self) {
+        if self.initialized {
+            self.request("shutdown", Value::Null, Request::Shutdown);
+            self.notify("exit", Value::Null);
+        }
+        let start = Instant::now();
+        while start.elapsed() < SHUTDOWN_TIMEOUT {
+            if let Ok(Some(_)) = self.process.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+/// The language servers of the open files, each started the first time a
+/// file of its language is synced.
+pub(crate) struct Lsp {
+    /// Command running the server of each language.
+    commands: HashMap<String, String>,
+    servers: HashMap<String, LanguageServer>,
+    /// The revision of the text of each file last sent, by path.
+    synced: HashMap<String, u64>,
+    /// Servers which could not start, not reported yet.
+    errors: Vec<String>,
+}
+
+impl Lsp {
+    pub fn new(commands: HashMap<String, String>) -> Self {
+        Lsp {
+            commands,
+            servers: HashMap::new(),
+            synced: HashMap::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Returns the server for the language of `file`, starting it if needed.
+    /// A server failing to start is not tried again.
+    fn server(&mut self, file: &File) -> Option<&mut LanguageServer> {
+        let language = file.content.get_language().name;
+        if !self.servers.contains_key(language) {
+            let command = self.commands.remove(language)?;
+            let root = env::current_dir().unwrap_or_default();
+            match LanguageServer::start(&command, &root) {
+                Ok(server) => {
+                    self.servers.insert(language.to_string(), server);
+                }
+                Err(e) => (self.errors).push(format!("Could not start {}: {}", command, e)),
+            }
+        }
+        self.servers.get_mut(language)
+    }
+
+    /// Sends the changes made to the files to their servers, and tells them
+    /// about the paths not open anymore.
+    pub fn sync(&mut self, files: &FileContext) {
+        for file in files.files() {
+            if let Some(path) = &file.path {
+                self.sync_file(file, path);
+            }
+        }
+        let closed: Vec<String> = (self.synced.keys())
+            .filter(|path| !(files.files().iter()).any(|f| f.path.as_ref() == Some(*path)))
+            .cloned()
+            .collect();
+        for path in closed {
+            self.synced.remove(&path);
+            for server in self.servers.values_mut() {
+                server.close(&path);
+            }
+        }
+    }
+
+    /// Sends the text of `file`, loaded from `path`, to its server unless it
+    /// did not change since the last time.
+    fn sync_file(&mut self, file: &File, path: &str) {
+        let revision = file.content.revision();
+        if self.synced.get(path) == Some(&revision) {
+            return;
+        }
+        let language = file.content.get_language().name;
+        if let Some(server) = self.server(file) {
+            server.sync(path, language, &file.content.get_text());
+            self.synced.insert(path.to_string(), revision);
+        }
+    }
+
+    /// Gives the files the diagnostics received, and returns the other
+    /// events.
+    pub fn poll(&mut self, files: &mut FileContext) -> Vec<Event> {
+        let mut events: Vec<Event> = self.errors.drain(..).map(Event::Error).collect();
+        for server in self.servers.values_mut() {
+            for event in server.poll() {
+                match event {
+                    Event::Diagnostics(path, diagnostics) => {
+                        if let Some(file) = files.find(&path) {
+                            files.files_mut()[file].diagnostics = diagnostics;
+                        }
+                    }
+                    event => events.push(event),
+                }
+            }
+        }
+        events
+    }
+
+    /// Runs `f` with the server of `file` and its path, and returns whether
+    /// it has them.
+    fn with_server<F: FnOnce(&mut LanguageServer, &str)>(&mut self, file: &File, f: F) -> bool {
+        let Some(path) = file.path.clone() else {
+            return false;
+        };
+        self.sync_file(file, &path);
+        let Some(server) = self.server(file) else {
+            return false;
+        };
+        f(server, &path);
+        true
+    }
+
+    pub fn hover(&mut self, file: &File) {
+        let cursor = file.content.get_cursor();
+        self.with_server(file, |server, path| server.hover(path, cursor));
+    }
+
+    pub fn definition(&mut self, file: &File) {
+        let cursor = file.content.get_cursor();
+        self.with_server(file, |server, path| server.definition(path, cursor));
+    }
+
+    /// Asks for the completions at the cursor of `file`, returns false when
+    /// it has no server.
+    pub fn completion(&mut self, file: &File) -> bool {
+        let cursor = file.content.get_cursor();
+        self.with_server(file, |server, path| server.completion(path, cursor))
+    }
+
+    pub fn shutdown(&mut self) {
+        for server in self.servers.values_mut() {
+            server.shutdown();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split('\n').map(String::from).collect()
+    }
+
+    /// Applies changes like a server would.
+    fn apply(text: &str, changes: &[Value]) -> String {
+        let mut lines = lines(text);
+        for change in changes {
+            let start = read_position(&lines, &change["range"]["start"]).unwrap();
+            let end = read_position(&lines, &change["range"]["end"]).unwrap();
+            let offset = |(l, c): (usize, usize)| {
+                lines[..l]
+                    .iter()
+                    .map(|l| l.chars().count() + 1)
+                    .sum::<usize>()
+                    + c
+            };
+            let mut chars: Vec<char> = lines.join("\n").chars().collect();
+            chars.splice(
+                offset(start)..offset(end),
+                change["text"].as_str().unwrap().chars(),
+            );
+            lines = self::lines(&chars.iter().collect::<String>());
+        }
+        lines.join("\n")
+    }
+
+    #[test]
+    fn incremental_changes() {
+        for (old, new) in [
+            ("a\nb\nc", "a\nx\nc"),
+            ("a\nb\nc", "a\nb"),
+            ("a\nb", "a\nb\nc\nd"),
+            ("a\nb\nc", "x"),
+            ("é\nb\nc", "é\nc\n"),
+            ("a\nb\nc\nd", "b\nc\nd\ne"),
+        ] {
+            let changes = content_changes(&lines(old), &lines(new));
+            assert_eq!(apply(old, &changes), new);
+        }
+        assert_eq!(utf16_to_char("a😀b", 3), 2);
+        assert_eq!(char_to_utf16("a😀b", 2), 3);
+    }
+
+    /// Path of the fake server built from `examples/fake_lsp_server.rs`.
+    fn fake_server() -> PathBuf {
+        let exe = env::current_exe().unwrap();
+        let profile_dir = exe.parent().unwrap().parent().unwrap();
+        let path = profile_dir.join("examples").join("fake_lsp_server");
+        assert!(path.exists(), "build the fake server with `cargo test`");
+        path
+    }
+
+    /// Polls `server` until it sends an event matching `f`.
+    fn wait_for<F: Fn(&Event) -> bool>(server: &mut LanguageServer, f: F) -> Event {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if let Some(event) = server.poll().into_iter().find(|e| f(e)) {
+                return event;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("no answer from the fake server");
+    }
+
+    #[test]
+    fn fake_server_session() {
+        let dir = env::temp_dir().join(format!("lsp_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("main.rs").to_string_lossy().to_string();
+        fs::write(&path, "").unwrap();
+        let command = fake_server().to_string_lossy().to_string();
+        let mut server = LanguageServer::start(&command, &dir).unwrap();
+
+        server.sync(&path, "Rust", &lines("let value = 1;\nlet other = val"));
+        server.sync(
+            &path,
+            "Rust",
+            &lines("let value = 1;\nerror\nlet other = val"),
+        );
+        let event = wait_for(
+            &mut server,
+            |e| matches!(e, Event::Diagnostics(_, d) if !d.is_empty()),
+        );
+        let Event::Diagnostics(diagnostic_path, diagnostics) = event else {
+            unreachable!()
+        };
+        assert_eq!(absolute_path(&diagnostic_path), absolute_path(&path));
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic {
+                start: (1, 0),
+                end: (1, 5),
+                kind: MarkerKind::Error,
+                message: "unexpected error".to_string(),
+            }]
+        );
+
+        server.hover(&path, (0, 6));
+        let event = wait_for(&mut server, |e| matches!(e, Event::Hover(_)));
+        assert_eq!(event, Event::Hover("value".to_string()));
+
+        server.definition(&path, (2, 13));
+        let event = wait_for(&mut server, |e| matches!(e, Event::Definition(..)));
+        let Event::Definition(definition_path, position) = event else {
+            unreachable!()
+        };
+        assert_eq!(absolute_path(&definition_path), absolute_path(&path));
+        assert_eq!(position, (0, 4));
+
+        server.completion(&path, (2, 15));
+        let event = wait_for(&mut server, |e| matches!(e, Event::Completion(_)));
+        assert_eq!(event, Event::Completion(vec!["value".to_string()]));
+
+        server.close(&path);
+        server.hover(&path, (0, 6));
+        let event = wait_for(&mut server, |e| matches!(e, Event::Error(_)));
+        assert_eq!(
+            event,
+            Event::Error("Language server error: unknown document".to_string())
+        );
+
+        server.shutdown();
+        assert!(server.process.try_wait().unwrap().is_some());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
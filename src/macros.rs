@@ -0,0 +1,273 @@
+use std::{collections::HashMap, fs, io};
+
+use crate::config::config_dir;
+
+/// A key press or a typed text, as received from the window.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Input {
+    /// The key code and the bits of the modifiers held.
+    Key(i32, u16),
+    Text(String),
+}
+
+/// What the next typed character is taken for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Naming {
+    /// The register to record in.
+    Recording,
+    /// The register to replay, after the number of times typed so far.
+    Replay(usize),
+}
+
+/// Most times a macro is replayed at once.
+const MAX_REPLAYS: usize = 1000;
+
+/// Macros recorded in registers named by a character, kept in `macros` in
+/// the configuration directory to be reused across sessions.
+pub(crate) struct Macros {
+    registers: HashMap<char, Vec<Input>>,
+    /// The register being recorded and the inputs recorded so far.
+    recording: Option<(char, Vec<Input>)>,
+    naming: Option<Naming>,
+    /// The register replayed last.
+    last: Option<char>,
+}
+
+/// Reads macros written as
+///
+/// ```text
+/// macro <register>
+/// key <key code> <modifiers>
+/// text <text>
+/// endmacro
+/// ```
+///
+/// ignoring invalid lines.
+fn parse(text: &str) -> HashMap<char, Vec<Input>> {
+    let mut registers = HashMap::new();
+    let mut current: Option<(char, Vec<Input>)> = None;
+    for line in text.lines() {
+        match current.as_mut() {
+            Some((register, inputs)) => {
+                if line.trim_end() == "endmacro" {
+                    registers.insert(*register, std::mem::take(inputs));
+                    current = None;
+                } else if let Some(text) = line.strip_prefix("text ") {
+                    inputs.push(Input::Text(text.to_string()));
+                } else if let Some(key) = line.strip_prefix("key ") {
+                    let mut numbers = key.split_whitespace();
+                    if let (Some(Ok(keycode)), Some(Ok(keymod))) = (
+                        numbers.next().map(str::parse),
+                        numbers.next().map(str::parse),
+                    ) {
+                        inputs.push(Input::Key(keycode, keymod));
+                    }
+                }
+            }
+            None => {
+                let register = line
+                    .strip_prefix("macro ")
+                    .and_then(|name| name.chars().next());
+                if let Some(register) = register {
+                    current = Some((register, Vec::new()));
+                }
+            }
+        }
+    }
+    registers
+}
+
+fn format(registers: &HashMap<char, Vec<Input>>) -> String {
+    let mut names: Vec<&char> = registers.keys().collect();
+    names.sort();
+    let mut text = String::new();
+    for name in names {
+        text.push_str(&format!("macro {}\n", name));
+        for input in &registers[name] {
+            match input {
+                Input::Key(keycode, keymod) => {
+                    text.push_str(&format!("key {} {}\n", keycode, keymod))
+                }
+                Input::Text(typed) => text.push_str(&format!("text {}\n", typed)),
+            }
+        }
+        text.push_str("endmacro\n");
+    }
+    text
+}
+
+impl Macros {
+    pub fn new() -> Self {
+        Macros {
+            registers: HashMap::new(),
+            recording: None,
+            naming: None,
+            last: None,
+        }
+    }
+
+    /// Reads the macros saved in the configuration directory.
+    pub fn load() -> Self {
+        let mut macros = Self::new();
+        let text = config_dir().and_then(|dir| fs::read_to_string(dir.join("macros")).ok());
+        macros.registers = parse(&text.unwrap_or_default());
+        macros
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(dir) = config_dir() else {
+            return Ok(());
+        };
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("macros"), format(&self.registers))
+    }
+
+    /// Waits for the name of the register to record in.
+    pub fn start_recording(&mut self) {
+        self.naming = Some(Naming::Recording);
+    }
+
+    /// Stores the inputs recorded in their register, and returns false when
+    /// nothing was being recorded.
+    pub fn stop_recording(&mut self) -> bool {
+        let Some((register, inputs)) = self.recording.take() else {
+            return false;
+        };
+        self.registers.insert(register, inputs);
+        true
+    }
+
+    /// Waits for the number of times to replay a macro, then for the name of
+    /// its register.
+    pub fn ask_replay(&mut self) {
+        self.naming = Some(Naming::Replay(0));
+    }
+
+    /// Returns whether the next typed character names a register.
+    pub fn is_naming(&self) -> bool {
+        self.naming.is_some()
+    }
+
+    pub fn cancel(&mut self) {
+        self.naming = None;
+    }
+
+    /// Takes `ch` as the name of the register asked for, or as a digit of the
+    /// number of times to replay it, and returns the inputs to replay.
+    pub fn name(&mut self, ch: char) -> Vec<Input> {
+        match self.naming.take() {
+            Some(Naming::Recording) => {
+                self.recording = Some((ch, Vec::new()));
+                Vec::new()
+            }
+            Some(Naming::Replay(count)) => match ch.to_digit(10) {
+                Some(digit) => {
+                    let count = count.saturating_mul(10).saturating_add(digit as usize);
+                    self.naming = Some(Naming::Replay(count.min(MAX_REPLAYS)));
+                    Vec::new()
+                }
+                None => {
+                    self.last = Some(ch);
+                    self.replay(ch, count.max(1))
+                }
+            },
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns the inputs to replay the last replayed macro again.
+    pub fn replay_last(&self) -> Vec<Input> {
+        self.last
+            .map_or(Vec::new(), |register| self.replay(register, 1))
+    }
+
+    fn replay(&self, register: char, count: usize) -> Vec<Input> {
+        self.registers.get(&register).map_or(Vec::new(), |inputs| {
+            let count = count.min(MAX_REPLAYS);
+            (0..count).flat_map(|_| inputs.iter().cloned()).collect()
+        })
+    }
+
+    /// Adds `input` to the macro being recorded.
+    pub fn record(&mut self, input: Input) {
+        if let Some((_, inputs)) = self.recording.as_mut() {
+            inputs.push(input);
+        }
+    }
+
+    /// Describes the register being recorded or asked for.
+    pub fn status(&self) -> Option<String> {
+        match (self.naming, &self.recording) {
+            (Some(Naming::Recording), _) => Some("Record macro in register:".to_string()),
+            (Some(Naming::Replay(0)), _) => Some("Replay macro in register:".to_string()),
+            (Some(Naming::Replay(count)), _) => {
+                Some(format!("Replay macro {} times in register:", count))
+            }
+            (None, Some((register, _))) => Some(format!("Recording macro {}", register)),
+            (None, None) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_macros() {
+        let registers = HashMap::from([
+            (
+                'a',
+                vec![
+                    Input::Key(13, 0x40),
+                    Input::Text(" ".to_string()),
+                    Input::Text("é".to_string()),
+                ],
+            ),
+            ('1', Vec::new()),
+        ]);
+        let text = format(&registers);
+        assert_eq!(
+            text,
+            "macro 1\nendmacro\nmacro a\nkey 13 64\ntext  \ntext é\nendmacro\n"
+        );
+        assert_eq!(parse(&text), registers);
+        assert_eq!(
+            parse("macro b\nkey x 0\nkey 97 0\n\nendmacro\nkey 1 1"),
+            HashMap::from([('b', vec![Input::Key(97, 0)])])
+        );
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let mut macros = Macros::new();
+        macros.record(Input::Text("x".to_string()));
+        macros.start_recording();
+        assert!(macros.is_naming() && macros.recording.is_none());
+        assert!(macros.name('q').is_empty());
+        macros.record(Input::Key(8, 0));
+        macros.record(Input::Text("y".to_string()));
+        assert!(macros.stop_recording());
+        assert!(!macros.stop_recording());
+        let inputs = vec![Input::Key(8, 0), Input::Text("y".to_string())];
+        assert!(macros.replay_last().is_empty());
+        macros.ask_replay();
+        assert!(macros.name('1').is_empty());
+        assert!(macros.name('2').is_empty());
+        assert_eq!(
+            macros.name('q'),
+            inputs.iter().cycle().take(24).cloned().collect::<Vec<_>>()
+        );
+        assert!(!macros.is_naming());
+        assert_eq!(macros.replay_last(), inputs);
+        macros.ask_replay();
+        assert!(macros.name('z').is_empty());
+        assert!(macros.replay_last().is_empty());
+        macros.ask_replay();
+        for _ in 0..30 {
+            assert!(macros.name('9').is_empty());
+        }
+        assert_eq!(macros.naming, Some(Naming::Replay(MAX_REPLAYS)));
+        assert_eq!(macros.name('q').len(), MAX_REPLAYS * inputs.len());
+    }
+}
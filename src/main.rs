@@ -1,84 +1,684 @@
 extern crate sdl2;
 
+use std::{collections::VecDeque, env, path::Path};
+
+use config::{Config, Keys};
+use emacs::Emacs;
 use files::{File, FileContext};
+use gutter::{Bookmarks, Changes, CodeChecks, Diagnostics, Gutter};
+use language::Language;
+use lsp::Lsp;
+use macros::{Input, Macros};
+use sdl2::clipboard::ClipboardUtil;
 use sdl2::event::{Event, WindowEvent};
 use sdl2::keyboard::{Keycode, Mod};
 use sdl2::mouse::MouseButton;
 use sdl2::render::BlendMode::Blend;
 use sdl2::ttf::{self};
-use text_zone::TextContent;
+use session::{Geometry, Session};
+use snippets::Snippets;
+use swap::{Answer, Swap, Swaps};
+use text_zone::{SelectionMode, TextContent};
 use timer::Timer;
-use vue::Vue;
+use vim::Vim;
+use vue::{ContentSnapshot, LineNumberMode, Orientation, Vue};
 
+mod commands;
+mod config;
+mod diff;
+mod emacs;
 mod files;
+mod git;
+mod gutter;
+mod language;
+mod lsp;
+mod macros;
 mod save_load;
+mod session;
+mod snippets;
+mod swap;
 mod text_zone;
 mod timer;
+mod vim;
 mod vue;
+mod words;
+
+/// Length of the word being typed from which completions are offered.
+const MIN_COMPLETED_PREFIX: usize = 2;
+
+fn selecting(keycode: Keycode, keymod: Mod, text_content: &mut TextContent) -> bool {
+    if !keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+        return false;
+    }
+    let mode = if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) {
+        SelectionMode::Block
+    } else {
+        SelectionMode::Linear
+    };
+    match keycode {
+        Keycode::Up => text_content.extend_selection_up(mode),
+        Keycode::Down => text_content.extend_selection_down(mode),
+        Keycode::Left => text_content.extend_selection_left(mode),
+        Keycode::Right => text_content.extend_selection_right(mode),
+        _ => return false,
+    }
+    true
+}
+
+fn clipboard(
+    keycode: Keycode,
+    keymod: Mod,
+    text_content: &mut TextContent,
+    clipboard: &ClipboardUtil,
+    vue: &mut Vue,
+) -> bool {
+    if !keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) {
+        return false;
+    }
+    match keycode {
+        Keycode::C => {
+            if let Some(text) = text_content.get_selected_text() {
+                if let Err(e) = clipboard.set_clipboard_text(&text) {
+                    vue.show_info(Some(format!("Could not copy: {}", e)));
+                }
+            }
+        }
+        Keycode::X => {
+            // The text is cut only once it is in the clipboard.
+            if let Some(text) = text_content.get_selected_text() {
+                match clipboard.set_clipboard_text(&text) {
+                    Ok(()) => {
+                        text_content.cut();
+                    }
+                    Err(e) => vue.show_info(Some(format!("Could not cut: {}", e))),
+                }
+            }
+        }
+        Keycode::V => {
+            if let Ok(text) = clipboard.clipboard_text() {
+                text_content.paste(text.replace("\r\n", "\n"));
+            }
+        }
+        _ => return false,
+    }
+    true
+}
 
-fn text_editing(keycode: Keycode, keymod: Mod, text_content: &mut TextContent) -> bool {
+/// Moves the cursors by visual row when lines are wrapped.
+fn wrapped_moving(
+    keycode: Keycode,
+    keymod: Mod,
+    text_content: &mut TextContent,
+    vue: &Vue,
+) -> bool {
+    if !vue.is_wrapping()
+        || keymod.intersects(
+            Mod::LCTRLMOD
+                | Mod::RCTRLMOD
+                | Mod::LALTMOD
+                | Mod::RALTMOD
+                | Mod::LSHIFTMOD
+                | Mod::RSHIFTMOD,
+        )
+    {
+        return false;
+    }
+    let n = match keycode {
+        Keycode::Up => -1,
+        Keycode::Down => 1,
+        _ => return false,
+    };
+    text_content.move_cursors_to(|cursor| vue.move_by_rows(cursor, n));
+    true
+}
+
+fn text_editing(
+    keycode: Keycode,
+    keymod: Mod,
+    text_content: &mut TextContent,
+    snippets: &Snippets,
+    file_name: &str,
+) -> bool {
     match keycode {
         Keycode::Return if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
             text_content.new_line()
         }
         Keycode::Return => text_content.break_line(),
+        Keycode::Up
+            if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+                && keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) =>
+        {
+            text_content.add_cursor_above()
+        }
+        Keycode::Down
+            if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+                && keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) =>
+        {
+            text_content.add_cursor_below()
+        }
+        Keycode::D
+            if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+                && keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) =>
+        {
+            text_content.duplicate_lines()
+        }
+        Keycode::K
+            if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+                && keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) =>
+        {
+            text_content.delete_lines()
+        }
+        Keycode::J if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => text_content.join_lines(),
+        Keycode::F9 if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) => {
+            text_content.reverse_lines()
+        }
+        Keycode::F9 if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+            text_content.unique_lines()
+        }
+        Keycode::F9 if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) => {
+            text_content.trim_trailing_whitespace()
+        }
+        Keycode::F9 => text_content.sort_lines(),
+        Keycode::D if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+            text_content.add_cursor_at_next_occurrence()
+        }
+        Keycode::Backslash
+            if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+                && keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) =>
+        {
+            text_content.jump_to_matching_bracket()
+        }
+        Keycode::Slash
+            if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+                && keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) =>
+        {
+            text_content.toggle_block_comment()
+        }
+        Keycode::Slash if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+            text_content.toggle_line_comment()
+        }
+        Keycode::LeftBracket
+            if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+                && keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) =>
+        {
+            text_content.fold()
+        }
+        Keycode::RightBracket
+            if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+                && keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) =>
+        {
+            text_content.unfold()
+        }
+        Keycode::Escape => {
+            text_content.end_snippet();
+            text_content.clear_selection();
+            text_content.clear_secondary_cursors()
+        }
         Keycode::Up if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) => {
             text_content.move_line_up()
         }
         Keycode::Down if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) => {
             text_content.move_line_down()
         }
-        Keycode::Up => text_content.move_cursor_up(1),
-        Keycode::Down => text_content.move_cursor_down(1),
-        Keycode::Left => text_content.move_cursor_left(1),
-        Keycode::Right => text_content.move_cursor_right(1),
+        Keycode::Up => text_content.move_cursors_up(1),
+        Keycode::Down => text_content.move_cursors_down(1),
+        Keycode::Left => text_content.move_cursors_left(1),
+        Keycode::Right => text_content.move_cursors_right(1),
         Keycode::Backspace => text_content.remove(),
-        Keycode::Tab => text_content.append("    ".to_string()),
+        Keycode::Tab if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) => {
+            if !text_content.previous_snippet_field() {
+                text_content.outdent_lines()
+            }
+        }
+        Keycode::Tab => {
+            if !text_content.next_snippet_field() && !snippets.expand(text_content, file_name) {
+                text_content.indent()
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Moves between the changes of the file and reverts them.
+fn changes(keycode: Keycode, keymod: Mod, file: &mut File) -> bool {
+    match keycode {
+        Keycode::F5 if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) => {
+            if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                file.previous_change()
+            } else {
+                file.next_change()
+            }
+        }
+        Keycode::R
+            if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+                && keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) =>
+        {
+            file.revert_change()
+        }
         _ => return false,
     }
-    return true;
+    true
 }
 
-fn command(keycode: Keycode, keymod: Mod, files: &mut FileContext) -> bool {
+/// Runs the command `name` asking the language server of the current file
+/// about the cursor, returns false for other commands. Without a server,
+/// completions come from the words of the open files.
+fn language_server(name: &str, lsp: &mut Lsp, files: &mut FileContext, vue: &mut Vue) -> bool {
+    match name {
+        "go_to_definition" => lsp.definition(files.current()),
+        "hover" => lsp.hover(files.current()),
+        "complete" => {
+            if !lsp.completion(files.current()) {
+                vue.show_completion(files.word_completions());
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Moves in the list of completions, and completes the word before the
+/// cursors with the chosen one. Other keys close the list, as Tab does when
+/// it goes to a snippet.
+fn completing(
+    keycode: Keycode,
+    vue: &mut Vue,
+    content: &mut TextContent,
+    snippets: &Snippets,
+) -> bool {
+    if !vue.is_completing() {
+        return false;
+    }
     match keycode {
-        Keycode::S if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
-            let current_file = files.current();
-            let opt_path = current_file.path.clone();
-            if !keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) && opt_path.is_some() {
-                save_load::save(&files.current().content.get_string(), &opt_path.unwrap()).unwrap();
-            } else if let Some(path) = save_load::select_save_file() {
-                save_load::save(&files.current().content.get_string(), &path).unwrap();
+        Keycode::Tab if snippets.takes_tab(content) => {
+            vue.hide_completion();
+            return false;
+        }
+        Keycode::Up => vue.select_next_completion(-1),
+        Keycode::Down => vue.select_next_completion(1),
+        Keycode::Tab | Keycode::Return => {
+            if let Some(word) = vue.selected_completion().map(String::from) {
+                content.complete_word(&word);
+            }
+            vue.hide_completion();
+        }
+        Keycode::Escape => vue.hide_completion(),
+        _ => {
+            vue.hide_completion();
+            return false;
+        }
+    }
+    true
+}
+
+fn view(keycode: Keycode, keymod: Mod, vue: &mut Vue) -> bool {
+    match keycode {
+        Keycode::Z if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) => {
+            vue.set_wrap(!vue.is_wrapping())
+        }
+        Keycode::B if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) => {
+            vue.show_blame(!vue.shows_blame())
+        }
+        Keycode::L if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) => {
+            vue.set_line_number_mode(match vue.line_number_mode() {
+                LineNumberMode::Absolute => LineNumberMode::Relative,
+                LineNumberMode::Relative => LineNumberMode::Hybrid,
+                LineNumberMode::Hybrid => LineNumberMode::Absolute,
+            })
+        }
+        Keycode::G if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) => {
+            vue.show_line_numbers(!vue.shows_line_numbers())
+        }
+        _ => return false,
+    }
+    true
+}
+
+fn panes(keycode: Keycode, keymod: Mod, vue: &mut Vue, files: &mut FileContext) -> bool {
+    match keycode {
+        Keycode::Backslash if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+            let orientation = if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) {
+                Orientation::Vertical
+            } else {
+                Orientation::Horizontal
+            };
+            vue.split(orientation);
+            let view = files.split();
+            files.focus(view);
+        }
+        Keycode::F6 => {
+            let n = if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                -1
+            } else {
+                1
+            };
+            files.focus(vue.focus_next_pane(n));
+        }
+        Keycode::W
+            if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+                && keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) =>
+        {
+            if vue.close_pane().is_some() {
+                files.close_view(vue.focused_pane());
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Starts and stops recording macros, and replays them by putting their
+/// inputs in front of the events left to handle. Keys are ignored while a
+/// register is being named.
+fn macro_keys(
+    keycode: Keycode,
+    keymod: Mod,
+    macros: &mut Macros,
+    events: &mut VecDeque<Event>,
+    vue: &mut Vue,
+) -> bool {
+    match keycode {
+        Keycode::Escape if macros.is_naming() => macros.cancel(),
+        _ if macros.is_naming() => {}
+        Keycode::F7 => {
+            if !macros.stop_recording() {
+                macros.start_recording();
+            } else if let Err(e) = macros.save() {
+                vue.show_info(Some(format!("Could not save the macros: {}", e)));
+            }
+        }
+        Keycode::F8 if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) => {
+            replay(macros.replay_last(), events)
+        }
+        Keycode::F8 => macros.ask_replay(),
+        _ => return false,
+    }
+    true
+}
+
+fn replay(inputs: Vec<Input>, events: &mut VecDeque<Event>) {
+    for input in inputs.into_iter().rev() {
+        events.push_front(match input {
+            Input::Key(keycode, keymod) => Event::KeyDown {
+                timestamp: 0,
+                window_id: 0,
+                keycode: Keycode::from_i32(keycode),
+                scancode: None,
+                keymod: Mod::from_bits_truncate(keymod),
+                repeat: false,
+            },
+            Input::Text(text) => Event::TextInput {
+                timestamp: 0,
+                window_id: 0,
+                text,
+            },
+        });
+    }
+}
+
+fn snapshot(file: &File, gutter: &mut Gutter) -> ContentSnapshot {
+    let content = &file.content;
+    let lines = content.get_text();
+    let cursor_line = content.get_cursor().0;
+    let underlines = file
+        .diagnostics
+        .iter()
+        .flat_map(|d| {
+            d.ranges(&lines)
+                .into_iter()
+                .map(|(l, start, end)| (l, start, end, d.kind))
+        })
+        .collect();
+    ContentSnapshot {
+        lines,
+        size: content.size(),
+        cursors: content.get_cursors(),
+        selections: content.selection_ranges(),
+        tab_width: content.get_tab_width(),
+        brackets: content
+            .matching_brackets()
+            .map_or(Vec::new(), |(a, b)| vec![a, b]),
+        folds: content.folds(),
+        markers: gutter.markers(file),
+        git_status: file.git_status().cloned(),
+        blame: file.blame_at(cursor_line),
+        underlines,
+        message: file
+            .diagnostics
+            .iter()
+            .find(|d| (d.start.0..=d.end.0).contains(&cursor_line))
+            .map(|d| d.message.clone()),
+        mode: None,
+        block_cursor: false,
+    }
+}
+
+/// Shows the file at `path`, loading it if it is not open yet.
+fn open_file(path: String, files: &mut FileContext, config: &Config, vue: &mut Vue) {
+    if let Some(index) = files.find(&path) {
+        files.select(index);
+        return;
+    }
+    match load_file(path, config) {
+        Ok(file) => {
+            files.add_file(file);
+            files.select_last();
+        }
+        Err(e) => vue.show_info(Some(e)),
+    }
+}
+
+/// Loads the file at `path`, or returns why it could not be read.
+fn load_file(path: String, config: &Config) -> Result<File, String> {
+    let content = match save_load::load(&path) {
+        Ok(content) => content,
+        Err(e) => return Err(format!("Could not open {}: {}", path, e)),
+    };
+    let mut content = TextContent::from_string(content);
+    config.configure(&mut content);
+    content.set_language(Language::from_path(Some(&path)));
+    Ok(File::loaded(path, content))
+}
+
+/// Reopens the files of `session`, skipping those that cannot be read
+/// anymore, each to be scrolled as it was once shown.
+fn restore(session: &Session, config: &Config, vue: &mut Vue) -> FileContext {
+    let (mut restored, mut current, mut errors) = (Vec::new(), 0, Vec::new());
+    for (i, open) in session.files.iter().enumerate() {
+        let file = match (&open.path, &open.text) {
+            (Some(path), _) => load_file(path.clone(), config)
+                .map_err(|e| errors.push(e))
+                .ok(),
+            (None, Some(text)) => {
+                let mut file = File::new();
+                file.content = TextContent::from_string(text.clone());
+                config.configure(&mut file.content);
+                Some(file)
+            }
+            (None, None) => None,
+        };
+        let Some(mut file) = file else {
+            continue;
+        };
+        file.content.set_cursor(open.cursor);
+        file.scroll = Some(open.scroll);
+        if i <= session.current {
+            current = restored.len();
+        }
+        restored.push(file);
+    }
+    if !errors.is_empty() {
+        vue.show_info(Some(errors.join("\n")));
+    }
+    FileContext::with_files(restored, current)
+}
+
+/// Saves the current file, asking where first when it has no path yet or
+/// `choose_path` is set.
+fn save(files: &mut FileContext, choose_path: bool, vue: &mut Vue) {
+    let path = match files.current().path.clone() {
+        Some(path) if !choose_path => path,
+        _ => match save_load::select_save_file() {
+            Some(path) => path,
+            None => return,
+        },
+    };
+    // The edits stay in the swap file when they cannot be saved.
+    if let Err(e) = save_load::save(&files.current().content.get_string(), &path) {
+        vue.show_info(Some(format!("Could not save {}: {}", path, e)));
+        return;
+    }
+    if files.current().path.as_ref() != Some(&path) {
+        files.set_current_path(path);
+    }
+    files.current().mark_saved();
+}
+
+/// Puts the text of `swap` in place of the one of its file, opening it
+/// first, or in a new file when it cannot be read anymore.
+fn recover(swap: Swap, files: &mut FileContext, config: &Config, vue: &mut Vue) {
+    if let Some(path) = swap.path.clone().filter(|path| Path::new(path).exists()) {
+        open_file(path, files, config, vue);
+    }
+    match swap.path.as_deref().and_then(|path| files.find(path)) {
+        Some(index) => files.select(index),
+        None => {
+            let mut file = File::new();
+            config.configure(&mut file.content);
+            files.add_file(file);
+            files.select_last();
+            if let Some(path) = swap.path {
                 files.set_current_path(path);
             }
         }
+    }
+    let lines: Vec<String> = swap.text.split('\n').map(str::to_string).collect();
+    let content = &mut files.current().content;
+    let (cursor, len) = (content.get_cursor(), content.get_text().len());
+    content.replace_lines(0, len, &lines);
+    content.set_cursor(cursor);
+}
+
+fn command(
+    keycode: Keycode,
+    keymod: Mod,
+    files: &mut FileContext,
+    config: &Config,
+    vue: &mut Vue,
+) -> bool {
+    match keycode {
+        Keycode::S if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => save(
+            files,
+            keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+            vue,
+        ),
         Keycode::O if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
             if let Some(path) = save_load::select_open_file() {
-                let content = save_load::load(&path).unwrap();
-                let file = File {
-                    path: Some(path),
-                    content: TextContent::from_string(content),
-                };
-                files.add_file(file);
-                files.select_last();
+                open_file(path, files, config, vue);
             }
         }
         _ => return false,
     }
-    return true;
+    true
+}
+
+/// Gives the keys without a text to the vim layer, and returns `None` when
+/// it leaves them to the other keys.
+fn vim_key(
+    keycode: Keycode,
+    keymod: Mod,
+    vim: &mut Vim,
+    content: &mut TextContent,
+) -> Option<vim::Action> {
+    if keymod.intersects(
+        Mod::LCTRLMOD
+            | Mod::RCTRLMOD
+            | Mod::LALTMOD
+            | Mod::RALTMOD
+            | Mod::LSHIFTMOD
+            | Mod::RSHIFTMOD,
+    ) {
+        return None;
+    }
+    let key = match keycode {
+        Keycode::Escape => vim::Key::Escape,
+        Keycode::Return => vim::Key::Return,
+        Keycode::Backspace => vim::Key::Backspace,
+        Keycode::Tab => vim::Key::Tab,
+        _ => return None,
+    };
+    Some(vim.key(key, content)).filter(|action| *action != vim::Action::Pass)
+}
+
+/// Gives the keys to the emacs keys, as a character typed with Ctrl or Alt
+/// or else as another key, and returns `None` when they are left to the
+/// other keys.
+fn emacs_key(
+    keycode: Keycode,
+    keymod: Mod,
+    emacs: &mut Emacs,
+    content: &mut TextContent,
+) -> Option<emacs::Action> {
+    let ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+    let alt = keymod.intersects(Mod::LALTMOD | Mod::RALTMOD);
+    let shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+    let ch = char::from_u32(keycode as i32 as u32).filter(|ch| *ch == ' ' || ch.is_ascii_graphic());
+    let key = match ch {
+        Some(ch) if ctrl && !alt && !shift => emacs::Key::Ctrl(ch),
+        Some(ch) if alt && !ctrl && !shift => emacs::Key::Meta(ch),
+        _ => emacs::Key::Other,
+    };
+    Some(emacs.key(key, content)).filter(|action| *action != emacs::Action::Pass)
+}
+
+/// Does what the command line of the vim keys asks for, and returns true to
+/// quit.
+fn vim_command(action: vim::Action, files: &mut FileContext, vue: &mut Vue) -> bool {
+    let quit = match action {
+        vim::Action::Write => {
+            save(files, false, vue);
+            false
+        }
+        vim::Action::WriteQuit => {
+            save(files, false, vue);
+            true
+        }
+        vim::Action::Quit => true,
+        vim::Action::ForceQuit => return true,
+        vim::Action::Unknown(command) => {
+            vue.show_info(Some(format!("Not an editor command: {}", command)));
+            false
+        }
+        vim::Action::Pass | vim::Action::Handled => false,
+    };
+    if quit && files.files().iter().any(File::is_modified) {
+        vue.show_info(Some(
+            "No write since last change, add ! to quit anyway".to_string(),
+        ));
+        return false;
+    }
+    quit
 }
 
 pub fn main() {
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
 
+    // Starts with a new file instead of the files open on the last exit.
+    let fresh = env::args().skip(1).any(|arg| arg == "--fresh");
+    let session = if fresh { None } else { Session::load() };
     let window = {
-        let w = video_subsystem
-            .window("Text Editor", 800, 600)
-            .position_centered()
-            .resizable()
-            .build()
-            .unwrap();
-        w
+        let geometry = session.as_ref().and_then(|session| session.window);
+        let (width, height) = geometry.map_or((800, 600), |geometry| geometry.size);
+        let mut w = video_subsystem.window("Text Editor", width, height);
+        match geometry {
+            Some(Geometry {
+                position: (x, y), ..
+            }) => w.position(x, y),
+            None => w.position_centered(),
+        };
+        w.resizable().build().unwrap()
     };
 
     let mut canvas = window.into_canvas().build().unwrap();
@@ -88,34 +688,172 @@ pub fn main() {
     let mut event_pump = sdl_context.event_pump().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
     video_subsystem.text_input().start();
-    let mut files = FileContext::new();
+    let config = Config::load();
+    let mut files = match &session {
+        Some(session) => restore(session, &config, &mut vue),
+        None => FileContext::new(),
+    };
+    config.configure(&mut files.current().content);
+    vue.set_wrap(config.wrap);
+    vue.show_blame(config.inline_blame);
+    if !config.errors.is_empty() {
+        vue.show_info(Some(config.errors.join("\n")));
+    }
+    match config.line_numbers {
+        Some(mode) => vue.set_line_number_mode(mode),
+        None => vue.show_line_numbers(false),
+    }
+    let mut gutter = Gutter::new();
+    gutter.add_provider(Box::new(Bookmarks));
+    gutter.add_provider(Box::new(CodeChecks));
+    gutter.add_provider(Box::new(Changes));
+    gutter.add_provider(Box::new(Diagnostics));
+    let mut lsp = Lsp::new(config.language_servers.clone());
+    // Shown in the info bar until a key is pressed.
+    let mut lsp_error = None;
+    let snippets = Snippets::load();
+    let mut macros = Macros::load();
+    let mut swaps = Swaps::new();
+    let mut vim = (config.keys == Keys::Vim).then(Vim::new);
+    let mut emacs = (config.keys == Keys::Emacs).then(Emacs::new);
     let timer = Timer::new();
     let mut refresh_switch = true;
     let mut left_click_origin = None;
     'running: loop {
-        let events = event_pump.poll_iter();
-        for event in events {
+        let mut events: VecDeque<Event> = event_pump.poll_iter().collect();
+        while let Some(event) = events.pop_front() {
             match event {
                 Event::Quit { .. } => break 'running,
+                Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } if swaps.is_asking() => swaps.skip(),
+                // The other keys wait for the answer.
+                Event::KeyDown { .. } if swaps.is_asking() => {}
                 Event::KeyDown {
                     keycode: Some(keycode),
                     keymod,
                     ..
                 } => {
-                    if text_editing(keycode, keymod, &mut files.current().content) {
+                    vue.show_info(None);
+                    lsp_error = None;
+                    if macro_keys(keycode, keymod, &mut macros, &mut events, &mut vue) {
+                        continue;
+                    }
+                    macros.record(Input::Key(keycode as i32, keymod.bits()));
+                    let file_name = files.current().file_name();
+                    let content = &mut files.current().content;
+                    if completing(keycode, &mut vue, content, &snippets) {
+                        vue.send_cursor_update()
+                    } else if let Some(action) =
+                        (vim.as_mut()).and_then(|vim| vim_key(keycode, keymod, vim, content))
+                    {
+                        if vim_command(action, &mut files, &mut vue) {
+                            break 'running;
+                        }
+                        vue.send_cursor_update()
+                    } else if let Some(action) = (emacs.as_mut())
+                        .and_then(|emacs| emacs_key(keycode, keymod, emacs, content))
+                    {
+                        match action {
+                            emacs::Action::Save => save(&mut files, false, &mut vue),
+                            emacs::Action::Open => {
+                                if let Some(path) = save_load::select_open_file() {
+                                    open_file(path, &mut files, &config, &mut vue);
+                                }
+                            }
+                            emacs::Action::Pass | emacs::Action::Handled => {}
+                        }
                         vue.send_cursor_update()
-                    } else if command(keycode, keymod, &mut files) {
+                    } else if let Some(name) = config.keymap.command(keycode, keymod) {
+                        if !language_server(name, &mut lsp, &mut files, &mut vue) {
+                            commands::run(name, &mut files.current().content);
+                        }
+                        vue.send_cursor_update()
+                    } else if selecting(keycode, keymod, content)
+                        || clipboard(
+                            keycode,
+                            keymod,
+                            content,
+                            &video_subsystem.clipboard(),
+                            &mut vue,
+                        )
+                        || wrapped_moving(keycode, keymod, content, &vue)
+                        || text_editing(keycode, keymod, content, &snippets, &file_name)
+                        || changes(keycode, keymod, files.current())
+                    {
+                        vue.send_cursor_update()
+                    } else if view(keycode, keymod, &mut vue)
+                        || panes(keycode, keymod, &mut vue, &mut files)
+                        || command(keycode, keymod, &mut files, &config, &mut vue)
+                    {
+                    }
+                }
+                Event::TextInput { text, .. } if swaps.is_asking() => {
+                    match text.chars().next().and_then(|ch| swaps.answer(ch)) {
+                        Some(Answer::Recover(swap)) => {
+                            recover(swap, &mut files, &config, &mut vue);
+                            vue.send_cursor_update();
+                        }
+                        Some(Answer::Diff(diff)) => vue.show_info(Some(diff)),
+                        None => {}
+                    }
+                }
+                Event::TextInput { text, .. } if macros.is_naming() => {
+                    if let Some(ch) = text.chars().next() {
+                        replay(macros.name(ch), &mut events);
                     }
                 }
                 Event::TextInput { text, .. } => {
-                    files.current().content.append(text);
+                    vue.show_info(None);
+                    macros.record(Input::Text(text.clone()));
+                    // Outside of the insert mode, the vim keys read the text
+                    // as commands.
+                    let mut typed = String::new();
+                    for ch in text.chars() {
+                        let action = vim.as_mut().map_or(vim::Action::Pass, |vim| {
+                            vim.key(vim::Key::Char(ch), &mut files.current().content)
+                        });
+                        if action == vim::Action::Pass {
+                            typed.push(ch);
+                        } else if vim_command(action, &mut files, &mut vue) {
+                            break 'running;
+                        }
+                    }
+                    if typed.is_empty() {
+                        vue.hide_completion();
+                        vue.send_cursor_update();
+                        continue;
+                    }
+                    let typing_word = typed.chars().all(text_zone::is_word_char);
+                    files.current().content.append(typed);
+                    let prefix = files.current().content.word_before_cursor();
+                    // The popup is not shown while Tab goes to a snippet.
+                    if typing_word
+                        && prefix.chars().count() >= MIN_COMPLETED_PREFIX
+                        && !snippets.takes_tab(&files.current().content)
+                    {
+                        vue.show_completion(files.word_completions());
+                    } else {
+                        vue.hide_completion();
+                    }
                     vue.send_cursor_update();
                 }
                 Event::MouseButtonDown { x, y, .. } => {
                     left_click_origin = Some((x, y));
-                    if vue.click_text_area_scroll_bar(x, y) {
+                    if let Some(pane) = vue.focus_pane_at(x, y) {
+                        files.focus(pane);
+                    }
+                    if vue.click_divider(x, y) || vue.click_text_area_scroll_bar(x, y) {
+                    } else if let Some(line) = vue.click_fold_marker(x, y) {
+                        files.current().content.toggle_fold(line);
                     } else if let Some(position) = vue.cursor_index(x, y) {
-                        files.current().content.set_cursor(position);
+                        let keymod = sdl_context.keyboard().mod_state();
+                        if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) {
+                            files.current().content.add_cursor(position);
+                        } else {
+                            files.current().content.set_cursor(position);
+                        }
                         vue.send_cursor_update();
                     }
                 }
@@ -123,13 +861,36 @@ pub fn main() {
                     x, y, xrel, yrel, ..
                 } if left_click_origin.is_some() => {
                     let origin = left_click_origin.unwrap();
-                    vue.hold_text_area_scroll_bar(origin, x, y, xrel, yrel);
+                    if !vue.hold_divider(x, y)
+                        && !vue.hold_text_area_scroll_bar(origin, x, y, xrel, yrel)
+                    {
+                        if let Some(position) = vue.cursor_index(x, y) {
+                            let keymod = sdl_context.keyboard().mod_state();
+                            let mode = if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) {
+                                SelectionMode::Block
+                            } else {
+                                SelectionMode::Linear
+                            };
+                            files.current().content.select_to(position, mode);
+                            vue.send_cursor_update();
+                        }
+                    }
                 }
+                Event::MouseMotion { x, y, .. } => vue.hover(Some((x, y))),
+                Event::Window {
+                    win_event: WindowEvent::Leave,
+                    ..
+                } => vue.hover(None),
+                Event::Window {
+                    win_event: WindowEvent::FocusGained,
+                    ..
+                } => files.reload_git(),
                 Event::MouseButtonUp {
                     mouse_btn: MouseButton::Left,
                     ..
                 } => {
                     left_click_origin = None;
+                    vue.release_divider();
                 }
                 Event::Window {
                     win_event: WindowEvent::Resized(..),
@@ -144,15 +905,68 @@ pub fn main() {
                 }
                 _ => {}
             }
+            files.current().content.track_snippet();
         }
         let refresh = refresh_switch != timer.switch_n_times_per_second(60);
         if refresh {
-            vue.refresh(
-                files.current().content.get_text(),
-                files.current().content.size(),
-                files.current().content.get_cursor(),
-            );
+            lsp.sync(&files);
+            for event in lsp.poll(&mut files) {
+                match event {
+                    lsp::Event::Hover(text) => vue.show_info(Some(text)),
+                    lsp::Event::Definition(path, position) => {
+                        open_file(path, &mut files, &config, &mut vue);
+                        // The definition is shown rather than the restored scroll.
+                        files.current().scroll = None;
+                        files.current().content.set_cursor(position);
+                        vue.send_cursor_update();
+                    }
+                    lsp::Event::Completion(items) => {
+                        let word = files.current().content.word_before_cursor();
+                        let items = items.into_iter().filter(|i| i.starts_with(&word));
+                        vue.show_completion(items.collect());
+                    }
+                    lsp::Event::Error(message) => lsp_error = Some(message),
+                    lsp::Event::Diagnostics(..) => {}
+                }
+            }
+            if let Some(scroll) = files.current().scroll.take() {
+                vue.set_text_area_scroll_offset(scroll);
+            }
+            files.load_blame(&vue.panes_showing_blame());
+            files.update_changes();
+            swaps.update(&files);
+            if let Some(errors) = swaps.take_errors() {
+                vue.show_info(Some(errors));
+            }
+            vue.refresh(files.map_views(|file| {
+                let mut snapshot = snapshot(file, &mut gutter);
+                snapshot.message = (macros.status())
+                    .or(swaps.status())
+                    .or(lsp_error.clone())
+                    .or(snapshot.message);
+                snapshot.mode = vim
+                    .as_ref()
+                    .map(Vim::status)
+                    .or(emacs.as_ref().and_then(Emacs::status));
+                snapshot.block_cursor = vim.as_ref().is_some_and(Vim::has_block_cursor);
+                snapshot
+            }));
             refresh_switch = !refresh_switch;
         }
     }
+    let (position, size) = vue.window_geometry();
+    let window = Some(Geometry { position, size });
+    let session = Session::capture(&files, &vue.text_area_scroll_offsets(), window);
+    if let Err(e) = session.save() {
+        // Shown until a key is pressed, not to close the window at once.
+        let message = format!("Could not save the session: {}, press a key to quit", e);
+        vue.show_info(Some(message));
+        vue.refresh(files.map_views(|file| snapshot(file, &mut gutter)));
+        while !matches!(
+            event_pump.wait_event(),
+            Event::Quit { .. } | Event::KeyDown { .. }
+        ) {}
+    }
+    swaps.clear();
+    lsp.shutdown();
 }
@@ -0,0 +1,255 @@
+use std::{fs, io};
+
+use serde_json::{json, Value};
+
+use crate::{config::state_dir, files::FileContext};
+
+/// Where the window was and how big it was.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Geometry {
+    pub position: (i32, i32),
+    pub size: (u32, u32),
+}
+
+/// A file open when the editor exited.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct OpenFile {
+    /// `None` for a new file, whose text is then kept in `text`.
+    pub path: Option<String>,
+    pub text: Option<String>,
+    pub cursor: (usize, usize),
+    pub scroll: (f32, f32),
+}
+
+/// The open files and the window, kept in `session` in the state directory
+/// to be restored on the next start.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Session {
+    pub files: Vec<OpenFile>,
+    /// Index in `files` of the file shown in the focused pane.
+    pub current: usize,
+    pub window: Option<Geometry>,
+}
+
+fn pair<T, F: Fn(&Value) -> Option<T>>(value: &Value, f: F) -> Option<(T, T)> {
+    Some((f(value.get(0)?)?, f(value.get(1)?)?))
+}
+
+fn to_usize(value: &Value) -> Option<usize> {
+    value.as_u64().map(|n| n as usize)
+}
+
+fn parse_file(value: &Value) -> Option<OpenFile> {
+    let path = value
+        .get("path")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    let text = value
+        .get("text")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    if path.is_none() && text.is_none() {
+        return None;
+    }
+    Some(OpenFile {
+        path,
+        text,
+        cursor: pair(value.get("cursor")?, to_usize)?,
+        scroll: value
+            .get("scroll")
+            .and_then(|scroll| pair(scroll, |n| n.as_f64().map(|n| n as f32)))
+            .unwrap_or((0.0, 0.0)),
+    })
+}
+
+fn parse_geometry(value: &Value) -> Option<Geometry> {
+    let to_i32 = |n: &Value| n.as_i64().and_then(|n| i32::try_from(n).ok());
+    let to_u32 = |n: &Value| n.as_u64().and_then(|n| u32::try_from(n).ok());
+    Some(Geometry {
+        position: pair(value.get("position")?, to_i32)?,
+        size: pair(value.get("size")?, to_u32)?,
+    })
+}
+
+/// Reads a session, ignoring the invalid files in it.
+fn parse(text: &str) -> Option<Session> {
+    let value: Value = serde_json::from_str(text).ok()?;
+    let files: Vec<OpenFile> = (value.get("files")?.as_array()?.iter())
+        .filter_map(parse_file)
+        .collect();
+    if files.is_empty() {
+        return None;
+    }
+    let current = value.get("current").and_then(to_usize).unwrap_or(0);
+    Some(Session {
+        current: current.min(files.len() - 1),
+        files,
+        window: value.get("window").and_then(parse_geometry),
+    })
+}
+
+fn format(session: &Session) -> String {
+    let files: Vec<Value> = (session.files.iter())
+        .map(|file| {
+            json!({
+                "path": file.path,
+                "text": file.text,
+                "cursor": [file.cursor.0, file.cursor.1],
+                "scroll": [file.scroll.0, file.scroll.1],
+            })
+        })
+        .collect();
+    let window = session.window.map(|window| {
+        json!({
+            "position": [window.position.0, window.position.1],
+            "size": [window.size.0, window.size.1],
+        })
+    });
+    let session = json!({"files": files, "current": session.current, "window": window});
+    serde_json::to_string_pretty(&session).unwrap_or_default()
+}
+
+impl Session {
+    /// Returns the session of `files`, the views being scrolled by `scrolls`.
+    /// Files not shown keep the scroll they were restored with. New files are
+    /// kept only when they have text.
+    pub fn capture(files: &FileContext, scrolls: &[(f32, f32)], window: Option<Geometry>) -> Self {
+        let mut session = Session {
+            files: Vec::new(),
+            current: 0,
+            window,
+        };
+        for (i, file) in files.files().iter().enumerate() {
+            if file.path.is_none() && !file.is_modified() {
+                continue;
+            }
+            let focused = i == files.current_file();
+            if focused {
+                session.current = session.files.len();
+            }
+            session.files.push(OpenFile {
+                path: file.path.clone(),
+                text: (file.path.is_none()).then(|| file.content.get_string()),
+                cursor: file.content.get_cursor(),
+                scroll: (files.view_of(i))
+                    .and_then(|view| scrolls.get(view).copied())
+                    .or(file.scroll)
+                    .unwrap_or((0.0, 0.0)),
+            });
+        }
+        session
+    }
+
+    /// Reads the session saved in the state directory, if any.
+    pub fn load() -> Option<Self> {
+        let dir = state_dir()?;
+        parse(&fs::read_to_string(dir.join("session")).ok()?)
+    }
+
+    /// Saves the session, or removes the saved one when no file is worth
+    /// restoring.
+    pub fn save(&self) -> io::Result<()> {
+        let Some(dir) = state_dir() else {
+            return Ok(());
+        };
+        let path = dir.join("session");
+        if self.files.is_empty() {
+            return match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+        fs::create_dir_all(&dir)?;
+        fs::write(path, format(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{files::File, text_zone::TextContent};
+
+    #[test]
+    fn saved_session() {
+        let session = Session {
+            files: vec![
+                OpenFile {
+                    path: Some("/tmp/a.rs".to_string()),
+                    text: None,
+                    cursor: (3, 1),
+                    scroll: (0.0, -120.5),
+                },
+                OpenFile {
+                    path: None,
+                    text: Some("new\n\"text\"".to_string()),
+                    cursor: (1, 0),
+                    scroll: (0.0, 0.0),
+                },
+            ],
+            current: 1,
+            window: Some(Geometry {
+                position: (-10, 40),
+                size: (800, 600),
+            }),
+        };
+        assert_eq!(parse(&format(&session)), Some(session.clone()));
+        let text = r#"{"files": [{"path": "b", "cursor": [0, 2]}, {"cursor": [0, 0]}],
+            "current": 7, "window": {"position": [0]}}"#;
+        let expected = Session {
+            files: vec![OpenFile {
+                path: Some("b".to_string()),
+                text: None,
+                cursor: (0, 2),
+                scroll: (0.0, 0.0),
+            }],
+            current: 0,
+            window: None,
+        };
+        assert_eq!(parse(text), Some(expected));
+        assert_eq!(parse(r#"{"files": []}"#), None);
+        assert_eq!(parse("files"), None);
+    }
+
+    #[test]
+    fn capture_files() {
+        let mut files = FileContext::new();
+        let mut content = TextContent::from_string("one\ntwo".to_string());
+        content.set_cursor((1, 2));
+        files.add_file(File::loaded("/tmp/one".to_string(), content));
+        files.add_file(File::new());
+        files.files_mut()[1].scroll = Some((0.0, -40.0));
+        files.select_last();
+        files.current().content.paste("draft".to_string());
+        let session = Session::capture(&files, &[(0.0, -20.0)], None);
+        assert_eq!(session.current, 1);
+        assert_eq!(
+            session.files,
+            vec![
+                OpenFile {
+                    path: Some("/tmp/one".to_string()),
+                    text: None,
+                    cursor: (1, 2),
+                    scroll: (0.0, -40.0),
+                },
+                OpenFile {
+                    path: None,
+                    text: Some("draft".to_string()),
+                    cursor: (0, 5),
+                    scroll: (0.0, -20.0),
+                },
+            ]
+        );
+        // A file shown in another view is scrolled as that view.
+        let view = files.split();
+        files.focus(view);
+        files.select(1);
+        let session = Session::capture(&files, &[(0.0, -20.0), (0.0, -60.0)], None);
+        assert_eq!(session.current, 0);
+        assert_eq!(
+            (session.files.iter())
+                .map(|file| file.scroll)
+                .collect::<Vec<_>>(),
+            vec![(0.0, -60.0), (0.0, -20.0)]
+        );
+    }
+}
@@ -0,0 +1,312 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{config::config_dir, git, text_zone::TextContent};
+
+/// Snippets of this file are offered for every language.
+const ALL_LANGUAGES: &str = "all";
+
+/// A piece of text inserted in place of its prefix.
+///
+/// The body may hold tab stops `$1` or `${1:placeholder}`, visited in order
+/// and ending at `$0`, stops repeated with the same number mirroring the
+/// first one, and variables `$NAME` or `${NAME:default}`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Snippet {
+    pub prefix: String,
+    pub body: String,
+}
+
+/// The text of an expanded snippet with its fields, each given by the
+/// character ranges it occupies, in the order they are visited.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Expansion {
+    pub text: String,
+    pub fields: Vec<Vec<(usize, usize)>>,
+}
+
+/// The snippets of each language, read from `snippets/<language>.snippets`
+/// in the configuration directory, with `<language>` in lower case or `all`.
+pub(crate) struct Snippets {
+    by_language: HashMap<String, Vec<Snippet>>,
+}
+
+/// Reads snippets written as
+///
+/// ```text
+/// snippet <prefix>
+/// <body>
+/// endsnippet
+/// ```
+///
+/// ignoring lines outside of them.
+fn parse(text: &str) -> Vec<Snippet> {
+    let mut snippets = Vec::new();
+    let mut current: Option<(String, Vec<&str>)> = None;
+    for line in text.lines() {
+        match current.as_mut() {
+            Some((prefix, body)) => {
+                if line.trim_end() == "endsnippet" {
+                    snippets.push(Snippet {
+                        prefix: std::mem::take(prefix),
+                        body: body.join("\n"),
+                    });
+                    current = None;
+                } else {
+                    body.push(line);
+                }
+            }
+            None => {
+                if let Some(prefix) = line.strip_prefix("snippet ") {
+                    current = Some((prefix.trim().to_string(), Vec::new()));
+                }
+            }
+        }
+    }
+    snippets
+}
+
+/// Reads `name` or `{name...}` at the start of `chars`, and returns it with
+/// the text after `:` and the number of characters read.
+fn read_reference(chars: &[char]) -> Option<(String, Option<String>, usize)> {
+    let is_name_char = |ch: &char| ch.is_ascii_alphanumeric() || *ch == '_';
+    if chars.first() != Some(&'{') {
+        let name: String = chars.iter().take_while(|ch| is_name_char(ch)).collect();
+        let len = name.chars().count();
+        return (len > 0).then_some((name, None, len));
+    }
+    let name: String = chars[1..]
+        .iter()
+        .take_while(|ch| is_name_char(ch))
+        .collect();
+    let mut i = 1 + name.chars().count();
+    let mut default = None;
+    if chars.get(i) == Some(&':') {
+        let mut text = String::new();
+        i += 1;
+        while let Some(ch) = chars.get(i) {
+            match ch {
+                '\\' if chars.get(i + 1).is_some() => {
+                    text.push(chars[i + 1]);
+                    i += 2;
+                    continue;
+                }
+                '}' => break,
+                ch => text.push(*ch),
+            }
+            i += 1;
+        }
+        default = Some(text);
+    }
+    if name.is_empty() || chars.get(i) != Some(&'}') {
+        return None;
+    }
+    Some((name, default, i + 1))
+}
+
+/// Expands `body`, replacing the variables by their value in `variables`,
+/// or else by their default.
+pub fn expand(body: &str, variables: &HashMap<&str, String>) -> Expansion {
+    let chars: Vec<char> = body.chars().collect();
+    let mut text: Vec<char> = Vec::new();
+    let mut stops: Vec<(usize, (usize, usize))> = Vec::new();
+    let mut placeholders: HashMap<usize, String> = HashMap::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let reference = match chars[i] {
+            '\\' if matches!(chars.get(i + 1), Some('$' | '\\' | '}')) => {
+                text.push(chars[i + 1]);
+                i += 2;
+                continue;
+            }
+            '$' => read_reference(&chars[i + 1..]),
+            _ => None,
+        };
+        let Some((name, default, len)) = reference else {
+            text.push(chars[i]);
+            i += 1;
+            continue;
+        };
+        i += 1 + len;
+        let value = match name.parse::<usize>() {
+            Ok(n) => {
+                // Mirrors take the placeholder of the first stop of their
+                // number.
+                let placeholder = placeholders
+                    .entry(n)
+                    .or_insert(default.unwrap_or_default())
+                    .clone();
+                stops.push((n, (text.len(), text.len() + placeholder.chars().count())));
+                placeholder
+            }
+            Err(_) => variables
+                .get(name.as_str())
+                .cloned()
+                .or(default)
+                .unwrap_or_default(),
+        };
+        text.extend(value.chars());
+    }
+    let mut numbers: Vec<usize> = stops.iter().map(|(n, _)| *n).collect();
+    numbers.sort();
+    numbers.dedup();
+    if numbers.first() == Some(&0) {
+        numbers.rotate_left(1);
+    } else {
+        stops.push((0, (text.len(), text.len())));
+        numbers.push(0);
+    }
+    let fields = numbers
+        .into_iter()
+        .map(|n| {
+            (stops.iter())
+                .filter(|(m, _)| *m == n)
+                .map(|(_, range)| *range)
+                .collect()
+        })
+        .collect();
+    Expansion {
+        text: text.into_iter().collect(),
+        fields,
+    }
+}
+
+impl Snippets {
+    pub fn new() -> Self {
+        Snippets {
+            by_language: HashMap::new(),
+        }
+    }
+
+    /// Reads the snippet files of the configuration directory.
+    pub fn load() -> Self {
+        let mut snippets = Self::new();
+        if let Some(dir) = config_dir() {
+            snippets.load_dir(&dir.join("snippets"));
+        }
+        snippets
+    }
+
+    fn load_dir(&mut self, dir: &Path) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension().is_none_or(|ext| ext != "snippets") {
+                continue;
+            }
+            let (Some(language), Ok(text)) = (
+                path.file_stem().and_then(|stem| stem.to_str()),
+                fs::read_to_string(&path),
+            ) else {
+                continue;
+            };
+            self.by_language
+                .entry(language.to_lowercase())
+                .or_default()
+                .extend(parse(&text));
+        }
+    }
+
+    /// Returns the snippet of `language` with the given prefix.
+    fn find(&self, language: &str, prefix: &str) -> Option<&Snippet> {
+        [language.to_lowercase().as_str(), ALL_LANGUAGES]
+            .iter()
+            .filter_map(|language| self.by_language.get(*language))
+            .flatten()
+            .find(|snippet| snippet.prefix == prefix)
+    }
+
+    /// Returns whether Tab goes to a snippet in `content`: to the next field
+    /// of the snippet being filled in, or to the snippet whose prefix is the
+    /// word before the cursor.
+    pub fn takes_tab(&self, content: &TextContent) -> bool {
+        content.in_snippet()
+            || content.snippet_trigger().is_some_and(|(prefix, _)| {
+                self.find(content.get_language().name, &prefix).is_some()
+            })
+    }
+
+    /// Replaces the word before the cursor of `content` by the snippet it is
+    /// the prefix of, and returns false if there is none. The text selected
+    /// after the prefix is given to the snippet as `TM_SELECTED_TEXT`.
+    pub fn expand(&self, content: &mut TextContent, file_name: &str) -> bool {
+        let Some((prefix, selection)) = content.snippet_trigger() else {
+            return false;
+        };
+        let Some(snippet) = self.find(content.get_language().name, &prefix) else {
+            return false;
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let variables = HashMap::from([
+            ("TM_FILENAME", file_name.to_string()),
+            ("TM_SELECTED_TEXT", selection),
+            ("CURRENT_DATE", git::format_date(now)),
+        ]);
+        content.insert_snippet(prefix.chars().count(), expand(&snippet.body, &variables));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_snippets() {
+        let text = "# For loops\nsnippet for\nfor ${1:i} in $2 {\n\t$0\n}\nendsnippet\n\nsnippet x\nendsnippet";
+        assert_eq!(
+            parse(text),
+            vec![
+                Snippet {
+                    prefix: "for".to_string(),
+                    body: "for ${1:i} in $2 {\n\t$0\n}".to_string(),
+                },
+                Snippet {
+                    prefix: "x".to_string(),
+                    body: String::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn expand_fields() {
+        let variables = HashMap::from([("TM_FILENAME", "main.rs".to_string())]);
+        let expansion = expand("fn ${1:name}($2) -> $1 {$0}", &variables);
+        assert_eq!(expansion.text, "fn name() -> name {}");
+        assert_eq!(
+            expansion.fields,
+            vec![vec![(3, 7), (13, 17)], vec![(8, 8)], vec![(19, 19)]]
+        );
+        let expansion = expand("// $TM_FILENAME ${AUTHOR:me} \\$1 ${1:a\\}b}", &variables);
+        assert_eq!(expansion.text, "// main.rs me $1 a}b");
+        assert_eq!(expansion.fields, vec![vec![(17, 20)], vec![(20, 20)]]);
+    }
+
+    #[test]
+    fn tab_for_snippets() {
+        let mut snippets = Snippets::new();
+        snippets.by_language.insert(
+            ALL_LANGUAGES.to_string(),
+            parse("snippet for\nfor $1 in $2\nendsnippet"),
+        );
+        let mut content = TextContent::new();
+        content.append("fo".to_string());
+        assert!(!snippets.takes_tab(&content));
+        content.append("r".to_string());
+        assert!(snippets.takes_tab(&content));
+        assert!(snippets.expand(&mut content, ""));
+        content.append("x".to_string());
+        assert!(snippets.takes_tab(&content));
+        assert!(content.next_snippet_field());
+        content.end_snippet();
+        assert!(!snippets.takes_tab(&content));
+    }
+}
@@ -0,0 +1,473 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, Instant},
+};
+
+use crate::{config::state_dir, diff::diff, files::FileContext};
+
+/// Longest time the edits of a file may wait to be written to its swap file.
+const WRITE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Most lines of differences shown between a swap file and its file.
+const MAX_DIFF_LINES: usize = 20;
+
+/// The unsaved text of a file, left by an editor which did not exit.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Swap {
+    /// Where the swap file is.
+    location: PathBuf,
+    /// The file edited, `None` for a new file.
+    pub path: Option<String>,
+    pub text: String,
+}
+
+/// What the editor still has to do with an answer about a swap file.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Answer {
+    /// Puts the text of the swap file in place of the one of its file.
+    Recover(Swap),
+    /// Shows the differences between the swap file and its file.
+    Diff(String),
+}
+
+/// Swap files holding the edits not saved yet, written in `swap` in the
+/// state directory so that they are not lost when the editor does not exit,
+/// and those left by such an editor, to recover or discard.
+pub(crate) struct Swaps {
+    dir: Option<PathBuf>,
+    /// The text last written to each swap file of this editor.
+    written: HashMap<PathBuf, String>,
+    /// The paths whose swap file was already looked for.
+    checked: HashSet<String>,
+    /// Where the swap file of each path goes.
+    locations: HashMap<String, PathBuf>,
+    /// The swap files left, waiting to be recovered or discarded.
+    found: VecDeque<Swap>,
+    /// Whether discarding the swap file asked about waits to be confirmed.
+    discarding: bool,
+    last_write: Instant,
+    /// Swap files of the same files written by other running editors, left
+    /// to them.
+    others: HashSet<PathBuf>,
+    /// Swap files which could not be written or removed, not reported yet.
+    errors: Vec<String>,
+}
+
+/// Reads a swap file written as
+///
+/// ```text
+/// pid <process id of the editor>
+/// path <path> or untitled
+/// <text>
+/// ```
+fn parse(text: &str) -> Option<(u32, Option<String>, String)> {
+    let (pid, rest) = text.split_once('\n')?;
+    let (path, text) = rest.split_once('\n')?;
+    let pid = pid.strip_prefix("pid ")?.parse().ok()?;
+    let path = match path.strip_prefix("path ") {
+        Some(path) => Some(path.to_string()),
+        None if path == "untitled" => None,
+        None => return None,
+    };
+    Some((pid, path, text.to_string()))
+}
+
+fn format(path: Option<&str>, text: &str) -> String {
+    let path = path.map_or("untitled".to_string(), |path| format!("path {}", path));
+    format!("pid {}\n{}\n{}", process::id(), path, text)
+}
+
+fn canonical(path: &str) -> String {
+    fs::canonicalize(path).map_or(path.to_string(), |path| path.to_string_lossy().to_string())
+}
+
+/// Returns whether the editor with the process id `pid` is still running,
+/// so that its swap files are not taken as left. Without `/proc`, `kill -0`
+/// tells, and when nothing can tell it is taken as running.
+fn is_running(pid: u32) -> bool {
+    if pid == process::id() {
+        return true;
+    }
+    if Path::new("/proc").is_dir() {
+        return Path::new("/proc").join(pid.to_string()).exists();
+    }
+    let status = process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(process::Stdio::null())
+        .status();
+    status.map_or(true, |status| status.success())
+}
+
+/// Returns the process id of the editor which wrote the swap file at
+/// `location`, if any.
+fn owner(location: &Path) -> Option<u32> {
+    let text = fs::read_to_string(location).ok()?;
+    Some(parse(&text)?.0)
+}
+
+/// Returns the lines removed from `old` and added in `new`, as in a unified
+/// diff without context.
+fn diff_text(old: &str, new: &str) -> String {
+    let (old, new): (Vec<&str>, Vec<&str>) = (old.split('\n').collect(), new.split('\n').collect());
+    let mut lines = Vec::new();
+    for hunk in diff(&old, &new) {
+        lines.push(format!(
+            "@@ -{},{} +{},{} @@",
+            hunk.old_start + 1,
+            hunk.old_len,
+            hunk.new_start + 1,
+            hunk.new_len
+        ));
+        let removed = &old[hunk.old_start..hunk.old_start + hunk.old_len];
+        let added = &new[hunk.new_start..hunk.new_start + hunk.new_len];
+        lines.extend(removed.iter().map(|line| format!("-{}", line)));
+        lines.extend(added.iter().map(|line| format!("+{}", line)));
+    }
+    if lines.is_empty() {
+        return "No differences".to_string();
+    }
+    if lines.len() > MAX_DIFF_LINES {
+        let more = lines.len() - MAX_DIFF_LINES;
+        lines.truncate(MAX_DIFF_LINES);
+        lines.push(format!("... {} more lines", more));
+    }
+    lines.join("\n")
+}
+
+impl Swaps {
+    /// Finds the swap files left in the state directory.
+    pub fn new() -> Self {
+        Self::in_dir(state_dir().map(|dir| dir.join("swap")))
+    }
+
+    fn in_dir(dir: Option<PathBuf>) -> Self {
+        let mut swaps = Swaps {
+            dir,
+            written: HashMap::new(),
+            checked: HashSet::new(),
+            locations: HashMap::new(),
+            found: VecDeque::new(),
+            discarding: false,
+            last_write: Instant::now(),
+            others: HashSet::new(),
+            errors: Vec::new(),
+        };
+        let entries = swaps.dir.as_ref().and_then(|dir| fs::read_dir(dir).ok());
+        let mut locations: Vec<PathBuf> = (entries.into_iter().flatten())
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .collect();
+        locations.sort();
+        for location in locations {
+            swaps.find(location);
+        }
+        swaps
+    }
+
+    /// Adds the swap file at `location` to the ones to ask about, when it was
+    /// left by an editor not running anymore.
+    fn find(&mut self, location: PathBuf) {
+        if self.found.iter().any(|swap| swap.location == location) {
+            return;
+        }
+        let Some((pid, path, text)) = fs::read_to_string(&location)
+            .ok()
+            .and_then(|text| parse(&text))
+        else {
+            return;
+        };
+        if is_running(pid) {
+            return;
+        }
+        if let Some(path) = &path {
+            self.checked.insert(path.clone());
+        }
+        self.found.push_back(Swap {
+            location,
+            path,
+            text,
+        });
+    }
+
+    /// Returns where the swap file of `path` goes, or of the new file at
+    /// `index`.
+    fn location(&mut self, path: Option<&str>, index: usize) -> Option<PathBuf> {
+        let dir = self.dir.as_ref()?;
+        let Some(path) = path else {
+            return Some(dir.join(format!("untitled-{}-{}.swp", process::id(), index)));
+        };
+        let location = (self.locations.entry(path.to_string())).or_insert_with(|| {
+            let name = canonical(path).replace(['/', '\\', ':'], "%");
+            dir.join(name + ".swp")
+        });
+        Some(location.clone())
+    }
+
+    /// Looks for a swap file left for the file opened at `path`.
+    fn check(&mut self, path: &str) {
+        if !self.checked.insert(path.to_string()) {
+            return;
+        }
+        if let Some(location) = self.location(Some(path), 0) {
+            self.find(location);
+        }
+    }
+
+    /// Every `WRITE_INTERVAL`, looks for the swap files left for the files
+    /// opened since, writes the text of the modified files to their swap
+    /// files, and removes those of the files saved since.
+    pub fn update(&mut self, files: &FileContext) {
+        if self.last_write.elapsed() < WRITE_INTERVAL {
+            return;
+        }
+        self.last_write = Instant::now();
+        let mut kept = HashSet::new();
+        for (i, file) in files.files().iter().enumerate() {
+            if let Some(path) = &file.path {
+                self.check(path);
+            }
+            if !file.is_modified() {
+                continue;
+            }
+            let Some(location) = self.location(file.path.as_deref(), i) else {
+                continue;
+            };
+            // The swap file left is kept until it is recovered or discarded.
+            if self.found.iter().any(|swap| swap.location == location) {
+                continue;
+            }
+            if !self.written.contains_key(&location) && !self.take_over(&location) {
+                continue;
+            }
+            kept.insert(location.clone());
+            let text = file.content.get_string();
+            if self.written.get(&location) == Some(&text) {
+                continue;
+            }
+            let dir = location.parent().unwrap_or(Path::new("."));
+            let written = fs::create_dir_all(dir)
+                .and_then(|_| fs::write(&location, format(file.path.as_deref(), &text)));
+            if let Err(e) = written {
+                (self.errors).push(format!("Could not write {}: {}", location.display(), e));
+            }
+            self.written.insert(location, text);
+        }
+        let errors = &mut self.errors;
+        self.written.retain(|location, _| {
+            kept.contains(location) || {
+                match fs::remove_file(location) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                        errors.push(format!("Could not remove {}: {}", location.display(), e))
+                    }
+                    _ => {}
+                }
+                false
+            }
+        });
+    }
+
+    /// Returns whether the swap file at `location` may be written, which is
+    /// not when another running editor writes it, the user being told once.
+    fn take_over(&mut self, location: &Path) -> bool {
+        let Some(pid) = owner(location).filter(|pid| *pid != process::id()) else {
+            return true;
+        };
+        if !is_running(pid) {
+            return true;
+        }
+        if self.others.insert(location.to_path_buf()) {
+            self.errors.push(format!(
+                "{} is used by another editor (process {}), the edits are not kept there",
+                location.display(),
+                pid
+            ));
+        }
+        false
+    }
+
+    /// Removes the swap files of this editor, when it exits.
+    pub fn clear(&mut self) {
+        for location in self.written.keys() {
+            let _ = fs::remove_file(location);
+        }
+        self.written.clear();
+    }
+
+    /// Returns the errors not reported yet, one per line.
+    pub fn take_errors(&mut self) -> Option<String> {
+        let errors = std::mem::take(&mut self.errors);
+        (!errors.is_empty()).then(|| errors.join("\n"))
+    }
+
+    /// Returns whether a swap file left is waiting for an answer.
+    pub fn is_asking(&self) -> bool {
+        !self.found.is_empty()
+    }
+
+    /// Leaves the swap file asked about for the next start.
+    pub fn skip(&mut self) {
+        self.discarding = false;
+        self.found.pop_front();
+    }
+
+    /// Takes `ch` as the answer about the swap file asked about: `r` to
+    /// recover it, `d` to see its differences with its file, or `x` twice to
+    /// discard it.
+    pub fn answer(&mut self, ch: char) -> Option<Answer> {
+        let swap = self.found.front()?;
+        let confirmed = std::mem::take(&mut self.discarding);
+        match ch {
+            'x' if !confirmed => {
+                self.discarding = true;
+                return None;
+            }
+            'd' => {
+                let saved = swap
+                    .path
+                    .as_ref()
+                    .and_then(|path| fs::read_to_string(path).ok());
+                let saved = saved.unwrap_or_default().replace("\r\n", "\n");
+                return Some(Answer::Diff(diff_text(&saved, &swap.text)));
+            }
+            'r' | 'x' => {}
+            _ => return None,
+        }
+        let swap = self.found.pop_front()?;
+        if let Err(e) = fs::remove_file(&swap.location) {
+            (self.errors).push(format!(
+                "Could not remove {}: {}",
+                swap.location.display(),
+                e
+            ));
+        }
+        (ch == 'r').then_some(Answer::Recover(swap))
+    }
+
+    /// Asks about the swap file left first.
+    pub fn status(&self) -> Option<String> {
+        let swap = self.found.front()?;
+        let name = swap.path.as_deref().map_or("a new file", |path| {
+            Path::new(path)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or(path)
+        });
+        if self.discarding {
+            return Some(format!(
+                "Discard the unsaved changes of {}? x to confirm",
+                name
+            ));
+        }
+        Some(format!(
+            "Unsaved changes of {} found: r to recover, d to see them, x to discard",
+            name
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::File;
+
+    #[test]
+    fn swap_files() {
+        let text = format(Some("/tmp/a b.rs"), "one\n\ntwo\n");
+        let pid = process::id();
+        assert_eq!(
+            parse(&text),
+            Some((
+                pid,
+                Some("/tmp/a b.rs".to_string()),
+                "one\n\ntwo\n".to_string()
+            ))
+        );
+        assert_eq!(parse(&format(None, "")), Some((pid, None, String::new())));
+        assert_eq!(parse("pid 12\nfile a\ntext"), None);
+        assert_eq!(parse("pid x\nuntitled\ntext"), None);
+        assert_eq!(
+            diff_text("a\nb\nc", "a\nB\nc\nd"),
+            "@@ -2,1 +2,1 @@\n-b\n+B\n@@ -4,0 +4,1 @@\n+d"
+        );
+        assert_eq!(diff_text("a", "a"), "No differences");
+    }
+
+    #[test]
+    fn write_and_recover() {
+        let dir = std::env::temp_dir().join(format!("text_editor_swaps_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut swaps = Swaps::in_dir(Some(dir.clone()));
+        let mut files = FileContext::new();
+        files.current().content.paste("draft".to_string());
+        swaps.last_write -= WRITE_INTERVAL;
+        swaps.update(&files);
+        let location = dir.join(format!("untitled-{}-0.swp", process::id()));
+        assert_eq!(
+            fs::read_to_string(&location).ok(),
+            Some(format(None, "draft"))
+        );
+        // Left by an editor not running anymore.
+        let left = dir.join("left.swp");
+        fs::write(&left, "pid 4294967295\nuntitled\nlost").unwrap();
+        let mut next = Swaps::in_dir(Some(dir.clone()));
+        assert_eq!(next.found.len(), 1);
+        assert!(next
+            .status()
+            .unwrap()
+            .starts_with("Unsaved changes of a new file"));
+        assert_eq!(next.answer('q'), None);
+        assert_eq!(
+            next.answer('d'),
+            Some(Answer::Diff("@@ -1,1 +1,1 @@\n-\n+lost".to_string()))
+        );
+        let Some(Answer::Recover(swap)) = next.answer('r') else {
+            panic!("the swap file should be recovered");
+        };
+        assert_eq!((swap.path, swap.text.as_str()), (None, "lost"));
+        assert!(!next.is_asking() && !left.exists());
+        fs::write(&left, "pid 4294967295\nuntitled\nlost").unwrap();
+        let mut next = Swaps::in_dir(Some(dir.clone()));
+        assert_eq!(next.answer('x'), None);
+        assert!(next.status().unwrap().starts_with("Discard"));
+        assert_eq!(next.answer('q'), None);
+        assert!(next.status().unwrap().starts_with("Unsaved"));
+        assert_eq!((next.answer('x'), next.answer('x')), (None, None));
+        assert!(!next.is_asking() && !left.exists());
+        files.add_file(File::new());
+        files.files_mut()[0].mark_saved();
+        swaps.update(&files);
+        assert!(location.exists());
+        swaps.last_write -= WRITE_INTERVAL;
+        swaps.update(&files);
+        assert!(!location.exists());
+        assert_eq!((swaps.take_errors(), next.take_errors()), (None, None));
+        swaps.clear();
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn other_editors() {
+        let dir = std::env::temp_dir().join(format!("text_editor_others_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        // Written by a running editor, here the first process.
+        let location = dir.join(format!("untitled-{}-0.swp", process::id()));
+        let theirs = "pid 1\nuntitled\ntheirs";
+        fs::write(&location, theirs).unwrap();
+        let mut swaps = Swaps::in_dir(Some(dir.clone()));
+        assert!(!swaps.is_asking());
+        let mut files = FileContext::new();
+        files.current().content.paste("ours".to_string());
+        for _ in 0..2 {
+            swaps.last_write -= WRITE_INTERVAL;
+            swaps.update(&files);
+        }
+        assert_eq!(fs::read_to_string(&location).ok().as_deref(), Some(theirs));
+        assert_eq!(swaps.take_errors().map(|e| e.lines().count()), Some(1));
+        swaps.clear();
+        assert!(location.exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,31 +1,359 @@
-use std::usize;
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    ops::{Deref, DerefMut},
+    rc::Rc,
+    sync::atomic::{AtomicU64, Ordering},
+    usize,
+};
+
+use crate::language::{Language, PLAIN_TEXT};
+
+mod emacs;
+mod snippet;
+mod transform;
+mod vim;
+
+pub(crate) use vim::{Motion, Region};
+
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum IndentStyle {
+    Tabs,
+    Spaces(usize),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum SelectionMode {
+    Linear,
+    Block,
+}
+
+/// The cursors and selection of one view of a text.
+#[derive(Clone)]
+pub(crate) struct CursorState {
+    cursor: (usize, usize),
+    secondary_cursors: Vec<(usize, usize)>,
+    selection: Option<((usize, usize), SelectionMode)>,
+}
+
+impl CursorState {
+    /// Keeps the cursors on their lines when `removed` lines at `at` are
+    /// replaced by `inserted` ones, like `shift_line_marks`. Cursors on the
+    /// removed lines go to the start of the line after the new ones.
+    pub fn shift_lines(&mut self, at: usize, removed: usize, inserted: usize) {
+        let shift = |(l, c): (usize, usize)| match l {
+            l if l < at || l < at + removed.min(inserted) => (l, c),
+            l if l < at + removed => (at + inserted, 0),
+            l => (l + inserted - removed, c),
+        };
+        self.cursor = shift(self.cursor);
+        for cursor in self.secondary_cursors.iter_mut() {
+            *cursor = shift(*cursor);
+        }
+        self.selection = self.selection.map(|(anchor, mode)| (shift(anchor), mode));
+    }
+}
+
+/// The lines of a text, with a revision changing whenever they may have been
+/// edited, that is on every mutable access.
+#[derive(Clone)]
+struct Lines {
+    lines: Vec<Vec<char>>,
+    revision: u64,
+}
+
+/// Returns a revision never returned before, so that texts replacing each
+/// other do not share one.
+fn next_revision() -> u64 {
+    static REVISION: AtomicU64 = AtomicU64::new(0);
+    REVISION.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+impl Lines {
+    fn new(lines: Vec<Vec<char>>) -> Self {
+        Lines {
+            lines,
+            revision: next_revision(),
+        }
+    }
+
+    /// Changes the revision, for what is derived from the lines and more.
+    fn touch(&mut self) {
+        self.revision = next_revision();
+    }
+}
+
+impl Deref for Lines {
+    type Target = Vec<Vec<char>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.lines
+    }
+}
+
+impl DerefMut for Lines {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.touch();
+        &mut self.lines
+    }
+}
+
+/// A value computed from the text, kept until its revision changes.
+#[derive(Clone, Default)]
+struct Cached<T>(RefCell<Option<(u64, Rc<T>)>>);
+
+impl<T> Cached<T> {
+    fn get<F: FnOnce() -> T>(&self, revision: u64, compute: F) -> Rc<T> {
+        if let Some((cached, value)) = &*self.0.borrow() {
+            if *cached == revision {
+                return value.clone();
+            }
+        }
+        let value = Rc::new(compute());
+        *self.0.borrow_mut() = Some((revision, value.clone()));
+        value
+    }
+}
 
 #[derive(Clone)]
 pub(crate) struct TextContent {
-    content: Vec<Vec<char>>,
+    content: Lines,
     cursor: (usize, usize),
+    secondary_cursors: Vec<(usize, usize)>,
+    /// Block selections store visual columns, other cursors store character
+    /// indices.
+    selection: Option<((usize, usize), SelectionMode)>,
+    tab_width: usize,
+    indent_style: IndentStyle,
+    language: &'static Language,
+    auto_close: bool,
+    /// First lines of the folded regions.
+    folded: Vec<usize>,
+    bookmarks: Vec<usize>,
+    /// The fields of the snippet being filled in.
+    snippet: Option<snippet::SnippetFields>,
+    code_mask: Cached<Vec<Vec<bool>>>,
+    unmatched_brackets: Cached<Vec<(usize, usize)>>,
+    fold_regions: Cached<Vec<(usize, usize)>>,
+    /// The lines replaced since `take_line_shifts`, as `(at, removed,
+    /// inserted)`, for the cursors of the other views of the text.
+    line_shifts: Vec<(usize, usize, usize)>,
+}
+
+/// Returns the column at which the `c`-th character of `line` is displayed.
+/// Positions past the end of the line count as one column each.
+pub fn visual_column<I: IntoIterator<Item = char>>(line: I, c: usize, tab_width: usize) -> usize {
+    let mut column = 0;
+    let mut n = 0;
+    for ch in line.into_iter().take(c) {
+        column += char_width(ch, column, tab_width);
+        n += 1;
+    }
+    column + c - n
+}
+
+/// Inverse of `visual_column`: returns the index of the character displayed
+/// at `column`.
+pub fn char_column<I: IntoIterator<Item = char>>(
+    line: I,
+    column: usize,
+    tab_width: usize,
+) -> usize {
+    let mut current = 0;
+    let mut n = 0;
+    for ch in line {
+        let width = char_width(ch, current, tab_width);
+        if current + width > column {
+            return n;
+        }
+        current += width;
+        n += 1;
+    }
+    n + column - current
+}
+
+pub fn expand_tabs(line: &str, tab_width: usize) -> String {
+    let mut res = String::new();
+    for ch in line.chars() {
+        if ch == '\t' {
+            let width = char_width(ch, res.chars().count(), tab_width);
+            res.push_str(&" ".repeat(width));
+        } else {
+            res.push(ch);
+        }
+    }
+    res
+}
+
+fn starts_with(line: &[char], c: usize, token: &str) -> bool {
+    let token: Vec<char> = token.chars().collect();
+    line[c..].starts_with(&token)
+}
+
+/// Returns the length of the character literal starting at `c`, as `'{'` or
+/// `'\n'`, or `None` when the quote starts a lifetime as `'a`.
+fn char_literal_len(line: &[char], c: usize) -> Option<usize> {
+    let end = match line.get(c + 1)? {
+        '\\' => (c + 3..line.len()).find(|&i| line[i] == '\'')?,
+        '\'' => return None,
+        _ => c + 2,
+    };
+    (line.get(end) == Some(&'\'')).then_some(end + 1 - c)
+}
+
+pub fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+fn is_identifier_char(ch: char) -> bool {
+    is_word_char(ch) || ch == '-'
+}
+
+fn is_not_whitespace(ch: char) -> bool {
+    !ch.is_whitespace()
+}
+
+fn is_blank(ch: char) -> bool {
+    ch == ' ' || ch == '\t'
+}
+
+pub fn char_width(ch: char, column: usize, tab_width: usize) -> usize {
+    if ch == '\t' {
+        tab_width - column % tab_width
+    } else {
+        1
+    }
 }
 
 impl TextContent {
     pub fn new() -> Self {
         TextContent {
-            content: {
-                let mut c = Vec::new();
-                c.push(Vec::new());
-                c
-            },
+            content: Lines::new(vec![Vec::new()]),
             cursor: (0, 0),
+            secondary_cursors: Vec::new(),
+            selection: None,
+            tab_width: DEFAULT_TAB_WIDTH,
+            indent_style: IndentStyle::Spaces(DEFAULT_TAB_WIDTH),
+            language: &PLAIN_TEXT,
+            auto_close: true,
+            folded: Vec::new(),
+            bookmarks: Vec::new(),
+            snippet: None,
+            code_mask: Cached::default(),
+            unmatched_brackets: Cached::default(),
+            fold_regions: Cached::default(),
+            line_shifts: Vec::new(),
         }
     }
 
     pub fn from_string(text: String) -> Self {
         let mut res = Self::new();
-        for line in text.lines() {
-            res.content.push(line.chars().collect());
-        }
+        res.content = Lines::new(
+            text.split('\n')
+                .map(|line| line.strip_suffix('\r').unwrap_or(line).chars().collect())
+                .collect(),
+        );
         res
     }
 
+    pub fn set_language(&mut self, language: &'static Language) {
+        self.language = language;
+        self.content.touch();
+    }
+
+    pub fn set_auto_close(&mut self, auto_close: bool) {
+        self.auto_close = auto_close;
+    }
+
+    pub fn get_language(&self) -> &'static Language {
+        self.language
+    }
+
+    pub fn get_tab_width(&self) -> usize {
+        self.tab_width
+    }
+
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = tab_width.max(1);
+        // The fold regions follow the width of the indentation.
+        self.content.touch();
+    }
+
+    pub fn set_indent_style(&mut self, indent_style: IndentStyle) {
+        self.indent_style = indent_style;
+    }
+
+    /// Guesses how the text is indented from the leading whitespace of its
+    /// lines. Returns `None` when no line is indented.
+    pub fn detect_indent_style(&self) -> Option<IndentStyle> {
+        let mut tabs = 0;
+        let mut spaces = Vec::new();
+        for line in self.content.iter() {
+            match line.first() {
+                Some('\t') => tabs += 1,
+                Some(' ') => {
+                    let n = line.iter().take_while(|ch| **ch == ' ').count();
+                    if line.get(n).is_some_and(|ch| !ch.is_whitespace()) {
+                        spaces.push(n)
+                    }
+                }
+                _ => {}
+            }
+        }
+        if tabs == 0 && spaces.is_empty() {
+            None
+        } else if tabs > spaces.len() {
+            Some(IndentStyle::Tabs)
+        } else {
+            let width = [8, 4, 3, 2]
+                .into_iter()
+                .find(|w| spaces.iter().filter(|n| *n % w == 0).count() * 10 >= spaces.len() * 9)
+                .unwrap_or(spaces.iter().copied().min().unwrap());
+            Some(IndentStyle::Spaces(width))
+        }
+    }
+
+    /// Returns the lines indented with both tabs and spaces.
+    pub fn mixed_indentation_lines(&self) -> Vec<usize> {
+        (0..self.content.len())
+            .filter(|l| {
+                let indentation = &self.content[*l][..self.indentation_length(*l)];
+                indentation.contains(&'\t') && indentation.contains(&' ')
+            })
+            .collect()
+    }
+
+    fn indent_unit(&self, column: usize) -> String {
+        match self.indent_style {
+            IndentStyle::Tabs => "\t".to_string(),
+            IndentStyle::Spaces(n) => " ".repeat(n - column % n),
+        }
+    }
+
+    fn to_visual(&self, cursor: (usize, usize)) -> (usize, usize) {
+        let (l, c) = cursor;
+        (
+            l,
+            visual_column(self.content[l].iter().cloned(), c, self.tab_width),
+        )
+    }
+
+    fn to_char(&self, cursor: (usize, usize)) -> (usize, usize) {
+        let (l, c) = cursor;
+        (
+            l,
+            char_column(self.content[l].iter().cloned(), c, self.tab_width),
+        )
+    }
+
+    /// Returns a number changing whenever the text may have changed, to know
+    /// when what was computed from it is outdated.
+    pub fn revision(&self) -> u64 {
+        self.content.revision
+    }
+
     pub fn get_text(&self) -> Vec<String> {
         self.content.iter().map(|l| l.iter().collect()).collect()
     }
@@ -35,7 +363,10 @@ impl TextContent {
     }
 
     pub fn get_cursor(&self) -> (usize, usize) {
-        let (l, c) = self.cursor.clone();
+        let (l, c) = match self.selection {
+            Some((_, SelectionMode::Block)) => self.to_char(self.cursor),
+            _ => self.cursor,
+        };
         if c >= self.content[l].len() {
             (l, self.content[l].len())
         } else {
@@ -43,174 +374,2522 @@ impl TextContent {
         }
     }
 
-    pub fn _empty(&self) -> bool {
-        self.content.is_empty() || self.content[0].is_empty()
+    /// Returns every cursor, the primary one first.
+    pub fn get_cursors(&self) -> Vec<(usize, usize)> {
+        if let Some(((anchor_l, _), SelectionMode::Block)) = self.selection {
+            let (l, c) = self.cursor;
+            let mut cursors = vec![self.to_char((l, c))];
+            cursors.extend(
+                (anchor_l.min(l)..=anchor_l.max(l))
+                    .filter(|i| *i != l)
+                    .map(|i| self.to_char((i, c))),
+            );
+            return cursors;
+        }
+        let mut cursors = vec![self.get_cursor()];
+        cursors.extend(self.secondary_cursors.iter().map(|c| self.clamp_cursor(*c)));
+        cursors
     }
 
-    pub fn line_count(&self) -> usize {
-        self.content.len()
+    pub fn cursor_state(&self) -> CursorState {
+        CursorState {
+            cursor: self.cursor,
+            secondary_cursors: self.secondary_cursors.clone(),
+            selection: self.selection,
+        }
     }
 
-    pub fn longest_line_length(&self) -> usize {
-        self.content.iter().map(|l| l.len()).max().unwrap()
+    /// Restores cursors saved by `cursor_state`, moving those left past the
+    /// end of the text or of their line by later edits back into it.
+    pub fn set_cursor_state(&mut self, state: CursorState) {
+        self.cursor = self.clamp_cursor(state.cursor);
+        self.secondary_cursors = (state.secondary_cursors.into_iter())
+            .map(|cursor| self.clamp_cursor(cursor))
+            .collect();
+        self.selection = state.selection.map(|(anchor, mode)| match mode {
+            SelectionMode::Linear => (self.clamp_cursor(anchor), mode),
+            // The column of a block is visual, and may be past the line.
+            SelectionMode::Block => ((anchor.0.min(self.content.len() - 1), anchor.1), mode),
+        });
+        self.merge_cursors();
     }
 
-    pub fn _current_line_length(&self) -> usize {
-        let (l, _) = self.cursor;
-        self.content[l].len()
+    /// Returns the lines replaced since the last call, as `(at, removed,
+    /// inserted)`.
+    pub fn take_line_shifts(&mut self) -> Vec<(usize, usize, usize)> {
+        std::mem::take(&mut self.line_shifts)
     }
 
-    pub fn size(&self) -> (usize, usize) {
-        (self.line_count(), self.longest_line_length())
+    fn clamp_cursor(&self, cursor: (usize, usize)) -> (usize, usize) {
+        let (l, c) = cursor;
+        let l = l.min(self.content.len() - 1);
+        (l, c.min(self.content[l].len()))
     }
 
-    pub fn append(&mut self, text: String) {
-        let text = text.chars().collect();
-        if self.content.is_empty() {
-            self.content.push(text);
+    fn offset_of(&self, cursor: (usize, usize)) -> usize {
+        let (l, c) = self.clamp_cursor(cursor);
+        self.content[..l]
+            .iter()
+            .map(|line| line.len() + 1)
+            .sum::<usize>()
+            + c
+    }
+
+    fn position_of(&self, offset: usize) -> (usize, usize) {
+        let mut offset = offset;
+        for (l, line) in self.content.iter().enumerate() {
+            if offset <= line.len() {
+                return (l, offset);
+            }
+            offset -= line.len() + 1;
+        }
+        let l = self.content.len() - 1;
+        (l, self.content[l].len())
+    }
+
+    fn char_count(&self) -> usize {
+        self.content
+            .iter()
+            .map(|line| line.len() + 1)
+            .sum::<usize>()
+            - 1
+    }
+
+    /// Runs `op` once per cursor, from the last cursor in the text to the first,
+    /// so that each edit only shifts the cursors that were already handled.
+    /// `op` also receives the rank of the cursor in the text.
+    fn for_each_cursor<F: FnMut(&mut Self, usize)>(&mut self, mut op: F) {
+        if self.secondary_cursors.is_empty() {
+            op(self, 0);
             return;
         }
-        let (l, mut c) = self.cursor;
-        if self.cursor.1 >= self.content[l].len() {
-            self.snap_cursor_end_of_line();
-            c = self.cursor.1;
+        let mut pending: Vec<(bool, (usize, usize))> = vec![(true, self.cursor)];
+        pending.extend(self.secondary_cursors.drain(..).map(|c| (false, c)));
+        pending.sort_by_key(|(_, c)| self.clamp_cursor(*c));
+        let mut done: Vec<(bool, (usize, usize), usize)> = Vec::new();
+        while let Some((primary, cursor)) = pending.pop() {
+            let count = self.char_count();
+            self.cursor = cursor;
+            op(self, pending.len());
+            let delta = self.char_count() as isize - count as isize;
+            if delta != 0 {
+                for (_, c, offset) in done.iter_mut() {
+                    *offset = (*offset as isize + delta) as usize;
+                    *c = self.position_of(*offset);
+                }
+            }
+            done.push((primary, self.cursor, self.offset_of(self.cursor)));
         }
-        let (left, right) = self.content[l].split_at(c);
-        let new = [left, &text, right].concat();
-        self.content[l] = new;
-        self.move_cursor_right(text.len());
+        for (primary, cursor, _) in done {
+            if primary {
+                self.cursor = cursor;
+            } else {
+                self.secondary_cursors.push(cursor);
+            }
+        }
+        self.merge_cursors();
+        self.reveal_cursors();
     }
 
-    pub fn snap_cursor_end_of_line(&mut self) {
-        let (l, _) = self.cursor;
-        let c = self.content[l].len();
-        self.cursor = (l, c);
+    fn merge_cursors(&mut self) {
+        let primary = self.get_cursor();
+        let mut seen = vec![primary];
+        let mut cursors = Vec::new();
+        for cursor in self.secondary_cursors.drain(..).collect::<Vec<_>>() {
+            let clamped = self.clamp_cursor(cursor);
+            if !seen.contains(&clamped) {
+                seen.push(clamped);
+                cursors.push(cursor);
+            }
+        }
+        self.secondary_cursors = cursors;
     }
 
-    pub fn snap_cursor_start_of_line(&mut self) {
-        let (l, _) = self.cursor;
-        self.cursor = (l, 0);
+    pub fn clear_secondary_cursors(&mut self) {
+        self.secondary_cursors.clear();
     }
 
-    pub fn move_cursor_up(&mut self, n: usize) {
-        let (l, c) = self.cursor;
-        let l = if l < n { 0 } else { l - n };
-        self.cursor = (l, c);
+    /// Adds a cursor at `cursor`, which becomes the primary one.
+    pub fn add_cursor(&mut self, cursor: (usize, usize)) {
+        let old = self.cursor;
+        self.place_cursor(cursor);
+        self.secondary_cursors.push(old);
+        self.merge_cursors();
+        self.reveal_cursors();
     }
 
-    pub fn move_cursor_down(&mut self, n: usize) {
-        let (l, c) = self.cursor;
-        let l = {
-            let l = l + n;
-            if l >= self.content.len() {
-                self.content.len() - 1
-            } else {
-                l
-            }
-        };
-        self.cursor = (l, c);
+    pub fn add_cursor_above(&mut self) {
+        let top = self
+            .get_cursors()
+            .into_iter()
+            .map(|(l, _)| l)
+            .min()
+            .unwrap();
+        if top > 0 {
+            let c = self.cursor.1;
+            self.add_cursor((top - 1, c));
+            self.cursor.1 = c;
+        }
     }
 
-    pub fn move_cursor_right(&mut self, n: usize) {
-        let (l, c) = self.cursor;
-        let no_more_characters = c >= self.content[l].len() && l == self.content.len() - 1;
-        let end_of_line = c >= self.content[l].len();
-        if no_more_characters {
-        } else if end_of_line {
-            self.move_cursor_down(1);
-            self.snap_cursor_start_of_line();
-        } else {
-            let c = c + n;
-            self.cursor = (l, c);
+    pub fn add_cursor_below(&mut self) {
+        let bottom = self
+            .get_cursors()
+            .into_iter()
+            .map(|(l, _)| l)
+            .max()
+            .unwrap();
+        if bottom + 1 < self.content.len() {
+            let c = self.cursor.1;
+            self.add_cursor((bottom + 1, c));
+            self.cursor.1 = c;
         }
     }
 
-    pub fn move_cursor_left(&mut self, n: usize) {
-        let (l, mut c) = self.cursor;
-        if c > self.content[l].len() {
-            self.snap_cursor_end_of_line();
-            c = self.cursor.1;
+    fn word_bounds(&self, cursor: (usize, usize)) -> Option<(usize, usize)> {
+        self.bounds_around(cursor, is_word_char)
+    }
+
+    /// Returns the bounds of the run of characters satisfying `part_of_word`
+    /// around `cursor`.
+    fn bounds_around(
+        &self,
+        cursor: (usize, usize),
+        part_of_word: fn(char) -> bool,
+    ) -> Option<(usize, usize)> {
+        let (l, c) = self.clamp_cursor(cursor);
+        let line = &self.content[l];
+        let mut start = c;
+        while start > 0 && part_of_word(line[start - 1]) {
+            start -= 1;
         }
-        let no_more_characters = c == 0 && l == 0;
-        let beginning_of_line = c < n;
-        if no_more_characters {
-        } else if beginning_of_line {
-            self.move_cursor_up(1);
-            self.snap_cursor_end_of_line()
+        let mut end = c;
+        while end < line.len() && part_of_word(line[end]) {
+            end += 1;
+        }
+        if start == end {
+            None
         } else {
-            self.cursor = (l, c - n);
+            Some((start, end))
+        }
+    }
+
+    /// Adds a cursor on the next occurrence of the word under the primary
+    /// cursor, at the same position inside the word.
+    pub fn add_cursor_at_next_occurrence(&mut self) {
+        let (l, c) = self.get_cursor();
+        let Some((start, end)) = self.word_bounds((l, c)) else {
+            return;
         };
+        let word = self.content[l][start..end].to_vec();
+        let cursors = self.get_cursors();
+        let line_count = self.content.len();
+        for i in 0..=line_count {
+            let line_index = (l + i) % line_count;
+            let line = &self.content[line_index];
+            let mut from = if i == 0 { end } else { 0 };
+            while from + word.len() <= line.len() {
+                let found = line[from..from + word.len()] == word[..]
+                    && self.word_bounds((line_index, from)) == Some((from, from + word.len()));
+                let target = (line_index, from + c - start);
+                if found && !cursors.contains(&target) {
+                    self.add_cursor(target);
+                    return;
+                }
+                from += 1;
+            }
+        }
     }
 
-    pub fn set_cursor(&mut self, cursor: (usize, usize)) {
-        let (mut l, mut c) = cursor;
-        if l >= self.content.len() {
-            l = self.content.len() - 1;
-            c = self.content[l].len();
+    pub fn clear_selection(&mut self) {
+        if let Some((_, SelectionMode::Block)) = self.selection {
+            self.cursor = self.to_char(self.cursor);
         }
-        if c > self.content[l].len() {
-            c = self.content[l].len();
+        self.selection = None;
+    }
+
+    /// Starts a selection at the cursor, or converts the current one to
+    /// `mode`.
+    fn start_selection(&mut self, mode: SelectionMode) {
+        self.secondary_cursors.clear();
+        let (anchor, cursor) = match self.selection {
+            Some((_, current)) if current == mode => return,
+            Some((anchor, SelectionMode::Block)) => {
+                (self.to_char(anchor), self.to_char(self.cursor))
+            }
+            Some((anchor, SelectionMode::Linear)) => (anchor, self.cursor),
+            None => (self.get_cursor(), self.get_cursor()),
+        };
+        match mode {
+            SelectionMode::Linear => {
+                self.selection = Some((anchor, mode));
+                self.cursor = cursor;
+            }
+            SelectionMode::Block => {
+                self.selection = Some((self.to_visual(anchor), mode));
+                self.cursor = self.to_visual(cursor);
+            }
         }
-        self.cursor = (l, c);
     }
 
-    pub fn remove(&mut self) {
-        let (l, mut c) = self.cursor;
-        if c > self.content[l].len() {
-            self.snap_cursor_end_of_line();
-            c = self.cursor.1;
+    /// Moves the cursor to `cursor`, selecting everything from where the
+    /// selection started. Block selections may reach past the end of lines.
+    pub fn select_to(&mut self, cursor: (usize, usize), mode: SelectionMode) {
+        self.start_selection(mode);
+        let (l, c) = cursor;
+        match mode {
+            SelectionMode::Linear => self.place_cursor(cursor),
+            SelectionMode::Block => {
+                let l = l.min(self.content.len() - 1);
+                self.cursor = self.to_visual((l, c));
+            }
         }
-        let nothing_to_remove = c == 0 && l == 0;
-        let begining_of_line = c == 0;
-        if nothing_to_remove {
-        } else if begining_of_line {
-            self.move_cursor_up(1);
-            self.snap_cursor_end_of_line();
-            let (l, _) = self.cursor;
-            let removed_line = self.content[l + 1].clone();
-            self.content[l].extend(removed_line);
-            self.content.remove(l + 1);
-        } else {
-            self.content[l].remove(c - 1);
-            self.cursor.1 -= 1;
+    }
+
+    pub fn extend_selection_up(&mut self, mode: SelectionMode) {
+        self.start_selection(mode);
+        match mode {
+            SelectionMode::Linear => self.move_cursor_up(1),
+            SelectionMode::Block => self.cursor.0 = self.cursor.0.saturating_sub(1),
         }
     }
 
-    pub fn move_line_up(&mut self) {
-        let (l, _) = self.cursor;
-        if l == 0 {
-            return;
+    pub fn extend_selection_down(&mut self, mode: SelectionMode) {
+        self.start_selection(mode);
+        match mode {
+            SelectionMode::Linear => self.move_cursor_down(1),
+            SelectionMode::Block => self.cursor.0 = (self.cursor.0 + 1).min(self.content.len() - 1),
         }
-        let line = self.content.remove(l);
-        self.content.insert(l - 1, line);
-        self.cursor.0 -= 1;
     }
 
-    pub fn move_line_down(&mut self) {
-        let (l, _) = self.cursor;
-        if l >= self.content.len() - 1 {
-            return;
+    pub fn extend_selection_left(&mut self, mode: SelectionMode) {
+        self.start_selection(mode);
+        match mode {
+            SelectionMode::Linear => {
+                self.cursor = self.position_of(self.offset_of(self.cursor).saturating_sub(1))
+            }
+            SelectionMode::Block => self.cursor.1 = self.cursor.1.saturating_sub(1),
         }
-        let line = self.content.remove(l);
-        self.content.insert(l + 1, line);
-        self.cursor.0 += 1;
     }
 
-    pub fn new_line(&mut self) {
-        let (l, _) = self.cursor;
-        self.content.insert(l + 1, Vec::new());
-        self.cursor = (l + 1, 0);
+    pub fn extend_selection_right(&mut self, mode: SelectionMode) {
+        self.start_selection(mode);
+        match mode {
+            SelectionMode::Linear => {
+                self.cursor = self.position_of(self.offset_of(self.cursor) + 1)
+            }
+            SelectionMode::Block => self.cursor.1 += 1,
+        }
     }
 
-    pub fn break_line(&mut self) {
-        let (l, c) = self.cursor;
-        if c >= self.content[l].len() {
-            self.new_line();
-            return;
+    fn linear_bounds(&self, anchor: (usize, usize)) -> ((usize, usize), (usize, usize)) {
+        let (a, b) = (self.clamp_cursor(anchor), self.get_cursor());
+        (a.min(b), a.max(b))
+    }
+
+    fn block_bounds(&self, anchor: (usize, usize)) -> ((usize, usize), (usize, usize)) {
+        let ((l1, c1), (l2, c2)) = (anchor, self.cursor);
+        ((l1.min(l2), l2.max(l1)), (c1.min(c2), c2.max(c1)))
+    }
+
+    /// Returns the selected part of each line as `(line, start, end)`.
+    pub fn selection_ranges(&self) -> Vec<(usize, usize, usize)> {
+        match self.selection {
+            None => Vec::new(),
+            Some((anchor, SelectionMode::Linear)) => {
+                let ((l1, c1), (l2, c2)) = self.linear_bounds(anchor);
+                (l1..=l2)
+                    .map(|l| {
+                        let start = if l == l1 { c1 } else { 0 };
+                        let end = if l == l2 { c2 } else { self.content[l].len() };
+                        (l, start, end)
+                    })
+                    .collect()
+            }
+            Some((anchor, SelectionMode::Block)) => {
+                let ((l1, l2), (left, right)) = self.block_bounds(anchor);
+                (l1..=l2)
+                    .map(|l| {
+                        let (_, start) = self.to_char((l, left));
+                        let (_, end) = self.to_char((l, right));
+                        (l, start, end)
+                    })
+                    .collect()
+            }
         }
-        let right = self.content[l].split_off(c);
-        self.content.insert(l + 1, right);
-        self.move_cursor_down(1);
-        self.snap_cursor_start_of_line();
+    }
+
+    pub fn get_selected_text(&self) -> Option<String> {
+        let empty = match self.selection {
+            None => true,
+            Some((anchor, SelectionMode::Linear)) => self.clamp_cursor(anchor) == self.get_cursor(),
+            Some((anchor, SelectionMode::Block)) => {
+                let (_, (left, right)) = self.block_bounds(anchor);
+                left == right
+            }
+        };
+        if empty {
+            return None;
+        }
+        let lines: Vec<String> = self
+            .selection_ranges()
+            .into_iter()
+            .map(|(l, start, end)| {
+                let line = &self.content[l];
+                line[start.min(line.len())..end.min(line.len())]
+                    .iter()
+                    .collect()
+            })
+            .collect();
+        Some(lines.join("\n"))
+    }
+
+    /// Deletes the selected text and leaves a cursor where it started, one
+    /// per line for block selections. Returns false if nothing was selected.
+    fn take_selection(&mut self, pad: bool) -> bool {
+        match self.selection {
+            None => false,
+            Some((anchor, SelectionMode::Linear)) => {
+                self.selection = None;
+                let (start, end) = self.linear_bounds(anchor);
+                if start == end {
+                    return false;
+                }
+                self.delete_range(start, end);
+                self.cursor = start;
+                true
+            }
+            Some((_, SelectionMode::Block)) => {
+                let removed = self.clear_block(pad);
+                let cursors = self.get_cursors();
+                self.selection = None;
+                self.cursor = cursors[0];
+                self.secondary_cursors = cursors[1..].to_vec();
+                removed
+            }
+        }
+    }
+
+    /// Deletes the text inside the block selection and shrinks it to its left
+    /// edge. Lines too short to reach the block are padded with spaces when
+    /// `pad` is set. Returns false if the block was already empty.
+    fn clear_block(&mut self, pad: bool) -> bool {
+        let Some((anchor, SelectionMode::Block)) = self.selection else {
+            return false;
+        };
+        let ((l1, l2), (left, right)) = self.block_bounds(anchor);
+        let tab_width = self.tab_width;
+        for line in self.content[l1..=l2].iter_mut() {
+            let width = visual_column(line.iter().cloned(), line.len(), tab_width);
+            if pad && width < left {
+                line.resize(line.len() + left - width, ' ');
+            }
+            let len = line.len();
+            let start = char_column(line.iter().cloned(), left, tab_width);
+            let end = char_column(line.iter().cloned(), right, tab_width);
+            line.drain(start.min(len)..end.min(len));
+        }
+        self.set_block_column(left);
+        left != right
+    }
+
+    fn set_block_column(&mut self, c: usize) {
+        if let Some(((l, _), mode)) = self.selection {
+            self.selection = Some(((l, c), mode));
+        }
+        self.cursor.1 = c;
+    }
+
+    fn block_selection_lines(&self) -> Option<(usize, usize)> {
+        match self.selection {
+            Some((anchor, SelectionMode::Block)) => Some(self.block_bounds(anchor).0),
+            _ => None,
+        }
+    }
+
+    fn block_append(&mut self, text: &str) {
+        let Some((l1, l2)) = self.block_selection_lines() else {
+            return;
+        };
+        self.clear_block(true);
+        let left = self.cursor.1;
+        let text: Vec<char> = text.chars().collect();
+        let tab_width = self.tab_width;
+        for line in self.content[l1..=l2].iter_mut() {
+            let c = char_column(line.iter().cloned(), left, tab_width);
+            line.splice(c..c, text.iter().cloned());
+        }
+        let (l, c) = self.to_char(self.cursor);
+        self.cursor = self.to_visual((l, c + text.len()));
+        self.set_block_column(self.cursor.1);
+    }
+
+    fn block_remove(&mut self) {
+        let Some((l1, l2)) = self.block_selection_lines() else {
+            return;
+        };
+        if self.clear_block(false) || self.cursor.1 == 0 {
+            return;
+        }
+        let left = self.cursor.1;
+        let (l, c) = self.to_char(self.cursor);
+        let tab_width = self.tab_width;
+        for line in self.content[l1..=l2].iter_mut() {
+            let c = char_column(line.iter().cloned(), left, tab_width);
+            if c > 0 && c <= line.len() {
+                line.remove(c - 1);
+            }
+        }
+        self.cursor = self.to_visual((l, c - 1));
+        self.set_block_column(self.cursor.1);
+    }
+
+    fn delete_range(&mut self, start: (usize, usize), end: (usize, usize)) {
+        let ((l1, c1), (l2, c2)) = (start, end);
+        let tail = self.content[l2].split_off(c2);
+        self.content[l1].truncate(c1);
+        self.content[l1].extend(tail);
+        self.content.drain(l1 + 1..=l2);
+        self.shift_line_marks(l1 + 1, l2 - l1, 0);
+    }
+
+    /// Removes the selection from the text and returns it.
+    pub fn cut(&mut self) -> Option<String> {
+        let text = self.get_selected_text();
+        if text.is_some() && !self.clear_block(false) {
+            self.take_selection(false);
+        }
+        text
+    }
+
+    /// Inserts `text` at every cursor, replacing the selection. When there is
+    /// one cursor per line of `text`, each cursor receives its own line.
+    pub fn paste(&mut self, text: String) {
+        let lines: Vec<&str> = text.split('\n').collect();
+        if lines.len() == 1 && self.block_selection_lines().is_some() {
+            self.block_append(lines[0]);
+            return;
+        }
+        self.take_selection(true);
+        let per_cursor = lines.len() > 1 && lines.len() == self.secondary_cursors.len() + 1;
+        self.for_each_cursor(|content, i| {
+            if per_cursor {
+                content.append_at_cursor(lines[i]);
+            } else {
+                content.insert_at_cursor(&lines);
+            }
+        });
+    }
+
+    fn insert_at_cursor(&mut self, lines: &[&str]) {
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                self.break_line_at_cursor();
+            }
+            self.append_at_cursor(line);
+        }
+    }
+
+    pub fn _empty(&self) -> bool {
+        self.content.is_empty() || self.content[0].is_empty()
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.content.len()
+    }
+
+    pub fn longest_line_length(&self) -> usize {
+        self.content
+            .iter()
+            .map(|l| visual_column(l.iter().cloned(), l.len(), self.tab_width))
+            .max()
+            .unwrap()
+    }
+
+    pub fn _current_line_length(&self) -> usize {
+        let (l, _) = self.cursor;
+        self.content[l].len()
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.line_count(), self.longest_line_length())
+    }
+
+    pub fn append(&mut self, text: String) {
+        if self.block_selection_lines().is_some() {
+            self.block_append(&text);
+            return;
+        }
+        self.take_selection(true);
+        self.for_each_cursor(|content, _| {
+            content.dedent_before_closing_bracket(&text);
+            content.type_at_cursor(&text)
+        });
+    }
+
+    /// Returns the part of the word before the cursor.
+    pub fn word_before_cursor(&self) -> String {
+        let (l, c) = self.clamp_cursor(self.cursor);
+        let line = &self.content[l][..c];
+        let start = line.len()
+            - line
+                .iter()
+                .rev()
+                .take_while(|ch| is_word_char(**ch))
+                .count();
+        line[start..].iter().collect()
+    }
+
+    /// Replaces the part of the word before each cursor by `word`.
+    pub fn complete_word(&mut self, word: &str) {
+        self.take_selection(true);
+        self.for_each_cursor(|content, _| {
+            let (l, c) = content.get_cursor();
+            let start = c - content.word_before_cursor().chars().count();
+            content.delete_range((l, start), (l, c));
+            content.cursor = (l, start);
+            content.insert_at_cursor(&word.split('\n').collect::<Vec<_>>());
+        });
+    }
+
+    /// Appends typed text, closing the pairs of the language or typing over
+    /// their closing character.
+    fn type_at_cursor(&mut self, text: &str) {
+        let mut chars = text.chars();
+        let (Some(ch), None, true) = (chars.next(), chars.next(), self.auto_close) else {
+            self.append_at_cursor(text);
+            return;
+        };
+        let (l, c) = self.get_cursor();
+        let next = self.content[l].get(c).copied();
+        let closes = self.language.auto_pairs.iter().any(|(open, close)| {
+            *close == ch && (*open != ch || self.in_string_or_comment((l, c)))
+        });
+        if closes && next == Some(ch) {
+            self.cursor = (l, c + 1);
+        } else if let Some(close) = self.auto_closing(ch, (l, c)) {
+            self.append_at_cursor(&format!("{}{}", ch, close));
+            self.cursor = (l, c + 1);
+        } else {
+            self.append_at_cursor(text);
+        }
+    }
+
+    fn auto_closing(&self, open: char, cursor: (usize, usize)) -> Option<char> {
+        let (_, close) = self.language.auto_pairs.iter().find(|(o, _)| *o == open)?;
+        let (l, c) = cursor;
+        let line = &self.content[l];
+        let word_after = line.get(c).is_some_and(|ch| is_word_char(*ch));
+        let word_before = c > 0 && is_word_char(line[c - 1]);
+        if word_after || (open == *close && word_before) || self.in_string_or_comment(cursor) {
+            None
+        } else {
+            Some(*close)
+        }
+    }
+
+    /// Removes one level of indentation when a closing bracket is typed on a
+    /// line holding only whitespace.
+    fn dedent_before_closing_bracket(&mut self, text: &str) {
+        let mut chars = text.chars();
+        let (Some(ch), None) = (chars.next(), chars.next()) else {
+            return;
+        };
+        let (l, c) = self.get_cursor();
+        let line = &self.content[l];
+        if !self.language.is_closing_bracket(ch) || !line[..c].iter().all(|ch| is_blank(*ch)) {
+            return;
+        }
+        let n = match line.first() {
+            Some('\t') => 1,
+            _ => line
+                .iter()
+                .take(self.indent_width())
+                .take_while(|ch| **ch == ' ')
+                .count(),
+        };
+        let n = n.min(c);
+        self.content[l].drain(..n);
+        self.cursor = (l, c - n);
+    }
+
+    fn append_at_cursor(&mut self, text: &str) {
+        let text: Vec<char> = text.chars().collect();
+        if self.content.is_empty() {
+            self.content.push(text);
+            return;
+        }
+        let (l, mut c) = self.cursor;
+        if self.cursor.1 >= self.content[l].len() {
+            self.snap_cursor_end_of_line();
+            c = self.cursor.1;
+        }
+        let (left, right) = self.content[l].split_at(c);
+        let new = [left, &text, right].concat();
+        self.content[l] = new;
+        self.move_cursor_right(text.len());
+    }
+
+    pub fn snap_cursor_end_of_line(&mut self) {
+        let (l, _) = self.cursor;
+        let c = self.content[l].len();
+        self.cursor = (l, c);
+    }
+
+    pub fn snap_cursor_start_of_line(&mut self) {
+        let (l, _) = self.cursor;
+        self.cursor = (l, 0);
+    }
+
+    pub fn move_cursors_up(&mut self, n: usize) {
+        self.clear_selection();
+        self.for_each_cursor(|content, _| content.move_cursor_up(n));
+    }
+
+    pub fn move_cursors_down(&mut self, n: usize) {
+        self.clear_selection();
+        self.for_each_cursor(|content, _| content.move_cursor_down(n));
+    }
+
+    pub fn move_cursors_right(&mut self, n: usize) {
+        self.clear_selection();
+        self.for_each_cursor(|content, _| content.move_cursor_right(n));
+    }
+
+    pub fn move_cursors_left(&mut self, n: usize) {
+        self.clear_selection();
+        self.for_each_cursor(|content, _| content.move_cursor_left(n));
+    }
+
+    /// Moves every cursor to its image through `f`.
+    pub fn move_cursors_to<F: Fn((usize, usize)) -> (usize, usize)>(&mut self, f: F) {
+        self.clear_selection();
+        self.for_each_cursor(|content, _| {
+            let cursor = f(content.get_cursor());
+            content.place_cursor(cursor);
+        });
+    }
+
+    pub fn move_cursor_up(&mut self, n: usize) {
+        let (l, c) = self.to_visual(self.cursor);
+        let l = self.visible_line(l, -(n as isize));
+        self.cursor = self.to_char((l, c));
+    }
+
+    pub fn move_cursor_down(&mut self, n: usize) {
+        let (l, c) = self.to_visual(self.cursor);
+        let l = self.visible_line(l, n as isize);
+        self.cursor = self.to_char((l, c));
+    }
+
+    pub fn move_cursor_right(&mut self, n: usize) {
+        let (l, c) = self.cursor;
+        let no_more_characters = c >= self.content[l].len() && l == self.content.len() - 1;
+        let end_of_line = c >= self.content[l].len();
+        if no_more_characters {
+        } else if end_of_line {
+            self.move_cursor_down(1);
+            self.snap_cursor_start_of_line();
+        } else {
+            let c = c + n;
+            self.cursor = (l, c);
+        }
+    }
+
+    pub fn move_cursor_left(&mut self, n: usize) {
+        let (l, mut c) = self.cursor;
+        if c > self.content[l].len() {
+            self.snap_cursor_end_of_line();
+            c = self.cursor.1;
+        }
+        let no_more_characters = c == 0 && l == 0;
+        let beginning_of_line = c < n;
+        if no_more_characters {
+        } else if beginning_of_line {
+            self.move_cursor_up(1);
+            self.snap_cursor_end_of_line()
+        } else {
+            self.cursor = (l, c - n);
+        };
+    }
+
+    pub fn set_cursor(&mut self, cursor: (usize, usize)) {
+        self.secondary_cursors.clear();
+        self.selection = None;
+        self.place_cursor(cursor);
+        self.reveal_cursors();
+    }
+
+    fn place_cursor(&mut self, cursor: (usize, usize)) {
+        let (mut l, mut c) = cursor;
+        if l >= self.content.len() {
+            l = self.content.len() - 1;
+            c = self.content[l].len();
+        }
+        if c > self.content[l].len() {
+            c = self.content[l].len();
+        }
+        self.cursor = (l, c);
+    }
+
+    pub fn remove(&mut self) {
+        if self.block_selection_lines().is_some() {
+            self.block_remove();
+            return;
+        }
+        if self.take_selection(false) {
+            return;
+        }
+        self.for_each_cursor(|content, _| content.remove_at_cursor());
+    }
+
+    fn remove_at_cursor(&mut self) {
+        let (l, c) = self.get_cursor();
+        let line = &self.content[l];
+        let empty_pair =
+            c > 0 && c < line.len() && self.language.auto_pairs.contains(&(line[c - 1], line[c]));
+        if self.auto_close && empty_pair {
+            self.content[l].drain(c - 1..=c);
+            self.cursor = (l, c - 1);
+            return;
+        }
+        let (l, mut c) = self.cursor;
+        if c > self.content[l].len() {
+            self.snap_cursor_end_of_line();
+            c = self.cursor.1;
+        }
+        let nothing_to_remove = c == 0 && l == 0;
+        let begining_of_line = c == 0;
+        if nothing_to_remove {
+        } else if begining_of_line {
+            let removed_line = self.content.remove(l);
+            self.shift_line_marks(l, 1, 0);
+            self.cursor = (l - 1, self.content[l - 1].len());
+            self.content[l - 1].extend(removed_line);
+        } else {
+            self.content[l].remove(c - 1);
+            self.cursor.1 -= 1;
+        }
+    }
+
+    /// Returns the lines holding a cursor or part of the selection, leaving
+    /// out the last line of a selection that ends at its very start.
+    fn cursor_lines(&self) -> Vec<usize> {
+        let mut lines: Vec<usize> = self.get_cursors().into_iter().map(|(l, _)| l).collect();
+        let mut ranges = self.selection_ranges();
+        if let (Some((_, SelectionMode::Linear)), Some((last, _, 0))) =
+            (self.selection, ranges.last().copied())
+        {
+            if ranges.len() > 1 {
+                lines.retain(|l| *l != last);
+                ranges.pop();
+            }
+        }
+        lines.extend(ranges.into_iter().map(|(l, _, _)| l));
+        lines.sort();
+        lines.dedup();
+        lines
+    }
+
+    fn shift_cursor_lines(&mut self, lines: &[usize], up: bool) {
+        let shift = |(l, c): (usize, usize)| {
+            if !lines.contains(&l) {
+                (l, c)
+            } else if up {
+                (l - 1, c)
+            } else {
+                (l + 1, c)
+            }
+        };
+        self.cursor = shift(self.cursor);
+        self.secondary_cursors = self.secondary_cursors.iter().map(|c| shift(*c)).collect();
+        self.selection = self.selection.map(|(anchor, mode)| (shift(anchor), mode));
+    }
+
+    fn shift_line_columns(&mut self, l: usize, chars: isize, columns: isize) {
+        let shift = |(cl, c): (usize, usize), delta: isize| {
+            if cl == l && (c > 0 || delta < 0) {
+                (cl, (c as isize + delta).max(0) as usize)
+            } else {
+                (cl, c)
+            }
+        };
+        let delta = match self.selection {
+            Some((_, SelectionMode::Block)) => columns,
+            _ => chars,
+        };
+        self.cursor = shift(self.cursor, delta);
+        self.secondary_cursors = self
+            .secondary_cursors
+            .iter()
+            .map(|c| shift(*c, chars))
+            .collect();
+        self.selection = self
+            .selection
+            .map(|(anchor, mode)| (shift(anchor, delta), mode));
+    }
+
+    fn indent_width(&self) -> usize {
+        match self.indent_style {
+            IndentStyle::Tabs => self.tab_width,
+            IndentStyle::Spaces(n) => n,
+        }
+    }
+
+    /// Inserts one level of indentation at every cursor, or in front of every
+    /// selected line when the selection spans several lines.
+    pub fn indent(&mut self) {
+        if self.selection_ranges().len() > 1 {
+            self.indent_lines();
+        } else if self.block_selection_lines().is_some() {
+            let unit = self.indent_unit(self.cursor.1);
+            self.block_append(&unit);
+        } else {
+            self.take_selection(true);
+            self.for_each_cursor(|content, _| {
+                let (_, c) = content.to_visual(content.get_cursor());
+                let unit = content.indent_unit(c);
+                content.append_at_cursor(&unit);
+            });
+        }
+    }
+
+    pub fn indent_lines(&mut self) {
+        let unit: Vec<char> = self.indent_unit(0).chars().collect();
+        let columns = visual_column(unit.iter().cloned(), unit.len(), self.tab_width);
+        for l in self.cursor_lines() {
+            if self.content[l].is_empty() {
+                continue;
+            }
+            self.content[l].splice(0..0, unit.iter().cloned());
+            self.shift_line_columns(l, unit.len() as isize, columns as isize);
+        }
+    }
+
+    /// Removes one level of indentation from every line holding a cursor or
+    /// part of the selection.
+    pub fn outdent_lines(&mut self) {
+        for l in self.cursor_lines() {
+            let line = &self.content[l];
+            let n = match line.first() {
+                Some('\t') => 1,
+                _ => line
+                    .iter()
+                    .take(self.indent_width())
+                    .take_while(|ch| **ch == ' ')
+                    .count(),
+            };
+            let columns = visual_column(line.iter().cloned(), n, self.tab_width);
+            self.content[l].drain(..n);
+            self.shift_line_columns(l, -(n as isize), -(columns as isize));
+        }
+    }
+
+    /// Groups the lines holding a cursor or part of the selection into
+    /// ranges of consecutive lines.
+    fn cursor_line_groups(&self) -> Vec<(usize, usize)> {
+        let mut groups: Vec<(usize, usize)> = Vec::new();
+        for l in self.cursor_lines() {
+            match groups.last_mut() {
+                Some((_, end)) if *end + 1 == l => *end = l,
+                _ => groups.push((l, l)),
+            }
+        }
+        groups
+    }
+
+    fn map_positions<F: Fn((usize, usize)) -> (usize, usize)>(&mut self, f: F) {
+        self.cursor = f(self.cursor);
+        self.secondary_cursors = self.secondary_cursors.iter().map(|c| f(*c)).collect();
+        self.selection = self.selection.map(|(anchor, mode)| (f(anchor), mode));
+    }
+
+    /// Copies the current or selected lines below themselves, moving the
+    /// cursors onto the copy.
+    pub fn duplicate_lines(&mut self) {
+        for (start, end) in self.cursor_line_groups().into_iter().rev() {
+            let copy = self.content[start..=end].to_vec();
+            let n = copy.len();
+            self.content.splice(end + 1..end + 1, copy);
+            self.shift_line_marks(end + 1, 0, n);
+            self.map_positions(|(l, c)| if l >= start { (l + n, c) } else { (l, c) });
+        }
+    }
+
+    pub fn delete_lines(&mut self) {
+        let groups = self.cursor_line_groups();
+        self.clear_selection();
+        for (start, end) in groups.into_iter().rev() {
+            self.content.drain(start..=end);
+            let n = end - start + 1;
+            self.shift_line_marks(start, n, 0);
+            self.map_positions(|(l, c)| {
+                if l > end {
+                    (l - n, c)
+                } else {
+                    (l.min(start), c)
+                }
+            });
+        }
+        if self.content.is_empty() {
+            self.content.push(Vec::new());
+        }
+        let last = self.content.len() - 1;
+        self.map_positions(|(l, c)| (l.min(last), c));
+        self.merge_cursors();
+    }
+
+    /// Joins the selected lines, or each current line with the next one,
+    /// separating their content with a single space.
+    pub fn join_lines(&mut self) {
+        let groups = self.cursor_line_groups();
+        let several_lines = self.selection_ranges().len() > 1;
+        self.clear_selection();
+        for (start, end) in groups.into_iter().rev() {
+            let end = if end > start {
+                end
+            } else {
+                (start + 1).min(self.content.len() - 1)
+            };
+            for _ in start..end {
+                let next = self.content.remove(start + 1);
+                self.shift_line_marks(start + 1, 1, 0);
+                let blank = next.iter().take_while(|ch| is_blank(**ch)).count();
+                let line = &mut self.content[start];
+                while line.last().is_some_and(|ch| is_blank(*ch)) {
+                    line.pop();
+                }
+                let join = line.len();
+                if join > 0 && blank < next.len() {
+                    line.push(' ');
+                }
+                let offset = line.len();
+                line.extend(&next[blank..]);
+                self.map_positions(|(l, c)| {
+                    if l == start + 1 {
+                        (start, offset + c.saturating_sub(blank))
+                    } else if l > start + 1 {
+                        (l - 1, c)
+                    } else if l == start && !several_lines {
+                        (start, join)
+                    } else {
+                        (l, c)
+                    }
+                });
+            }
+        }
+        self.merge_cursors();
+    }
+
+    /// Rearranges with `f` the lines holding a cursor or part of the
+    /// selection, the others staying in place. The lines left over once
+    /// rearranged are removed.
+    fn rearrange_lines<F: FnOnce(&mut Vec<Vec<char>>)>(&mut self, f: F) {
+        let selected = self.selection.is_some();
+        let at = self.cursor_lines();
+        let mut lines: Vec<Vec<char>> = at.iter().map(|l| self.content[*l].clone()).collect();
+        f(&mut lines);
+        for (l, line) in at.iter().zip(lines.iter()) {
+            self.content[*l] = line.clone();
+            self.shift_line_marks(*l, 1, 1);
+        }
+        for l in at[lines.len()..].iter().rev() {
+            self.content.remove(*l);
+            self.shift_line_marks(*l, 1, 0);
+        }
+        let (start, end) = (at[0], at[lines.len() - 1]);
+        self.secondary_cursors.clear();
+        self.clear_selection();
+        if selected {
+            self.selection = Some(((start, 0), SelectionMode::Linear));
+            self.cursor = (end, self.content[end].len());
+        } else {
+            let l = self.cursor.0.min(end);
+            self.cursor = (l, self.cursor.1.min(self.content[l].len()));
+        }
+    }
+
+    /// Replaces `removed` lines at `start` by `lines`, and puts the cursor
+    /// at the start of the first of them.
+    pub fn replace_lines(&mut self, start: usize, removed: usize, lines: &[String]) {
+        let start = start.min(self.content.len());
+        let removed = removed.min(self.content.len() - start);
+        self.content.splice(
+            start..start + removed,
+            lines.iter().map(|line| line.chars().collect()),
+        );
+        self.shift_line_marks(start, removed, lines.len());
+        if self.content.is_empty() {
+            self.content.push(Vec::new());
+        }
+        self.set_cursor((start, 0));
+    }
+
+    pub fn sort_lines(&mut self) {
+        self.rearrange_lines(|lines| lines.sort());
+    }
+
+    pub fn reverse_lines(&mut self) {
+        self.rearrange_lines(|lines| lines.reverse());
+    }
+
+    /// Removes the lines already present earlier in the range.
+    pub fn unique_lines(&mut self) {
+        self.rearrange_lines(|lines| {
+            let mut seen = HashSet::new();
+            lines.retain(|line| seen.insert(line.clone()));
+            if lines.is_empty() {
+                lines.push(Vec::new());
+            }
+        });
+    }
+
+    pub fn trim_trailing_whitespace(&mut self) {
+        for l in self.cursor_lines() {
+            let line = &mut self.content[l];
+            while line.last().is_some_and(|ch| is_blank(*ch)) {
+                line.pop();
+            }
+        }
+    }
+
+    pub fn move_line_up(&mut self) {
+        let lines = self.cursor_lines();
+        if lines[0] == 0 {
+            return;
+        }
+        for &l in lines.iter() {
+            let line = self.content.remove(l);
+            self.content.insert(l - 1, line);
+            self.shift_line_marks(l - 1, 2, 2);
+            self.swap_bookmarks(l - 1, l);
+        }
+        self.shift_cursor_lines(&lines, true);
+    }
+
+    pub fn move_line_down(&mut self) {
+        let lines = self.cursor_lines();
+        if lines[lines.len() - 1] >= self.content.len() - 1 {
+            return;
+        }
+        for &l in lines.iter().rev() {
+            let line = self.content.remove(l);
+            self.content.insert(l + 1, line);
+            self.shift_line_marks(l, 2, 2);
+            self.swap_bookmarks(l, l + 1);
+        }
+        self.shift_cursor_lines(&lines, false);
+    }
+
+    pub fn new_line(&mut self) {
+        self.take_selection(false);
+        self.for_each_cursor(|content, _| {
+            content.snap_cursor_end_of_line();
+            content.indented_break_line_at_cursor()
+        });
+    }
+
+    fn new_line_at_cursor(&mut self) {
+        let (l, _) = self.cursor;
+        self.content.insert(l + 1, Vec::new());
+        self.shift_line_marks(l + 1, 0, 1);
+        self.cursor = (l + 1, 0);
+    }
+
+    pub fn break_line(&mut self) {
+        self.take_selection(false);
+        self.for_each_cursor(|content, _| content.indented_break_line_at_cursor());
+    }
+
+    /// Breaks the line, starting the new one with the indentation of the
+    /// current one, one level deeper after a block opener. Breaking between
+    /// a pair of brackets moves the closing one to a third line.
+    fn indented_break_line_at_cursor(&mut self) {
+        let (l, c) = self.get_cursor();
+        let line = &self.content[l];
+        let indent: String = line[..c].iter().take_while(|ch| is_blank(**ch)).collect();
+        let before = line[..c].iter().rev().find(|ch| !is_blank(**ch)).copied();
+        let after = line[c..].iter().find(|ch| !is_blank(**ch)).copied();
+        let opens = before.is_some_and(|ch| self.language.indent_after.contains(&ch));
+        let closes = opens
+            && after.is_some()
+            && before.and_then(|ch| self.language.closing_bracket(ch)) == after;
+        let blank_after = line[c..].iter().take_while(|ch| is_blank(**ch)).count();
+        self.content[l].drain(c..c + blank_after);
+        self.cursor = (l, c);
+        self.break_line_at_cursor();
+        self.append_at_cursor(&indent);
+        if opens {
+            let (_, column) = self.to_visual(self.cursor);
+            let unit = self.indent_unit(column);
+            self.append_at_cursor(&unit);
+        }
+        if closes {
+            let cursor = self.cursor;
+            self.break_line_at_cursor();
+            self.append_at_cursor(&indent);
+            self.cursor = cursor;
+        }
+    }
+
+    fn break_line_at_cursor(&mut self) {
+        let (l, c) = self.cursor;
+        if c >= self.content[l].len() {
+            self.new_line_at_cursor();
+            return;
+        }
+        let right = self.content[l].split_off(c);
+        self.content.insert(l + 1, right);
+        self.shift_line_marks(if c == 0 { l } else { l + 1 }, 0, 1);
+        self.cursor = (l + 1, 0);
+    }
+
+    /// Moves the positions on `line` after characters replaced at `at`.
+    fn map_line_positions(&mut self, line: usize, at: usize, removed: usize, inserted: usize) {
+        self.map_positions(|(l, c)| {
+            if l != line || c < at || c == 0 {
+                (l, c)
+            } else if c < at + removed {
+                (l, at + inserted)
+            } else {
+                (l, c - removed + inserted)
+            }
+        });
+    }
+
+    fn indentation_length(&self, l: usize) -> usize {
+        self.content[l]
+            .iter()
+            .take_while(|ch| is_blank(**ch))
+            .count()
+    }
+
+    /// Comments the current or selected lines out using the line comment of
+    /// the language, or uncomments them when they all already are. Comment
+    /// tokens are aligned on the least indented line.
+    pub fn toggle_line_comment(&mut self) {
+        let Some(token) = self.language.line_comment else {
+            if self.language.block_comment.is_some() {
+                self.toggle_block_comment();
+            }
+            return;
+        };
+        let token: Vec<char> = token.chars().collect();
+        let lines: Vec<usize> = self
+            .cursor_lines()
+            .into_iter()
+            .filter(|l| self.indentation_length(*l) < self.content[*l].len())
+            .collect();
+        let commented = lines
+            .iter()
+            .all(|l| self.content[*l][self.indentation_length(*l)..].starts_with(&token));
+        if lines.is_empty() {
+        } else if commented {
+            for l in lines {
+                let at = self.indentation_length(l);
+                let mut n = token.len();
+                if self.content[l].get(at + n) == Some(&' ') {
+                    n += 1;
+                }
+                self.content[l].drain(at..at + n);
+                self.map_line_positions(l, at, n, 0);
+            }
+        } else {
+            let tab_width = self.tab_width;
+            let column = lines
+                .iter()
+                .map(|l| self.to_visual((*l, self.indentation_length(*l))).1)
+                .min()
+                .unwrap();
+            for l in lines {
+                let at = char_column(self.content[l].iter().cloned(), column, tab_width);
+                let inserted = [&token[..], &[' ']].concat();
+                let n = inserted.len();
+                self.content[l].splice(at..at, inserted);
+                self.map_line_positions(l, at, 0, n);
+            }
+        }
+    }
+
+    /// Wraps the selection, or the content of the current line, in a block
+    /// comment of the language, or unwraps it when it already is one.
+    pub fn toggle_block_comment(&mut self) {
+        let Some((start, end)) = self.language.block_comment else {
+            if self.language.line_comment.is_some() {
+                self.toggle_line_comment();
+            }
+            return;
+        };
+        let (start, end): (Vec<char>, Vec<char>) = (start.chars().collect(), end.chars().collect());
+        let range = match self.selection {
+            Some((anchor, SelectionMode::Linear)) if self.get_selected_text().is_some() => {
+                self.linear_bounds(anchor)
+            }
+            _ => {
+                let (l, _) = self.get_cursor();
+                let line = &self.content[l];
+                let trailing = line.iter().rev().take_while(|ch| is_blank(**ch)).count();
+                let indentation = self.indentation_length(l);
+                if indentation == line.len() {
+                    return;
+                }
+                ((l, indentation), (l, line.len() - trailing))
+            }
+        };
+        let ((l1, c1), (l2, c2)) = range;
+        let wrapped = self.content[l1][c1..].starts_with(&start)
+            && self.content[l2][..c2].ends_with(&end)
+            && (l1 < l2 || c1 + start.len() + end.len() <= c2);
+        self.secondary_cursors.clear();
+        let (removed, inserted) = if wrapped {
+            let mut n = end.len();
+            if c2 > n && self.content[l2][c2 - n - 1] == ' ' && (l1 < l2 || c2 - n - 1 > c1) {
+                n += 1;
+            }
+            self.content[l2].drain(c2 - n..c2);
+            self.map_line_positions(l2, c2 - n, n, 0);
+            let mut m = start.len();
+            if self.content[l1].get(c1 + m) == Some(&' ') {
+                m += 1;
+            }
+            self.content[l1].drain(c1..c1 + m);
+            self.map_line_positions(l1, c1, m, 0);
+            (n + if l1 == l2 { m } else { 0 }, 0)
+        } else {
+            let closing = [&[' '], &end[..]].concat();
+            let n = closing.len();
+            self.content[l2].splice(c2..c2, closing);
+            self.map_line_positions(l2, c2, 0, n);
+            let opening = [&start[..], &[' ']].concat();
+            let m = opening.len();
+            self.content[l1].splice(c1..c1, opening);
+            self.map_line_positions(l1, c1, 0, m);
+            (0, n + if l1 == l2 { m } else { 0 })
+        };
+        if let Some((_, SelectionMode::Linear)) = self.selection {
+            self.selection = Some(((l1, c1), SelectionMode::Linear));
+            self.cursor = (l2, c2 + inserted - removed);
+        }
+    }
+
+    /// Returns the foldable regions as `(first, last)` lines, the first line
+    /// staying visible when folded. Regions follow the indentation, and the
+    /// brackets spanning several lines unless the text is plain.
+    pub fn fold_regions(&self) -> Vec<(usize, usize)> {
+        let revision = self.content.revision;
+        (self.fold_regions)
+            .get(revision, || self.find_fold_regions())
+            .to_vec()
+    }
+
+    fn find_fold_regions(&self) -> Vec<(usize, usize)> {
+        let indents: Vec<Option<usize>> = (0..self.content.len())
+            .map(|l| {
+                if self.content[l].iter().all(|ch| is_blank(*ch)) {
+                    None
+                } else {
+                    Some(self.to_visual((l, self.indentation_length(l))).1)
+                }
+            })
+            .collect();
+        let mut ends: Vec<Option<usize>> = vec![None; self.content.len()];
+        for (l, indent) in indents.iter().enumerate() {
+            let Some(indent) = indent else {
+                continue;
+            };
+            let mut end = l;
+            for (m, other) in indents.iter().enumerate().skip(l + 1) {
+                match other {
+                    Some(other) if other <= indent => break,
+                    Some(_) => end = m,
+                    None => {}
+                }
+            }
+            if end > l {
+                ends[l] = Some(end);
+            }
+        }
+        if self.language.name != PLAIN_TEXT.name {
+            let mask = self.code_mask();
+            let mut open = Vec::new();
+            for (l, line) in self.content.iter().enumerate() {
+                for (c, ch) in line.iter().enumerate() {
+                    if !mask[l][c] {
+                        continue;
+                    }
+                    if self.language.closing_bracket(*ch).is_some() {
+                        open.push(l);
+                    } else if self.language.is_closing_bracket(*ch) {
+                        match open.pop() {
+                            Some(first) if l > first + 1 => {
+                                ends[first] = ends[first].max(Some(l - 1));
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+        ends.iter()
+            .enumerate()
+            .filter_map(|(l, end)| end.map(|end| (l, end)))
+            .collect()
+    }
+
+    /// Returns the foldable regions as `(first, last, folded)`.
+    pub fn folds(&self) -> Vec<(usize, usize, bool)> {
+        self.fold_regions()
+            .into_iter()
+            .map(|(first, last)| (first, last, self.folded.contains(&first)))
+            .collect()
+    }
+
+    fn folded_regions(&self) -> Vec<(usize, usize)> {
+        if self.folded.is_empty() {
+            return Vec::new();
+        }
+        self.fold_regions()
+            .into_iter()
+            .filter(|(first, _)| self.folded.contains(first))
+            .collect()
+    }
+
+    fn hidden_lines(&self) -> Vec<bool> {
+        let mut hidden = vec![false; self.content.len()];
+        for (first, last) in self.folded_regions() {
+            hidden[first + 1..=last].fill(true);
+        }
+        hidden
+    }
+
+    /// Returns the line `n` visible lines below `l`, or above it when `n` is
+    /// negative, stopping at the first and last visible lines.
+    fn visible_line(&self, l: usize, n: isize) -> usize {
+        let hidden = self.hidden_lines();
+        let mut l = l.min(self.content.len() - 1);
+        let mut left = n.unsigned_abs();
+        while left > 0 {
+            let next = if n < 0 {
+                l.checked_sub(1)
+            } else {
+                Some(l + 1).filter(|l| *l < self.content.len())
+            };
+            let Some(next) = next else {
+                break;
+            };
+            l = next;
+            if !hidden[l] {
+                left -= 1;
+            }
+        }
+        while hidden[l] {
+            l -= 1;
+        }
+        l
+    }
+
+    /// Keeps the folds and bookmarks on their lines when `removed` lines at
+    /// `at` are replaced by `inserted` ones. Folds whose first line went are
+    /// dropped, bookmarks only when no line took the place of theirs. The
+    /// shift is kept for the cursors of the other views.
+    fn shift_line_marks(&mut self, at: usize, removed: usize, inserted: usize) {
+        self.line_shifts.push((at, removed, inserted));
+        self.bookmarks = self
+            .bookmarks
+            .iter()
+            .filter_map(|l| match *l {
+                l if l < at || l < at + removed.min(inserted) => Some(l),
+                l if l < at + removed => None,
+                l => Some(l + inserted - removed),
+            })
+            .collect();
+        self.folded = self
+            .folded
+            .iter()
+            .filter_map(|first| match *first {
+                first if first < at => Some(first),
+                first if first < at + removed => None,
+                first => Some(first + inserted - removed),
+            })
+            .collect();
+    }
+
+    /// Unfolds the regions hiding a cursor, and forgets the folds that are
+    /// no longer foldable regions.
+    fn reveal_cursors(&mut self) {
+        if self.folded.is_empty() {
+            return;
+        }
+        let regions = self.fold_regions();
+        let cursors = self.get_cursors();
+        self.folded.retain(|first| {
+            regions
+                .iter()
+                .any(|(f, last)| f == first && !cursors.iter().any(|(l, _)| first < l && l <= last))
+        });
+    }
+
+    /// Moves the cursors out of the folded regions, to the end of their
+    /// first line.
+    fn hide_cursors(&mut self) {
+        let hidden = self.hidden_lines();
+        let mut targets = Vec::new();
+        let mut visible = 0;
+        for (l, is_hidden) in hidden.iter().enumerate() {
+            if !is_hidden {
+                visible = l;
+            }
+            targets.push((visible, self.content[visible].len()));
+        }
+        self.map_positions(|(l, c)| match hidden.get(l) {
+            Some(true) => targets[l],
+            _ => (l, c),
+        });
+        self.merge_cursors();
+    }
+
+    /// Folds the innermost region around the cursor that is not folded yet.
+    pub fn fold(&mut self) {
+        let (l, _) = self.get_cursor();
+        let region = self
+            .fold_regions()
+            .into_iter()
+            .filter(|(first, last)| *first <= l && l <= *last && !self.folded.contains(first))
+            .max_by_key(|(first, _)| *first);
+        if let Some((first, _)) = region {
+            self.folded.push(first);
+            self.hide_cursors();
+        }
+    }
+
+    /// Unfolds the region starting on the cursor line.
+    pub fn unfold(&mut self) {
+        let (l, _) = self.get_cursor();
+        self.folded.retain(|first| *first != l);
+    }
+
+    /// Folds or unfolds the region starting on line `l`.
+    pub fn toggle_fold(&mut self, l: usize) {
+        if self.folded.contains(&l) {
+            self.folded.retain(|first| *first != l);
+        } else if self.fold_regions().iter().any(|(first, _)| *first == l) {
+            self.folded.push(l);
+            self.hide_cursors();
+        }
+    }
+
+    /// Makes the bookmarks follow two lines that were swapped.
+    fn swap_bookmarks(&mut self, a: usize, b: usize) {
+        for l in self.bookmarks.iter_mut() {
+            if *l == a {
+                *l = b;
+            } else if *l == b {
+                *l = a;
+            }
+        }
+        self.bookmarks.sort();
+    }
+
+    pub fn bookmarks(&self) -> &[usize] {
+        &self.bookmarks
+    }
+
+    /// Adds a bookmark on the cursor line, or removes the one there.
+    pub fn toggle_bookmark(&mut self) {
+        let (l, _) = self.get_cursor();
+        if self.bookmarks.contains(&l) {
+            self.bookmarks.retain(|b| *b != l);
+        } else {
+            self.bookmarks.push(l);
+            self.bookmarks.sort();
+        }
+    }
+
+    /// Moves the cursor to the next bookmarked line, going back to the first
+    /// one after the last.
+    pub fn next_bookmark(&mut self) {
+        let (l, _) = self.get_cursor();
+        let next = self.bookmarks.iter().find(|b| **b > l);
+        if let Some(b) = next.or(self.bookmarks.first()).copied() {
+            self.set_cursor((b, 0));
+        }
+    }
+
+    /// Moves the cursor to the previous bookmarked line, going to the last
+    /// one before the first.
+    pub fn previous_bookmark(&mut self) {
+        let (l, _) = self.get_cursor();
+        let previous = self.bookmarks.iter().rev().find(|b| **b < l);
+        if let Some(b) = previous.or(self.bookmarks.last()).copied() {
+            self.set_cursor((b, 0));
+        }
+    }
+
+    /// Replaces the selected text, or else the word under each cursor, by
+    /// its image through `f`, leaving the text untouched where `f` fails.
+    fn transform_text(&mut self, part_of_word: fn(char) -> bool, f: fn(&str) -> Option<String>) {
+        match self.selection {
+            Some((anchor, SelectionMode::Linear)) if self.get_selected_text().is_some() => {
+                let (start, end) = self.linear_bounds(anchor);
+                if let Some(text) = f(&self.get_selected_text().unwrap()) {
+                    self.delete_range(start, end);
+                    self.cursor = start;
+                    self.insert_at_cursor(&text.split('\n').collect::<Vec<_>>());
+                    self.selection = Some((start, SelectionMode::Linear));
+                }
+            }
+            Some((anchor, SelectionMode::Block)) => {
+                let ((_, _), (left, right)) = self.block_bounds(anchor);
+                let mut new_right = left;
+                for (l, start, end) in self.selection_ranges() {
+                    let line = &self.content[l];
+                    let (start, end) = (start.min(line.len()), end.min(line.len()));
+                    let text: String = line[start..end].iter().collect();
+                    let Some(text) = f(&text).filter(|t| !t.contains('\n')) else {
+                        new_right = new_right.max(right);
+                        continue;
+                    };
+                    let new_end = start + text.chars().count();
+                    self.content[l].splice(start..end, text.chars());
+                    for cursor in self.secondary_cursors.iter_mut() {
+                        if cursor.0 == l && cursor.1 >= end {
+                            cursor.1 = cursor.1 - end + new_end;
+                        }
+                    }
+                    new_right = new_right.max(self.to_visual((l, new_end)).1);
+                }
+                // The block is widened or narrowed to the transformed text.
+                if self.cursor.1 == right && right > left {
+                    self.cursor.1 = new_right;
+                } else if anchor.1 == right {
+                    self.selection = Some(((anchor.0, new_right), SelectionMode::Block));
+                }
+            }
+            _ => self.for_each_cursor(|content, _| {
+                let (l, c) = content.get_cursor();
+                let Some((start, end)) = content.bounds_around((l, c), part_of_word) else {
+                    return;
+                };
+                let text: String = content.content[l][start..end].iter().collect();
+                if let Some(text) = f(&text).filter(|t| !t.contains('\n')) {
+                    let text: Vec<char> = text.chars().collect();
+                    let n = text.len();
+                    content.content[l].splice(start..end, text);
+                    content.cursor = (l, c.min(start + n));
+                }
+            }),
+        }
+    }
+
+    pub fn upper_case(&mut self) {
+        self.transform_text(is_identifier_char, transform::upper_case);
+    }
+
+    pub fn lower_case(&mut self) {
+        self.transform_text(is_identifier_char, transform::lower_case);
+    }
+
+    pub fn title_case(&mut self) {
+        self.transform_text(is_identifier_char, transform::title_case);
+    }
+
+    pub fn snake_case(&mut self) {
+        self.transform_text(is_identifier_char, transform::snake_case);
+    }
+
+    pub fn camel_case(&mut self) {
+        self.transform_text(is_identifier_char, transform::camel_case);
+    }
+
+    pub fn kebab_case(&mut self) {
+        self.transform_text(is_identifier_char, transform::kebab_case);
+    }
+
+    pub fn base64_encode(&mut self) {
+        self.transform_text(is_not_whitespace, transform::base64_encode);
+    }
+
+    pub fn base64_decode(&mut self) {
+        self.transform_text(is_not_whitespace, transform::base64_decode);
+    }
+
+    pub fn url_encode(&mut self) {
+        self.transform_text(is_not_whitespace, transform::url_encode);
+    }
+
+    pub fn url_decode(&mut self) {
+        self.transform_text(is_not_whitespace, transform::url_decode);
+    }
+
+    /// Swaps the characters around each cursor, or the last two of the line
+    /// at its end, and moves forward.
+    pub fn transpose_characters(&mut self) {
+        self.clear_selection();
+        self.for_each_cursor(|content, _| {
+            let (l, c) = content.get_cursor();
+            let len = content.content[l].len();
+            if len < 2 || c == 0 {
+                return;
+            }
+            let c = c.min(len - 1);
+            content.content[l].swap(c - 1, c);
+            content.cursor = (l, c + 1);
+        });
+    }
+
+    pub fn increment_number(&mut self) {
+        self.add_to_number(1);
+    }
+
+    pub fn decrement_number(&mut self) {
+        self.add_to_number(-1);
+    }
+
+    /// Adds `n` to the number under each cursor, or else to the next one on
+    /// its line, and puts the cursor on its last digit.
+    fn add_to_number(&mut self, n: i64) {
+        self.for_each_cursor(|content, _| {
+            let (l, c) = content.get_cursor();
+            let line = &content.content[l];
+            let mut start = c;
+            while start > 0 && line[start - 1].is_ascii_digit() {
+                start -= 1;
+            }
+            if start == c && !line.get(c).is_some_and(|ch| ch.is_ascii_digit()) {
+                match line[c..].iter().position(|ch| ch.is_ascii_digit()) {
+                    Some(i) => start = c + i,
+                    None => return,
+                }
+            }
+            let mut end = start;
+            while end < line.len() && line[end].is_ascii_digit() {
+                end += 1;
+            }
+            // A minus after a word is an operator or a separator, not a sign.
+            if start > 0 && line[start - 1] == '-' && !(start > 1 && is_word_char(line[start - 2]))
+            {
+                start -= 1;
+            }
+            let text: String = line[start..end].iter().collect();
+            let Ok(number) = text.parse::<i64>() else {
+                return;
+            };
+            let text: Vec<char> = number.saturating_add(n).to_string().chars().collect();
+            let last = start + text.len() - 1;
+            content.content[l].splice(start..end, text);
+            content.cursor = (l, last);
+        });
+    }
+
+    /// Marks which characters are code, as opposed to parts of the strings
+    /// and comments of the current language. Each line gets an extra entry
+    /// telling whether its end is in code.
+    fn code_mask(&self) -> Rc<Vec<Vec<bool>>> {
+        (self.code_mask).get(self.content.revision, || self.mark_code())
+    }
+
+    fn mark_code(&self) -> Vec<Vec<bool>> {
+        let language = self.language;
+        let mut in_block_comment = false;
+        let mut masks = Vec::new();
+        for line in self.content.iter() {
+            let mut mask = vec![true; line.len()];
+            let mut string = None;
+            let mut in_line_comment = false;
+            let mut c = 0;
+            while c < line.len() {
+                if in_block_comment {
+                    let (_, end) = language.block_comment.unwrap();
+                    if starts_with(line, c, end) {
+                        let n = end.chars().count();
+                        mask[c..c + n].fill(false);
+                        in_block_comment = false;
+                        c += n;
+                    } else {
+                        mask[c] = false;
+                        c += 1;
+                    }
+                    continue;
+                }
+                if let Some(delimiter) = string {
+                    mask[c] = false;
+                    if line[c] == '\\' && c + 1 < line.len() {
+                        mask[c + 1] = false;
+                        c += 1;
+                    } else if line[c] == delimiter {
+                        string = None;
+                    }
+                    c += 1;
+                    continue;
+                }
+                if language
+                    .line_comment
+                    .is_some_and(|token| starts_with(line, c, token))
+                {
+                    mask[c..].fill(false);
+                    in_line_comment = true;
+                    break;
+                }
+                if let Some((start, _)) = language.block_comment {
+                    if starts_with(line, c, start) {
+                        let n = start.chars().count();
+                        mask[c..c + n].fill(false);
+                        in_block_comment = true;
+                        c += n;
+                        continue;
+                    }
+                }
+                if language.char_literals && line[c] == '\'' {
+                    if let Some(n) = char_literal_len(line, c) {
+                        mask[c..c + n].fill(false);
+                        c += n;
+                        continue;
+                    }
+                }
+                if language.string_delimiters.contains(&line[c]) {
+                    mask[c] = false;
+                    string = Some(line[c]);
+                }
+                c += 1;
+            }
+            mask.push(!in_block_comment && !in_line_comment && string.is_none());
+            masks.push(mask);
+        }
+        masks
+    }
+
+    fn in_string_or_comment(&self, cursor: (usize, usize)) -> bool {
+        let (l, c) = cursor;
+        let mask = self.code_mask();
+        let mask = &mask[l];
+        if c >= self.content[l].len() {
+            !mask[mask.len() - 1]
+        } else {
+            c > 0 && !mask[c - 1] && !mask[c]
+        }
+    }
+
+    /// Looks for the bracket closing the pair `open`/`close` from `from`,
+    /// forward for a closing bracket or backward for an opening one.
+    fn search_bracket(
+        &self,
+        mask: &[Vec<bool>],
+        from: (usize, usize),
+        open: char,
+        close: char,
+        forward: bool,
+    ) -> Option<(usize, usize)> {
+        let (l, c) = from;
+        let (target, other) = if forward {
+            (close, open)
+        } else {
+            (open, close)
+        };
+        let mut depth = 0;
+        let lines: Box<dyn Iterator<Item = usize>> = if forward {
+            Box::new(l..self.content.len())
+        } else {
+            Box::new((0..=l).rev())
+        };
+        for line in lines {
+            let columns: Box<dyn Iterator<Item = usize>> = match (forward, line == l) {
+                (true, true) => Box::new(c + 1..self.content[line].len()),
+                (true, false) => Box::new(0..self.content[line].len()),
+                (false, true) => Box::new((0..c.min(self.content[line].len())).rev()),
+                (false, false) => Box::new((0..self.content[line].len()).rev()),
+            };
+            for column in columns {
+                if !mask[line][column] {
+                    continue;
+                }
+                let ch = self.content[line][column];
+                if ch == other {
+                    depth += 1;
+                } else if ch == target {
+                    if depth == 0 {
+                        return Some((line, column));
+                    }
+                    depth -= 1;
+                }
+            }
+        }
+        None
+    }
+
+    /// Finds the bracket under the cursor, or else right before it, and the
+    /// one matching it.
+    pub fn matching_brackets(&self) -> Option<((usize, usize), (usize, usize))> {
+        let mask = self.code_mask();
+        let (l, c) = self.get_cursor();
+        for c in [Some(c), c.checked_sub(1)].into_iter().flatten() {
+            if c >= self.content[l].len() || !mask[l][c] {
+                continue;
+            }
+            let ch = self.content[l][c];
+            let found = if let Some(close) = self.language.closing_bracket(ch) {
+                self.search_bracket(&mask, (l, c), ch, close, true)
+            } else if let Some(open) = self.language.opening_bracket(ch) {
+                self.search_bracket(&mask, (l, c), open, ch, false)
+            } else {
+                continue;
+            };
+            if let Some(position) = found {
+                return Some(((l, c), position));
+            }
+        }
+        None
+    }
+
+    /// Returns the positions of the brackets outside of strings and comments
+    /// that are not closed, or close nothing, sorted. A closing bracket
+    /// matching an outer one closes it, leaving the inner ones unclosed.
+    /// Always empty for plain text.
+    pub fn unmatched_brackets(&self) -> Vec<(usize, usize)> {
+        let revision = self.content.revision;
+        (self.unmatched_brackets)
+            .get(revision, || self.find_unmatched_brackets())
+            .to_vec()
+    }
+
+    fn find_unmatched_brackets(&self) -> Vec<(usize, usize)> {
+        if self.language.name == PLAIN_TEXT.name {
+            return Vec::new();
+        }
+        let mask = self.code_mask();
+        let mut open = Vec::new();
+        let mut unmatched = Vec::new();
+        for (l, line) in self.content.iter().enumerate() {
+            for (c, ch) in line.iter().enumerate() {
+                if !mask[l][c] {
+                    continue;
+                }
+                if let Some(close) = self.language.closing_bracket(*ch) {
+                    open.push(((l, c), close));
+                } else if self.language.is_closing_bracket(*ch) {
+                    match open.iter().rposition(|(_, close)| close == ch) {
+                        Some(i) => unmatched.extend(open.drain(i..).skip(1).map(|(p, _)| p)),
+                        None => unmatched.push((l, c)),
+                    }
+                }
+            }
+        }
+        unmatched.extend(open.into_iter().map(|(position, _)| position));
+        unmatched.sort();
+        unmatched
+    }
+
+    pub fn jump_to_matching_bracket(&mut self) {
+        if let Some((_, position)) = self.matching_brackets() {
+            self.set_cursor(position);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn content(text: &str, cursor: (usize, usize)) -> TextContent {
+        let mut content = TextContent::new();
+        content.paste(text.to_string());
+        content.set_cursor(cursor);
+        content
+    }
+
+    #[test]
+    fn edit_at_every_cursor() {
+        let mut c = content("aaa\nbb", (0, 0));
+        c.add_cursor((0, 2));
+        c.add_cursor((1, 1));
+        c.append("xy".to_string());
+        assert_eq!(c.get_text(), vec!["xyaaxya", "bxyb"]);
+        assert_eq!(c.get_cursors(), vec![(1, 3), (0, 6), (0, 2)]);
+        c.paste("1\n2".to_string());
+        assert_eq!(c.get_text(), vec!["xy1", "2aaxy1", "2a", "bxy1", "2b"]);
+        assert_eq!(c.get_cursors(), vec![(4, 1), (2, 1), (1, 1)]);
+        for _ in 0..3 {
+            c.remove();
+        }
+        assert_eq!(c.get_text(), vec!["xyaaxya", "bxyb"]);
+        c.remove();
+        assert_eq!(c.get_text(), vec!["xaaxa", "bxb"]);
+        c.move_cursors_left(1);
+        assert_eq!(c.get_cursors(), vec![(1, 1), (0, 3), (0, 0)]);
+    }
+
+    #[test]
+    fn cursor_at_next_occurrence() {
+        let mut c = content("foo bar\nfoobar foo\n(foo)", (0, 1));
+        c.add_cursor_at_next_occurrence();
+        assert_eq!(c.get_cursors(), vec![(1, 8), (0, 1)]);
+        c.add_cursor_at_next_occurrence();
+        assert_eq!(c.get_cursors(), vec![(2, 2), (0, 1), (1, 8)]);
+        c.add_cursor_at_next_occurrence();
+        assert_eq!(c.get_cursors().len(), 3);
+        c.append("!".to_string());
+        assert_eq!(c.get_text(), vec!["f!oo bar", "foobar f!oo", "(f!oo)"]);
+    }
+
+    #[test]
+    fn block_selection() {
+        let mut c = content("abcd\nab\nabcd", (0, 1));
+        c.select_to((2, 3), SelectionMode::Block);
+        assert_eq!(c.get_selected_text(), Some("bc\nb\nbc".to_string()));
+        assert_eq!(c.cut(), Some("bc\nb\nbc".to_string()));
+        assert_eq!(c.get_text(), vec!["ad", "a", "ad"]);
+        assert_eq!(c.get_cursors(), vec![(2, 1), (0, 1), (1, 1)]);
+        c.paste("XY".to_string());
+        assert_eq!(c.get_text(), vec!["aXYd", "aXY", "aXYd"]);
+        c.clear_selection();
+
+        let mut c = content("a\tb\nabcdefg", (0, 0));
+        c.select_to((1, 5), SelectionMode::Block);
+        assert_eq!(c.get_selected_text(), Some("a\tb\nabcde".to_string()));
+        c.select_to((1, 2), SelectionMode::Block);
+        assert_eq!(c.get_selected_text(), Some("a\nab".to_string()));
+    }
+
+    #[test]
+    fn linear_selection() {
+        let mut c = content("one\ntwo\nthree", (0, 1));
+        c.select_to((2, 2), SelectionMode::Linear);
+        assert_eq!(c.get_selected_text(), Some("ne\ntwo\nth".to_string()));
+        assert_eq!(c.selection_ranges(), vec![(0, 1, 3), (1, 0, 3), (2, 0, 2)]);
+        assert_eq!(c.cut(), Some("ne\ntwo\nth".to_string()));
+        assert_eq!(c.get_text(), vec!["oree"]);
+        assert_eq!(c.get_selected_text(), None);
+        c.paste("ne\ntwo\nth".to_string());
+        assert_eq!(c.get_text(), vec!["one", "two", "three"]);
+        assert_eq!(c.get_cursor(), (2, 2));
+        // One line of the pasted text for each cursor.
+        let mut c = content("a\nb", (0, 1));
+        c.add_cursor((1, 1));
+        c.paste("1\n2".to_string());
+        assert_eq!(c.get_text(), vec!["a1", "b2"]);
+    }
+
+    #[test]
+    fn tab_columns() {
+        let line: Vec<char> = "\tab\tc".chars().collect();
+        let columns: Vec<usize> = (0..=6).map(|c| visual_column(line.clone(), c, 4)).collect();
+        assert_eq!(columns, vec![0, 4, 5, 6, 8, 9, 10]);
+        let chars: Vec<usize> = (0..=10).map(|c| char_column(line.clone(), c, 4)).collect();
+        assert_eq!(chars, vec![0, 0, 0, 0, 1, 2, 3, 3, 4, 5, 6]);
+        assert_eq!(visual_column(line.clone(), 2, 8), 9);
+        assert_eq!(expand_tabs("a\tb", 4), "a   b");
+    }
+
+    #[test]
+    fn detect_indent_style() {
+        let detect = |text: &str| content(text, (0, 0)).detect_indent_style();
+        assert_eq!(detect("a\nb"), None);
+        assert_eq!(detect("a\n\tb\n\t\tc\n  d"), Some(IndentStyle::Tabs));
+        assert_eq!(
+            detect("a\n    b\n        c\n  \nd"),
+            Some(IndentStyle::Spaces(4))
+        );
+        assert_eq!(
+            detect("a\n  b\n    c\n      d"),
+            Some(IndentStyle::Spaces(2))
+        );
+        assert_eq!(detect("a\n   b\n      c"), Some(IndentStyle::Spaces(3)));
+        let mut c = content("\t  a\n  b", (0, 0));
+        assert_eq!(c.mixed_indentation_lines(), vec![0]);
+        c.set_indent_style(IndentStyle::Tabs);
+        c.set_cursor((1, 0));
+        c.indent();
+        assert_eq!(c.get_text()[1], "\t  b");
+    }
+
+    #[test]
+    fn auto_indent() {
+        let mut c = content("fn f() {}", (0, 8));
+        c.set_language(Language::from_path(Some("main.rs")));
+        c.break_line();
+        assert_eq!(c.get_text(), vec!["fn f() {", "    ", "}"]);
+        assert_eq!(c.get_cursor(), (1, 4));
+        c.append("a".to_string());
+        c.break_line();
+        assert_eq!(c.get_text(), vec!["fn f() {", "    a", "    ", "}"]);
+
+        let mut c = content("if x:", (0, 5));
+        c.set_language(Language::from_path(Some("main.py")));
+        c.set_indent_style(IndentStyle::Tabs);
+        c.new_line();
+        assert_eq!(c.get_text(), vec!["if x:", "\t"]);
+    }
+
+    #[test]
+    fn dedent_closing_bracket() {
+        let mut c = content("    if x {\n        ", (1, 8));
+        c.set_language(Language::from_path(Some("main.rs")));
+        c.append("}".to_string());
+        assert_eq!(c.get_text()[1], "    }");
+        assert_eq!(c.get_cursor(), (1, 5));
+        // Only on lines holding nothing else.
+        let mut c = content("    a  ", (0, 7));
+        c.set_language(Language::from_path(Some("main.rs")));
+        c.append(")".to_string());
+        assert_eq!(c.get_text()[0], "    a  )");
+        let mut c = content("\t\t", (0, 2));
+        c.set_language(Language::from_path(Some("main.rs")));
+        c.append("]".to_string());
+        assert_eq!(c.get_text()[0], "\t]");
+    }
+
+    #[test]
+    fn matching_brackets() {
+        let mut c = content("fn f(a: [u8; 2]) {\n    g(\")\", b); // (\n}", (0, 4));
+        c.set_language(Language::from_path(Some("main.rs")));
+        assert_eq!(c.matching_brackets(), Some(((0, 4), (0, 15))));
+        c.set_cursor((0, 16));
+        assert_eq!(c.matching_brackets(), Some(((0, 15), (0, 4))));
+        // Brackets in strings and comments are left out.
+        c.set_cursor((1, 5));
+        assert_eq!(c.matching_brackets(), Some(((1, 5), (1, 12))));
+        c.set_cursor((1, 22));
+        assert_eq!(c.matching_brackets(), None);
+        c.set_cursor((0, 17));
+        c.jump_to_matching_bracket();
+        assert_eq!(c.get_cursor(), (2, 0));
+        c.set_cursor((0, 1));
+        assert_eq!(c.matching_brackets(), None);
+
+        // Character literals hold no bracket, unlike lifetimes.
+        let text = "fn f<'a>(x: &'a str) -> bool {\n    x.ends_with(['}', '\\'', '\\u{7d}'])\n}";
+        let mut c = content(text, (0, 8));
+        c.set_language(Language::from_path(Some("main.rs")));
+        assert_eq!(c.matching_brackets(), Some(((0, 8), (0, 19))));
+        c.set_cursor((0, 29));
+        assert_eq!(c.matching_brackets(), Some(((0, 29), (2, 0))));
+        c.set_cursor((1, 15));
+        assert_eq!(c.matching_brackets(), Some(((1, 15), (1, 37))));
+
+        let mut c = content("/* ( */ (\n)", (0, 8));
+        c.set_language(Language::from_path(Some("main.c")));
+        assert_eq!(c.matching_brackets(), Some(((0, 8), (1, 0))));
+    }
+
+    #[test]
+    fn auto_close_pairs() {
+        let mut c = content("", (0, 0));
+        c.set_language(Language::from_path(Some("main.rs")));
+        c.append("(".to_string());
+        assert_eq!(c.get_text(), vec!["()"]);
+        assert_eq!(c.get_cursor(), (0, 1));
+        c.append("a".to_string());
+        c.append(")".to_string());
+        assert_eq!(c.get_text(), vec!["(a)"]);
+        assert_eq!(c.get_cursor(), (0, 3));
+        // Not before a word, nor inside strings.
+        c.set_cursor((0, 1));
+        c.append("[".to_string());
+        assert_eq!(c.get_text(), vec!["([a)"]);
+        c.set_cursor((0, 4));
+        c.append("\"".to_string());
+        c.append("{".to_string());
+        assert_eq!(c.get_text(), vec!["([a)\"{\""]);
+        c.append("\"".to_string());
+        assert_eq!(c.get_text(), vec!["([a)\"{\""]);
+        assert_eq!(c.get_cursor(), (0, 7));
+        // Backspace between an empty pair removes both.
+        let mut c = content("x", (0, 1));
+        c.set_language(Language::from_path(Some("main.rs")));
+        c.append(" ".to_string());
+        c.append("{".to_string());
+        c.remove();
+        assert_eq!(c.get_text(), vec!["x "]);
+        c.set_auto_close(false);
+        c.append("{".to_string());
+        assert_eq!(c.get_text(), vec!["x {"]);
+    }
+
+    #[test]
+    fn duplicate_lines() {
+        let mut c = content("a\nb\nc", (1, 1));
+        c.duplicate_lines();
+        assert_eq!(c.get_text(), vec!["a", "b", "b", "c"]);
+        assert_eq!(c.get_cursor(), (2, 1));
+
+        let mut c = content("a\nb\nc", (0, 0));
+        c.select_to((1, 1), SelectionMode::Linear);
+        c.duplicate_lines();
+        assert_eq!(c.get_text(), vec!["a", "b", "a", "b", "c"]);
+        assert_eq!(c.get_selected_text(), Some("a\nb".to_string()));
+    }
+
+    #[test]
+    fn delete_lines() {
+        let mut c = content("a\nb\nc", (1, 0));
+        c.delete_lines();
+        assert_eq!(c.get_text(), vec!["a", "c"]);
+        assert_eq!(c.get_cursor(), (1, 0));
+
+        let mut c = content("a\nb\nc", (2, 1));
+        c.select_to((1, 0), SelectionMode::Linear);
+        c.delete_lines();
+        assert_eq!(c.get_text(), vec!["a"]);
+        assert_eq!(c.get_cursor(), (0, 0));
+
+        let mut c = content("a", (0, 0));
+        c.delete_lines();
+        assert_eq!(c.get_text(), vec![""]);
+    }
+
+    #[test]
+    fn join_lines() {
+        let mut c = content("let a =  \n    1;\nb", (0, 2));
+        c.join_lines();
+        assert_eq!(c.get_text(), vec!["let a = 1;", "b"]);
+        assert_eq!(c.get_cursor(), (0, 7));
+
+        let mut c = content("a\n b\n  c\nd", (0, 0));
+        c.select_to((2, 1), SelectionMode::Linear);
+        c.join_lines();
+        assert_eq!(c.get_text(), vec!["a b c", "d"]);
+    }
+
+    #[test]
+    fn sort_reverse_and_unique_lines() {
+        let mut c = content("c\na\nb\na", (0, 0));
+        c.select_to((3, 1), SelectionMode::Linear);
+        c.sort_lines();
+        assert_eq!(c.get_text(), vec!["a", "a", "b", "c"]);
+        c.unique_lines();
+        assert_eq!(c.get_text(), vec!["a", "b", "c"]);
+        c.reverse_lines();
+        assert_eq!(c.get_text(), vec!["c", "b", "a"]);
+
+        // Without a selection, the cursor lines are rearranged.
+        let mut c = content("d\na\nb\nc", (0, 1));
+        c.sort_lines();
+        assert_eq!(c.get_text(), vec!["d", "a", "b", "c"]);
+        c.add_cursor((2, 0));
+        c.sort_lines();
+        assert_eq!(c.get_text(), vec!["b", "a", "d", "c"]);
+        assert_eq!(c.get_cursors(), vec![(2, 0)]);
+        let mut c = content("a\nx\na\ny", (0, 0));
+        c.add_cursor((2, 0));
+        c.unique_lines();
+        assert_eq!(c.get_text(), vec!["a", "x", "y"]);
+
+        let mut c = content("z\nc\na\nb\nz", (1, 0));
+        c.select_to((3, 1), SelectionMode::Linear);
+        c.sort_lines();
+        assert_eq!(c.get_text(), vec!["z", "a", "b", "c", "z"]);
+        assert_eq!(c.get_selected_text(), Some("a\nb\nc".to_string()));
+    }
+
+    #[test]
+    fn toggle_line_comment() {
+        let mut c = content("fn f() {\n    a();\n\n  b();\n}", (1, 0));
+        c.set_language(Language::from_path(Some("main.rs")));
+        c.select_to((3, 2), SelectionMode::Linear);
+        c.toggle_line_comment();
+        assert_eq!(
+            c.get_text(),
+            vec!["fn f() {", "  //   a();", "", "  // b();", "}"]
+        );
+        c.toggle_line_comment();
+        assert_eq!(
+            c.get_text(),
+            vec!["fn f() {", "    a();", "", "  b();", "}"]
+        );
+    }
+
+    #[test]
+    fn toggle_block_comment() {
+        let mut c = content("  let a = 1;  ", (0, 4));
+        c.set_language(Language::from_path(Some("main.c")));
+        c.toggle_block_comment();
+        assert_eq!(c.get_text(), vec!["  /* let a = 1; */  "]);
+        c.toggle_block_comment();
+        assert_eq!(c.get_text(), vec!["  let a = 1;  "]);
+
+        c.set_cursor((0, 6));
+        c.select_to((0, 7), SelectionMode::Linear);
+        c.toggle_block_comment();
+        assert_eq!(c.get_text(), vec!["  let /* a */ = 1;  "]);
+        assert_eq!(c.get_selected_text(), Some("/* a */".to_string()));
+        c.toggle_block_comment();
+        assert_eq!(c.get_text(), vec!["  let a = 1;  "]);
+    }
+
+    #[test]
+    fn trim_trailing_whitespace() {
+        let mut c = content("a  \nb\t\nc ", (0, 0));
+        c.select_to((1, 0), SelectionMode::Linear);
+        c.select_to((1, 1), SelectionMode::Linear);
+        c.trim_trailing_whitespace();
+        assert_eq!(c.get_text(), vec!["a", "b", "c "]);
+    }
+
+    #[test]
+    fn change_case() {
+        let mut c = content("let parseHTTPRequest = 1;", (0, 8));
+        c.snake_case();
+        assert_eq!(c.get_text(), vec!["let parse_http_request = 1;"]);
+        c.kebab_case();
+        assert_eq!(c.get_text(), vec!["let parse-http-request = 1;"]);
+        c.camel_case();
+        assert_eq!(c.get_text(), vec!["let parseHttpRequest = 1;"]);
+        c.upper_case();
+        assert_eq!(c.get_text(), vec!["let PARSEHTTPREQUEST = 1;"]);
+
+        let mut c = content("hello big world", (0, 0));
+        c.select_to((0, 9), SelectionMode::Linear);
+        c.title_case();
+        assert_eq!(c.get_text(), vec!["Hello Big world"]);
+        assert_eq!(c.get_selected_text(), Some("Hello Big".to_string()));
+    }
+
+    #[test]
+    fn encode_and_decode() {
+        let mut c = content("x = a b&c", (0, 4));
+        c.select_to((0, 9), SelectionMode::Linear);
+        c.url_encode();
+        assert_eq!(c.get_text(), vec!["x = a%20b%26c"]);
+        c.url_decode();
+        assert_eq!(c.get_text(), vec!["x = a b&c"]);
+
+        let mut c = content("say hello", (0, 5));
+        c.base64_encode();
+        assert_eq!(c.get_text(), vec!["say aGVsbG8="]);
+        c.base64_decode();
+        assert_eq!(c.get_text(), vec!["say hello"]);
+
+        let mut c = content("%zz", (0, 0));
+        c.url_decode();
+        assert_eq!(c.get_text(), vec!["%zz"]);
+
+        // A block is resized to the transformed text.
+        let mut c = content("[a b]\n[c&d]", (0, 1));
+        c.select_to((1, 4), SelectionMode::Block);
+        c.url_encode();
+        assert_eq!(c.get_text(), vec!["[a%20b]", "[c%26d]"]);
+        assert_eq!(c.get_selected_text(), Some("a%20b\nc%26d".to_string()));
+        c.url_decode();
+        assert_eq!(c.get_text(), vec!["[a b]", "[c&d]"]);
+        assert_eq!(c.get_selected_text(), Some("a b\nc&d".to_string()));
+        let mut c = content("ab\ncd", (1, 2));
+        c.select_to((0, 0), SelectionMode::Block);
+        c.base64_encode();
+        assert_eq!(c.get_selected_text(), Some("YWI=\nY2Q=".to_string()));
+    }
+
+    #[test]
+    fn transpose_characters() {
+        let mut c = content("abc", (0, 1));
+        c.transpose_characters();
+        assert_eq!(c.get_text(), vec!["bac"]);
+        assert_eq!(c.get_cursor(), (0, 2));
+        c.set_cursor((0, 3));
+        c.transpose_characters();
+        assert_eq!(c.get_text(), vec!["bca"]);
+    }
+
+    #[test]
+    fn increment_and_decrement_number() {
+        let mut c = content("width: 9px", (0, 0));
+        c.increment_number();
+        assert_eq!(c.get_text(), vec!["width: 10px"]);
+        assert_eq!(c.get_cursor(), (0, 8));
+        c.set_cursor((0, 7));
+        for _ in 0..11 {
+            c.decrement_number();
+        }
+        assert_eq!(c.get_text(), vec!["width: -1px"]);
+
+        let mut c = content("none", (0, 0));
+        c.increment_number();
+        assert_eq!(c.get_text(), vec!["none"]);
+
+        let mut c = content("x-1", (0, 0));
+        c.increment_number();
+        assert_eq!(c.get_text(), vec!["x-2"]);
+        let mut c = content("a-5 (-5)", (0, 0));
+        c.decrement_number();
+        assert_eq!(c.get_text(), vec!["a-4 (-5)"]);
+        c.set_cursor((0, 4));
+        c.decrement_number();
+        assert_eq!(c.get_text(), vec!["a-4 (-6)"]);
+    }
+
+    #[test]
+    fn fold_regions() {
+        let mut c = content("a\n  b\n\n  c\nd\n  e", (0, 0));
+        assert_eq!(c.fold_regions(), vec![(0, 3), (4, 5)]);
+        c.set_cursor((4, 0));
+        c.delete_lines();
+        assert_eq!(c.fold_regions(), vec![(0, 4)]);
+
+        let mut c = content("\tb\n    c", (0, 0));
+        assert_eq!(c.fold_regions(), vec![]);
+        c.set_tab_width(2);
+        assert_eq!(c.fold_regions(), vec![(0, 1)]);
+
+        let mut c = content("f(\n1,\n2)\nx", (0, 0));
+        assert_eq!(c.fold_regions(), vec![]);
+        c.set_language(Language::from_path(Some("a.rs")));
+        assert_eq!(c.fold_regions(), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn fold_and_move() {
+        let mut c = content("a {\n  b\n  c\n}\nd", (1, 1));
+        c.fold();
+        assert_eq!(c.get_cursor(), (0, 3));
+        c.move_cursors_down(1);
+        assert_eq!(c.get_cursor(), (3, 1));
+        c.move_cursors_left(2);
+        assert_eq!(c.get_cursor(), (0, 3));
+        c.move_cursors_up(1);
+        c.move_cursors_down(1);
+        assert_eq!(c.folds(), vec![(0, 2, true)]);
+
+        c.set_cursor((4, 0));
+        c.break_line();
+        c.set_cursor((0, 0));
+        c.break_line();
+        assert_eq!(c.folds(), vec![(1, 3, true)]);
+
+        c.unfold();
+        assert_eq!(c.folds(), vec![(1, 3, false)]);
+        c.toggle_fold(1);
+        c.add_cursor((2, 0));
+        assert_eq!(c.folds(), vec![(1, 3, false)]);
+    }
+
+    #[test]
+    fn bookmarks() {
+        let mut c = content("a\nb\nc\nd", (1, 0));
+        c.toggle_bookmark();
+        c.set_cursor((3, 0));
+        c.toggle_bookmark();
+        c.next_bookmark();
+        assert_eq!(c.get_cursor(), (1, 0));
+        c.previous_bookmark();
+        assert_eq!(c.get_cursor(), (3, 0));
+
+        c.set_cursor((0, 0));
+        c.break_line();
+        assert_eq!(c.bookmarks(), &[2, 4]);
+        c.set_cursor((2, 0));
+        c.move_line_down();
+        assert_eq!(c.bookmarks(), &[3, 4]);
+        c.delete_lines();
+        assert_eq!(c.bookmarks(), &[3]);
+        c.set_cursor((3, 0));
+        c.toggle_bookmark();
+        assert!(c.bookmarks().is_empty());
+    }
+
+    #[test]
+    fn code_checks() {
+        let mut c = content("fn a() {\n\t  (]\n}}", (0, 0));
+        c.set_language(Language::from_path(Some("a.rs")));
+        assert_eq!(c.unmatched_brackets(), vec![(1, 3), (1, 4), (2, 1)]);
+        assert_eq!(c.mixed_indentation_lines(), vec![1]);
+        // What is computed from the text follows its edits and language.
+        c.set_cursor((1, 4));
+        c.remove();
+        assert_eq!(c.unmatched_brackets(), vec![(1, 3), (2, 1)]);
+        c.set_cursor((0, 7));
+        assert_eq!(c.matching_brackets(), Some(((0, 7), (2, 0))));
+        c.set_language(&PLAIN_TEXT);
+        assert!(c.unmatched_brackets().is_empty());
+    }
+
+    #[test]
+    fn replace_lines() {
+        let mut c = content("a\nb\nc", (2, 1));
+        c.replace_lines(1, 1, &["x".to_string(), "y".to_string()]);
+        assert_eq!(c.get_text(), vec!["a", "x", "y", "c"]);
+        assert_eq!(c.get_cursor(), (1, 0));
+        c.replace_lines(4, 0, &["d".to_string()]);
+        assert_eq!(c.get_text(), vec!["a", "x", "y", "c", "d"]);
+        c.replace_lines(0, 5, &[]);
+        assert_eq!(c.get_text(), vec![""]);
+    }
+
+    #[test]
+    fn from_string() {
+        let c = TextContent::from_string("a\r\nb\n".to_string());
+        assert_eq!(c.get_text(), vec!["a", "b", ""]);
+        assert_eq!(TextContent::from_string(String::new()).get_text(), vec![""]);
+    }
+
+    #[test]
+    fn complete_word() {
+        let mut c = content("let ab = a", (0, 10));
+        assert_eq!(c.word_before_cursor(), "a");
+        c.add_cursor((0, 6));
+        c.complete_word("abc");
+        assert_eq!(c.get_text(), vec!["let abc = abc"]);
+        assert_eq!(c.get_cursors(), vec![(0, 7), (0, 13)]);
+    }
+
+    #[test]
+    fn snippet_fields() {
+        let mut c = content("  fn", (0, 4));
+        assert_eq!(c.snippet_trigger(), Some(("fn".to_string(), String::new())));
+        let body = "fn ${1:name}($2) -> $1 {\n\t$0\n}";
+        c.insert_snippet(2, crate::snippets::expand(body, &Default::default()));
+        assert_eq!(c.get_text(), vec!["  fn name() -> name {", "      ", "  }"]);
+        assert_eq!(c.get_selected_text(), Some("name".to_string()));
+        c.append("ab".to_string());
+        c.track_snippet();
+        c.append("c".to_string());
+        c.track_snippet();
+        assert_eq!(c.get_text()[0], "  fn abc() -> abc {");
+        assert_eq!(c.get_cursor(), (0, 8));
+        assert!(c.next_snippet_field());
+        assert_eq!(c.get_cursor(), (0, 9));
+        c.append("x".to_string());
+        c.track_snippet();
+        assert!(c.previous_snippet_field());
+        assert_eq!(c.get_selected_text(), Some("abc".to_string()));
+        assert!(c.next_snippet_field());
+        assert!(c.next_snippet_field());
+        assert_eq!(c.get_cursor(), (1, 6));
+        assert!(!c.next_snippet_field());
+    }
+
+    #[test]
+    fn vim_motions() {
+        let c = content("fn main() {\n\n    x.y\n}", (0, 0));
+        let targets = |motion, count| {
+            (1..=count)
+                .map(|n| c.motion_target((0, 0), motion, n))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            targets(Motion::WordStart, 5),
+            vec![(0, 3), (0, 7), (0, 10), (1, 0), (2, 4)]
+        );
+        assert_eq!(targets(Motion::WordEnd, 3), vec![(0, 1), (0, 6), (0, 8)]);
+        assert_eq!(c.motion_target((2, 6), Motion::WordBack, 1), (2, 5));
+        assert_eq!(c.motion_target((2, 4), Motion::WordBack, 1), (1, 0));
+        assert_eq!(c.motion_target((1, 0), Motion::WordBack, 1), (0, 10));
+        assert_eq!(c.motion_target((0, 4), Motion::LineEnd, 2), (1, 0));
+        let region = c.motion_region((0, 7), Motion::WordStart, 2);
+        assert_eq!(region.end, (0, 11));
+        assert_eq!(c.region_text(region), "() {");
+        let region = c.motion_region((2, 5), Motion::Up, 1);
+        assert_eq!(c.region_text(region), "\n    x.y");
+        let empty = TextContent::new();
+        assert_eq!(empty.motion_target((0, 0), Motion::WordBack, 1), (0, 0));
+        assert_eq!(empty.change_word_region((0, 0), 1).end, (0, 0));
+    }
+
+    #[test]
+    fn cursor_state() {
+        let mut c = content("a\nb\nc", (2, 1));
+        let state = c.cursor_state();
+        c.set_cursor((0, 0));
+        c.delete_lines();
+        c.delete_lines();
+        c.set_cursor_state(state);
+        assert_eq!(c.get_cursor(), (0, 1));
     }
 }
@@ -0,0 +1,70 @@
+use super::{Region, SelectionMode, TextContent};
+
+impl TextContent {
+    /// Sets the mark at the cursor, the region then going from the mark to
+    /// the cursor as it moves.
+    pub fn set_mark(&mut self) {
+        self.secondary_cursors.clear();
+        self.selection = Some((self.get_cursor(), SelectionMode::Linear));
+    }
+
+    pub fn has_mark(&self) -> bool {
+        matches!(self.selection, Some((_, SelectionMode::Linear)))
+    }
+
+    /// Moves the cursor with `op`, extending the region when the mark is set,
+    /// or else moves every cursor.
+    pub fn move_point(&mut self, op: fn(&mut TextContent)) {
+        if self.has_mark() {
+            op(self);
+            return;
+        }
+        self.clear_selection();
+        self.for_each_cursor(|content, _| op(content));
+    }
+
+    /// Deletes from the cursor to the end of its line, or the line break when
+    /// the cursor is already there, and returns the deleted text.
+    pub fn kill_line(&mut self) -> String {
+        self.clear_selection();
+        self.secondary_cursors.clear();
+        let (l, c) = self.get_cursor();
+        let end = if c < self.content[l].len() {
+            (l, self.content[l].len())
+        } else if l + 1 < self.content.len() {
+            (l + 1, 0)
+        } else {
+            return String::new();
+        };
+        let text = self.region_text(Region {
+            start: (l, c),
+            end,
+            lines: false,
+        });
+        self.delete_range((l, c), end);
+        self.cursor = (l, c);
+        text
+    }
+
+    /// Inserts `text` at the cursor, replacing the region, and returns where
+    /// it went.
+    pub fn yank(&mut self, text: &str) -> Region {
+        self.take_selection(false);
+        self.secondary_cursors.clear();
+        let start = self.get_cursor();
+        self.insert_at_cursor(&text.split('\n').collect::<Vec<_>>());
+        Region {
+            start,
+            end: self.get_cursor(),
+            lines: false,
+        }
+    }
+
+    /// Replaces the text yanked in `region` by `text`.
+    pub fn replace_yank(&mut self, region: Region, text: &str) -> Region {
+        self.clear_selection();
+        self.delete_range(region.start, region.end);
+        self.cursor = region.start;
+        self.yank(text)
+    }
+}
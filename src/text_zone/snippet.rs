@@ -0,0 +1,242 @@
+use crate::snippets::Expansion;
+
+use super::{is_blank, is_word_char, SelectionMode, TextContent};
+
+/// The fields of an expanded snippet, as character offsets in the text, kept
+/// in step with the edits until the last field is reached.
+#[derive(Clone)]
+pub(super) struct SnippetFields {
+    fields: Vec<Vec<(usize, usize)>>,
+    current: usize,
+    /// The text when the fields were last updated.
+    text: Vec<char>,
+}
+
+impl TextContent {
+    pub(super) fn flat_text(&self) -> Vec<char> {
+        self.content.join(&'\n')
+    }
+
+    /// Replaces the characters between the offsets `start` and `end` by
+    /// `text`.
+    fn splice_offsets(&mut self, start: usize, end: usize, text: &[char]) {
+        self.delete_range(self.position_of(start), self.position_of(end));
+        let (l, c) = self.position_of(start);
+        let mut lines: Vec<Vec<char>> = text.split(|ch| *ch == '\n').map(|l| l.to_vec()).collect();
+        let tail = self.content[l].split_off(c);
+        self.content[l].extend(lines.remove(0));
+        let inserted = lines.len();
+        self.content.splice(l + 1..l + 1, lines);
+        self.content[l + inserted].extend(tail);
+        self.shift_line_marks(l + 1, 0, inserted);
+    }
+
+    /// Returns the word before the selection or the cursor with the selected
+    /// text, when a snippet may replace them.
+    pub fn snippet_trigger(&self) -> Option<(String, String)> {
+        let (start, end) = match self.selection {
+            None => (self.get_cursor(), self.get_cursor()),
+            Some((anchor, SelectionMode::Linear)) => self.linear_bounds(anchor),
+            Some((_, SelectionMode::Block)) => return None,
+        };
+        if start.0 != end.0 || !self.secondary_cursors.is_empty() {
+            return None;
+        }
+        let (l, c) = start;
+        let line = &self.content[l];
+        let prefix_start = c - line[..c]
+            .iter()
+            .rev()
+            .take_while(|ch| is_word_char(**ch))
+            .count();
+        if prefix_start == c {
+            return None;
+        }
+        let prefix = line[prefix_start..c].iter().collect();
+        Some((prefix, line[c..end.1].iter().collect()))
+    }
+
+    /// Replaces the selection and the `prefix_len` characters before it by
+    /// the text of `expansion`, indented like the cursor line, and selects
+    /// its first field.
+    pub fn insert_snippet(&mut self, prefix_len: usize, expansion: Expansion) {
+        self.take_selection(false);
+        self.secondary_cursors.clear();
+        let (l, c) = self.get_cursor();
+        let indentation: Vec<char> = self.content[l][..self.indentation_length(l)].to_vec();
+        let unit: Vec<char> = self.indent_unit(0).chars().collect();
+        // Offsets of the expansion in the inserted text, which has the
+        // indentation added.
+        let mut offsets = Vec::new();
+        let mut text = Vec::new();
+        let mut line_start = false;
+        for ch in expansion.text.chars() {
+            offsets.push(text.len());
+            match ch {
+                '\n' => {
+                    text.push('\n');
+                    text.extend(&indentation);
+                    line_start = true;
+                    continue;
+                }
+                '\t' if line_start => text.extend(&unit),
+                ch => text.push(ch),
+            }
+            line_start = line_start && is_blank(ch);
+        }
+        offsets.push(text.len());
+        let start = self.offset_of((l, c - prefix_len));
+        self.splice_offsets(start, start + prefix_len, &text);
+        let fields = expansion
+            .fields
+            .into_iter()
+            .map(|ranges| {
+                (ranges.into_iter())
+                    .map(|(a, b)| (start + offsets[a], start + offsets[b]))
+                    .collect()
+            })
+            .collect();
+        self.snippet = Some(SnippetFields {
+            fields,
+            current: 0,
+            text: self.flat_text(),
+        });
+        self.select_snippet_field();
+    }
+
+    /// Selects the first range of the current field, and ends the snippet
+    /// when it is the last one.
+    fn select_snippet_field(&mut self) {
+        let Some(snippet) = &self.snippet else {
+            return;
+        };
+        let (start, end) = snippet.fields[snippet.current][0];
+        let last = snippet.current == snippet.fields.len() - 1;
+        self.secondary_cursors.clear();
+        self.selection = (start != end).then_some((self.position_of(start), SelectionMode::Linear));
+        self.cursor = self.position_of(end);
+        if last {
+            self.snippet = None;
+        }
+        self.reveal_cursors();
+    }
+
+    /// Goes to the next field of the snippet, and returns false when there is
+    /// no snippet.
+    pub fn next_snippet_field(&mut self) -> bool {
+        self.track_snippet();
+        let Some(snippet) = self.snippet.as_mut() else {
+            return false;
+        };
+        snippet.current += 1;
+        self.select_snippet_field();
+        true
+    }
+
+    /// Goes back to the previous field of the snippet, and returns false when
+    /// there is no snippet.
+    pub fn previous_snippet_field(&mut self) -> bool {
+        self.track_snippet();
+        let Some(snippet) = self.snippet.as_mut() else {
+            return false;
+        };
+        snippet.current = snippet.current.saturating_sub(1);
+        self.select_snippet_field();
+        true
+    }
+
+    /// Returns whether the fields of a snippet are being filled in.
+    pub fn in_snippet(&self) -> bool {
+        self.snippet.is_some()
+    }
+
+    pub fn end_snippet(&mut self) {
+        self.snippet = None;
+    }
+
+    /// Moves the fields of the snippet after the edits made since the last
+    /// call, and copies an edited field to its mirrors. An edit overlapping
+    /// the edge of a field ends the snippet.
+    pub fn track_snippet(&mut self) {
+        let Some(mut snippet) = self.snippet.take() else {
+            return;
+        };
+        let text = self.flat_text();
+        if text == snippet.text {
+            self.snippet = Some(snippet);
+            return;
+        }
+        let (old_len, new_len) = (snippet.text.len(), text.len());
+        let delta = new_len as isize - old_len as isize;
+        // The edit is taken to end at the cursor when the text around it
+        // repeats, as when typing the character before the cursor again.
+        let cursor = self.offset_of(self.get_cursor());
+        let prefix = (snippet.text.iter().zip(&text))
+            .take_while(|(a, b)| a == b)
+            .count()
+            .min(cursor.saturating_sub(delta.max(0) as usize));
+        let suffix = (snippet.text[prefix..].iter().rev())
+            .zip(text[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let (start, old_end) = (prefix, old_len - suffix);
+        let mut edited = None;
+        for (i, ranges) in snippet.fields.iter_mut().enumerate() {
+            for (j, (a, b)) in ranges.iter_mut().enumerate() {
+                let inside = *a <= start && old_end <= *b;
+                if i == snippet.current && inside {
+                    *b = (*b as isize + delta) as usize;
+                    edited = Some(j);
+                } else if *a >= old_end {
+                    *a = (*a as isize + delta) as usize;
+                    *b = (*b as isize + delta) as usize;
+                } else if *b <= start {
+                } else if inside {
+                    *b = (*b as isize + delta) as usize;
+                } else {
+                    return;
+                }
+            }
+        }
+        if let Some(j) = edited {
+            self.mirror_field(&mut snippet, j);
+        }
+        snippet.text = self.flat_text();
+        self.snippet = Some(snippet);
+    }
+
+    /// Copies the range `edited` of the current field over its other ranges.
+    fn mirror_field(&mut self, snippet: &mut SnippetFields, edited: usize) {
+        let ranges = snippet.fields[snippet.current].clone();
+        let (a, b) = ranges[edited];
+        let value: Vec<char> = self.flat_text()[a..b].to_vec();
+        let mut others: Vec<usize> = (0..ranges.len()).filter(|j| *j != edited).collect();
+        others.sort_by_key(|j| std::cmp::Reverse(ranges[*j].0));
+        for j in others {
+            let (start, end) = snippet.fields[snippet.current][j];
+            let delta = value.len() as isize - (end - start) as isize;
+            let shift = |offset: usize| (offset as isize + delta) as usize;
+            let cursor = self.offset_of(self.cursor);
+            let anchor = self.selection.map(|(anchor, _)| self.offset_of(anchor));
+            self.splice_offsets(start, end, &value);
+            if cursor >= end {
+                self.cursor = self.position_of(shift(cursor));
+            }
+            if let (Some(anchor), Some((_, mode))) = (anchor.filter(|a| *a >= end), self.selection)
+            {
+                self.selection = Some((self.position_of(shift(anchor)), mode));
+            }
+            for (i, ranges) in snippet.fields.iter_mut().enumerate() {
+                for (k, (a, b)) in ranges.iter_mut().enumerate() {
+                    if (i, k) == (snippet.current, j) {
+                        *b = start + value.len();
+                    } else if *a >= end {
+                        (*a, *b) = (shift(*a), shift(*b));
+                    } else if *b >= end && *a <= start {
+                        *b = shift(*b);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,160 @@
+/// Splits an identifier or a phrase into its words, on separators and on
+/// changes of case.
+fn words(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut words = Vec::new();
+    let mut current = String::new();
+    for (i, ch) in chars.iter().enumerate() {
+        if !ch.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        let prev = if i > 0 { Some(chars[i - 1]) } else { None };
+        let next = chars.get(i + 1);
+        let boundary = ch.is_uppercase()
+            && prev.is_some_and(|p| {
+                p.is_lowercase()
+                    || p.is_numeric()
+                    || (p.is_uppercase() && next.is_some_and(|n| n.is_lowercase()))
+            });
+        if boundary && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        current.push(*ch);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(|c| c.to_lowercase()))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+pub fn upper_case(text: &str) -> Option<String> {
+    Some(text.to_uppercase())
+}
+
+pub fn lower_case(text: &str) -> Option<String> {
+    Some(text.to_lowercase())
+}
+
+/// Capitalizes every word, keeping what separates them.
+pub fn title_case(text: &str) -> Option<String> {
+    let mut res = String::new();
+    let mut start_of_word = true;
+    for ch in text.chars() {
+        if start_of_word {
+            res.extend(ch.to_uppercase());
+        } else {
+            res.extend(ch.to_lowercase());
+        }
+        start_of_word = !ch.is_alphanumeric() && ch != '\'';
+    }
+    Some(res)
+}
+
+pub fn snake_case(text: &str) -> Option<String> {
+    let words: Vec<String> = words(text).iter().map(|w| w.to_lowercase()).collect();
+    Some(words.join("_"))
+}
+
+pub fn kebab_case(text: &str) -> Option<String> {
+    let words: Vec<String> = words(text).iter().map(|w| w.to_lowercase()).collect();
+    Some(words.join("-"))
+}
+
+pub fn camel_case(text: &str) -> Option<String> {
+    let words = words(text);
+    let mut res = String::new();
+    for (i, word) in words.iter().enumerate() {
+        if i == 0 {
+            res.push_str(&word.to_lowercase());
+        } else {
+            res.push_str(&capitalize(word));
+        }
+    }
+    Some(res)
+}
+
+const BASE64_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn base64_encode(text: &str) -> Option<String> {
+    let mut res = String::new();
+    for chunk in text.as_bytes().chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                res.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                res.push('=');
+            }
+        }
+    }
+    Some(res)
+}
+
+/// Returns `None` when `text` is not base64 encoded UTF-8.
+pub fn base64_decode(text: &str) -> Option<String> {
+    let text = text.trim_end_matches('=');
+    let mut bytes = Vec::new();
+    let mut n = 0u32;
+    for (i, ch) in text.bytes().enumerate() {
+        let value = BASE64_ALPHABET.iter().position(|b| *b == ch)? as u32;
+        n = n << 6 | value;
+        if i % 4 == 3 {
+            bytes.extend_from_slice(&n.to_be_bytes()[1..]);
+            n = 0;
+        }
+    }
+    match text.len() % 4 {
+        0 => {}
+        2 => bytes.push((n >> 4) as u8),
+        3 => bytes.extend_from_slice(&((n >> 2) as u16).to_be_bytes()),
+        _ => return None,
+    }
+    String::from_utf8(bytes).ok()
+}
+
+pub fn url_encode(text: &str) -> Option<String> {
+    let mut res = String::new();
+    for b in text.bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) {
+            res.push(b as char);
+        } else {
+            res.push_str(&format!("%{:02X}", b));
+        }
+    }
+    Some(res)
+}
+
+/// Returns `None` when `text` holds invalid escapes or UTF-8.
+pub fn url_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut res = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            res.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            res.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(res).ok()
+}
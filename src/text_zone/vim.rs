@@ -0,0 +1,333 @@
+use super::{is_word_char, SelectionMode, TextContent};
+
+/// Where a cursor goes with the motions of the vim keys.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    /// To the first non-blank character of the next line.
+    NextLine,
+    WordStart,
+    WordEnd,
+    WordBack,
+    LineStart,
+    /// To the last character of the line.
+    LineEnd,
+    /// To the first non-blank character of the given line.
+    Line(usize),
+}
+
+/// A part of the text an operator applies to. `end` is excluded, and whole
+/// lines are taken from `start` to `end` when `lines` is set.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Region {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub lines: bool,
+}
+
+/// Whitespace, word characters and other characters, the runs of the last
+/// two being the words of the vim keys.
+fn char_class(ch: char) -> u8 {
+    if ch.is_whitespace() {
+        0
+    } else if is_word_char(ch) {
+        1
+    } else {
+        2
+    }
+}
+
+impl Motion {
+    fn is_linewise(self) -> bool {
+        matches!(
+            self,
+            Motion::Up | Motion::Down | Motion::NextLine | Motion::Line(_)
+        )
+    }
+
+    /// Whether an operator also applies to the character the motion ends on.
+    fn is_inclusive(self) -> bool {
+        matches!(self, Motion::WordEnd | Motion::LineEnd)
+    }
+}
+
+impl TextContent {
+    pub fn char_at(&self, position: (usize, usize)) -> Option<char> {
+        let (l, c) = position;
+        self.content.get(l).and_then(|line| line.get(c)).copied()
+    }
+
+    pub fn first_non_blank(&self, l: usize) -> (usize, usize) {
+        (l, self.indentation_length(l))
+    }
+
+    /// Returns the offset of the start of the next word after `offset`, an
+    /// empty line counting as a word.
+    fn next_word_start(&self, text: &[char], offset: usize) -> usize {
+        let mut i = offset;
+        if let Some(class) = text.get(i).map(|ch| char_class(*ch)).filter(|c| *c != 0) {
+            while i < text.len() && char_class(text[i]) == class {
+                i += 1;
+            }
+        }
+        while i < text.len() && char_class(text[i]) == 0 {
+            if text[i] == '\n' && text.get(i + 1) == Some(&'\n') {
+                return i + 1;
+            }
+            i += 1;
+        }
+        i
+    }
+
+    fn word_end(&self, text: &[char], offset: usize) -> usize {
+        let mut i = offset + 1;
+        while i < text.len() && char_class(text[i]) == 0 {
+            i += 1;
+        }
+        let Some(class) = text.get(i).map(|ch| char_class(*ch)) else {
+            return text.len().saturating_sub(1).max(offset);
+        };
+        while i + 1 < text.len() && char_class(text[i + 1]) == class {
+            i += 1;
+        }
+        i
+    }
+
+    fn previous_word_start(&self, text: &[char], offset: usize) -> usize {
+        if text.is_empty() {
+            return offset;
+        }
+        let mut i = offset;
+        while i > 0 {
+            i -= 1;
+            let empty_line = text[i] == '\n' && (i == 0 || text[i - 1] == '\n');
+            if empty_line {
+                return i;
+            }
+            if char_class(text[i]) != 0 {
+                break;
+            }
+        }
+        let class = char_class(text[i]);
+        while i > 0 && char_class(text[i - 1]) == class && class != 0 {
+            i -= 1;
+        }
+        i
+    }
+
+    /// Returns where `motion` repeated `count` times goes from `from`.
+    pub fn motion_target(
+        &self,
+        from: (usize, usize),
+        motion: Motion,
+        count: usize,
+    ) -> (usize, usize) {
+        let (l, c) = self.clamp_cursor(from);
+        let last = self.content.len() - 1;
+        let column = |l: usize| c.min(self.content[l].len().saturating_sub(1));
+        match motion {
+            Motion::Left => (l, c.saturating_sub(count)),
+            Motion::Right => (l, c.saturating_add(count).min(self.content[l].len())),
+            Motion::Up => {
+                let l = l.saturating_sub(count);
+                (l, column(l))
+            }
+            Motion::Down => {
+                let l = l.saturating_add(count).min(last);
+                (l, column(l))
+            }
+            Motion::NextLine => self.first_non_blank(l.saturating_add(count).min(last)),
+            Motion::LineStart => (l, 0),
+            Motion::LineEnd => {
+                let l = l.saturating_add(count.saturating_sub(1)).min(last);
+                (l, self.content[l].len().saturating_sub(1))
+            }
+            Motion::Line(n) => self.first_non_blank(n.min(last)),
+            Motion::WordStart | Motion::WordEnd | Motion::WordBack => {
+                let text = self.flat_text();
+                let mut offset = self.offset_of((l, c));
+                // Each move passes at least a character until the text ends.
+                for _ in 0..count.min(text.len() + 1) {
+                    offset = match motion {
+                        Motion::WordStart => self.next_word_start(&text, offset),
+                        Motion::WordEnd => self.word_end(&text, offset),
+                        _ => self.previous_word_start(&text, offset),
+                    };
+                }
+                self.position_of(offset)
+            }
+        }
+    }
+
+    /// Returns the part of the text an operator followed by `motion` applies
+    /// to.
+    pub fn motion_region(&self, from: (usize, usize), motion: Motion, count: usize) -> Region {
+        let from = self.clamp_cursor(from);
+        let mut to = self.motion_target(from, motion, count);
+        if motion.is_linewise() {
+            let (l1, l2) = (from.0.min(to.0), from.0.max(to.0));
+            return Region {
+                start: (l1, 0),
+                end: (l2, self.content[l2].len()),
+                lines: true,
+            };
+        }
+        // A word motion stops at the end of the line its last word is on.
+        if motion == Motion::WordStart && to.0 > from.0 && to.1 <= self.indentation_length(to.0) {
+            to = (to.0 - 1, self.content[to.0 - 1].len());
+        }
+        let (start, mut end) = (from.min(to), from.max(to));
+        if motion.is_inclusive() {
+            end.1 = (end.1 + 1).min(self.content[end.0].len());
+        }
+        Region {
+            start,
+            end,
+            lines: false,
+        }
+    }
+
+    /// Returns the region `cw` changes on a word: from the cursor to the end
+    /// of its word, then of the `count - 1` next ones.
+    pub fn change_word_region(&self, from: (usize, usize), count: usize) -> Region {
+        let text = self.flat_text();
+        let start = self.offset_of(from);
+        let class = |i: usize| text.get(i).map(|ch| char_class(*ch));
+        let same_word = class(start).is_some() && class(start + 1) == class(start);
+        let mut end = if same_word {
+            self.word_end(&text, start)
+        } else {
+            start
+        };
+        for _ in 1..count.min(text.len() + 1) {
+            end = self.word_end(&text, end);
+        }
+        Region {
+            start: from,
+            end: self.position_of((end + 1).min(text.len())),
+            lines: false,
+        }
+    }
+
+    pub fn region_text(&self, region: Region) -> String {
+        if region.lines {
+            let lines = &self.content[region.start.0..=region.end.0];
+            let lines: Vec<String> = lines.iter().map(|line| line.iter().collect()).collect();
+            return lines.join("\n");
+        }
+        let text = self.flat_text();
+        text[self.offset_of(region.start)..self.offset_of(region.end)]
+            .iter()
+            .collect()
+    }
+
+    /// Deletes `region` and returns its text, leaving the cursor where it
+    /// started.
+    pub fn delete_region(&mut self, region: Region) -> String {
+        let text = self.region_text(region);
+        let ((l1, c1), (l2, _)) = (region.start, region.end);
+        if region.lines {
+            self.content.drain(l1..=l2);
+            if self.content.is_empty() {
+                self.content.push(Vec::new());
+            }
+            self.shift_line_marks(l1, l2 - l1 + 1, 0);
+            self.set_cursor(self.first_non_blank(l1.min(self.content.len() - 1)));
+        } else {
+            self.delete_range(region.start, region.end);
+            self.set_cursor((l1, c1));
+        }
+        text
+    }
+
+    /// Deletes `region` and returns its text, keeping one line with the
+    /// indentation of the first one when whole lines are changed.
+    pub fn change_region(&mut self, region: Region) -> String {
+        if !region.lines {
+            return self.delete_region(region);
+        }
+        let (l1, l2) = (region.start.0, region.end.0);
+        let text = self.region_text(region);
+        let indentation = self.content[l1][..self.indentation_length(l1)].to_vec();
+        self.content.drain(l1 + 1..=l2);
+        self.shift_line_marks(l1 + 1, l2 - l1, 0);
+        self.content[l1] = indentation;
+        self.set_cursor((l1, self.content[l1].len()));
+        text
+    }
+
+    /// Inserts `text` `count` times after the cursor, or before it, as whole
+    /// lines under or above the cursor line when `lines` is set.
+    pub fn put(&mut self, text: &str, lines: bool, before: bool, count: usize) {
+        let (l, c) = self.get_cursor();
+        if lines {
+            let at = if before { l } else { l + 1 };
+            let inserted: Vec<Vec<char>> = (0..count)
+                .flat_map(|_| text.split('\n'))
+                .map(|line| line.chars().collect())
+                .collect();
+            let n = inserted.len();
+            self.content.splice(at..at, inserted);
+            self.shift_line_marks(at, 0, n);
+            self.set_cursor(self.first_non_blank(at));
+            return;
+        }
+        let at = if before {
+            c
+        } else {
+            (c + 1).min(self.content[l].len())
+        };
+        let repeated: String = text.repeat(count);
+        let start = self.offset_of((l, at));
+        self.set_cursor((l, at));
+        self.insert_at_cursor(&repeated.split('\n').collect::<Vec<_>>());
+        let len = repeated.chars().count();
+        self.set_cursor(self.position_of(start + len.saturating_sub(1)));
+    }
+
+    /// Opens a line under the cursor line, or above it, indented like it.
+    pub fn open_line(&mut self, above: bool) {
+        let (l, _) = self.get_cursor();
+        let indentation = self.content[l][..self.indentation_length(l)].to_vec();
+        let at = if above { l } else { l + 1 };
+        let len = indentation.len();
+        self.content.insert(at, indentation);
+        self.shift_line_marks(at, 0, 1);
+        self.set_cursor((at, len));
+    }
+
+    /// Keeps the cursor on a character of its line, as the normal mode of the
+    /// vim keys does.
+    pub fn cursor_on_char(&mut self) {
+        let (l, c) = self.get_cursor();
+        let len = self.content[l].len();
+        if len > 0 && c >= len {
+            self.set_cursor((l, len - 1));
+        }
+    }
+
+    /// Selects from `anchor` to `cursor`, the character under the cursor
+    /// being shown by the cursor itself, or whole lines when `lines` is set.
+    pub fn select_region(&mut self, anchor: (usize, usize), cursor: (usize, usize), lines: bool) {
+        let (anchor, cursor) = if lines {
+            let line_end = |l: usize| (l, self.content[l].len());
+            if cursor.0 >= anchor.0 {
+                ((anchor.0, 0), line_end(cursor.0))
+            } else {
+                (line_end(anchor.0), (cursor.0, 0))
+            }
+        } else if cursor < anchor {
+            (
+                (anchor.0, (anchor.1 + 1).min(self.content[anchor.0].len())),
+                cursor,
+            )
+        } else {
+            (anchor, cursor)
+        };
+        self.set_cursor(anchor);
+        self.select_to(cursor, SelectionMode::Linear);
+    }
+}
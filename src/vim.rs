@@ -0,0 +1,744 @@
+use std::collections::HashMap;
+
+use crate::text_zone::{Motion, Region, TextContent};
+
+/// The register written by every deletion and copy.
+const UNNAMED_REGISTER: char = '"';
+
+/// Most times a command is repeated, however large its count.
+const MAX_COUNT: usize = 10_000;
+
+/// The keys the vim layer reads, typed characters and the few keys without
+/// a text it uses.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Key {
+    Char(char),
+    Escape,
+    Return,
+    Backspace,
+    Tab,
+}
+
+/// What the editor still has to do with a key given to the vim layer.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Action {
+    /// The key is not used by the vim layer.
+    Pass,
+    Handled,
+    Write,
+    Quit,
+    /// Quits even with unsaved changes.
+    ForceQuit,
+    WriteQuit,
+    /// A command line that is not a known command.
+    Unknown(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Mode {
+    Normal,
+    Insert,
+    /// Selects characters from `anchor` to `cursor`, or whole lines.
+    Visual {
+        anchor: (usize, usize),
+        cursor: (usize, usize),
+        lines: bool,
+    },
+    /// Typing a command after `:`.
+    Command(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+/// What a complete normal mode command does.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Command {
+    Move(Motion),
+    Operate(Operator, Motion),
+    /// The operator doubled, applied to the lines from the cursor one.
+    OperateLines(Operator),
+    /// Enters the insert mode, `i`, `a`, `I`, `A`, `o` or `O`.
+    Insert(char),
+    DeleteChar,
+    Put {
+        before: bool,
+    },
+    Join,
+    Repeat,
+    Visual {
+        lines: bool,
+    },
+    OpenCommandLine,
+}
+
+/// The result of reading the keys typed so far in the normal mode.
+#[derive(Debug, PartialEq)]
+enum Parse {
+    Incomplete,
+    Invalid,
+    Complete {
+        register: Option<char>,
+        count: Option<usize>,
+        command: Command,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Register {
+    text: String,
+    lines: bool,
+}
+
+/// Modal editing in front of the usual keys: the normal mode moves and edits
+/// with commands made of a count, an operator and a motion, the insert mode
+/// types text, and the visual mode applies operators to a selection.
+pub(crate) struct Vim {
+    mode: Mode,
+    /// The keys of the normal mode command being typed.
+    pending: Vec<char>,
+    registers: HashMap<char, Register>,
+    /// The keys of the change being made, followed by the keys typed in the
+    /// insert mode it entered.
+    change: Option<Vec<Key>>,
+    /// The keys of the last complete change, replayed by `.`.
+    last_change: Vec<Key>,
+    /// Whether a change is being replayed, the typed text then being
+    /// inserted by the vim layer itself.
+    repeating: bool,
+}
+
+/// Reads a count at the start of `keys`, and returns it with the rest.
+fn read_count(keys: &[char]) -> (Option<usize>, &[char]) {
+    let digits = keys
+        .iter()
+        .enumerate()
+        .take_while(|(i, ch)| ch.is_ascii_digit() && (*i > 0 || **ch != '0'))
+        .count();
+    let count = (digits > 0).then(|| {
+        keys[..digits].iter().fold(0usize, |count, ch| {
+            let digit = ch.to_digit(10).unwrap_or(0) as usize;
+            count.saturating_mul(10).saturating_add(digit)
+        })
+    });
+    (count, &keys[digits..])
+}
+
+fn operator(ch: char) -> Option<Operator> {
+    match ch {
+        'd' => Some(Operator::Delete),
+        'c' => Some(Operator::Change),
+        'y' => Some(Operator::Yank),
+        _ => None,
+    }
+}
+
+/// Reads a motion, the whole of `keys`. `count` goes to the line motions,
+/// which use it as a line number.
+fn read_motion(keys: &[char], count: Option<usize>, last_line: usize) -> Parse {
+    let motion = match keys {
+        ['h'] => Motion::Left,
+        ['l'] | [' '] => Motion::Right,
+        ['k'] => Motion::Up,
+        ['j'] => Motion::Down,
+        ['w'] => Motion::WordStart,
+        ['e'] => Motion::WordEnd,
+        ['b'] => Motion::WordBack,
+        ['0'] => Motion::LineStart,
+        ['$'] => Motion::LineEnd,
+        ['g'] => return Parse::Incomplete,
+        ['g', 'g'] => Motion::Line(count.unwrap_or(1).saturating_sub(1)),
+        ['G'] => Motion::Line(count.map_or(last_line, |n| n.saturating_sub(1))),
+        [] => return Parse::Incomplete,
+        _ => return Parse::Invalid,
+    };
+    Parse::Complete {
+        register: None,
+        count: if let Motion::Line(_) = motion {
+            None
+        } else {
+            count
+        },
+        command: Command::Move(motion),
+    }
+}
+
+/// Reads a normal mode command: an optional `"` and register, a count, then
+/// a command, or an operator with a count and a motion. In the visual mode,
+/// operators apply to the selection without a motion.
+fn parse(keys: &[char], last_line: usize, visual: bool) -> Parse {
+    let (register, keys) = match keys {
+        ['"'] => return Parse::Incomplete,
+        ['"', register, rest @ ..] => (Some(*register), rest),
+        keys => (None, keys),
+    };
+    let (count, keys) = read_count(keys);
+    let command = match keys {
+        [] => return Parse::Incomplete,
+        ['i' | 'a' | 'I' | 'A' | 'o' | 'O'] => Command::Insert(keys[0]),
+        ['x'] => Command::DeleteChar,
+        ['D'] => Command::Operate(Operator::Delete, Motion::LineEnd),
+        ['C'] => Command::Operate(Operator::Change, Motion::LineEnd),
+        ['p'] => Command::Put { before: false },
+        ['P'] => Command::Put { before: true },
+        ['J'] => Command::Join,
+        ['.'] => Command::Repeat,
+        ['v'] => Command::Visual { lines: false },
+        ['V'] => Command::Visual { lines: true },
+        [':'] => Command::OpenCommandLine,
+        [first] if visual && operator(*first).is_some() => {
+            Command::OperateLines(operator(*first).unwrap())
+        }
+        [first, rest @ ..] if operator(*first).is_some() => {
+            let op = operator(*first).unwrap();
+            if rest == [*first] {
+                Command::OperateLines(op)
+            } else {
+                let (motion_count, motion_keys) = read_count(rest);
+                let total = match (count, motion_count) {
+                    (None, None) => None,
+                    (a, b) => Some(a.unwrap_or(1).saturating_mul(b.unwrap_or(1))),
+                };
+                return match read_motion(motion_keys, total, last_line) {
+                    Parse::Complete {
+                        count,
+                        command: Command::Move(motion),
+                        ..
+                    } => Parse::Complete {
+                        register,
+                        count,
+                        command: Command::Operate(op, motion),
+                    },
+                    parse => parse,
+                };
+            }
+        }
+        keys => {
+            return match read_motion(keys, count, last_line) {
+                Parse::Complete { count, command, .. } => Parse::Complete {
+                    register,
+                    count,
+                    command,
+                },
+                parse => parse,
+            }
+        }
+    };
+    Parse::Complete {
+        register,
+        count,
+        command,
+    }
+}
+
+impl Vim {
+    pub fn new() -> Self {
+        Vim {
+            mode: Mode::Normal,
+            pending: Vec::new(),
+            registers: HashMap::new(),
+            change: None,
+            last_change: Vec::new(),
+            repeating: false,
+        }
+    }
+
+    pub fn is_inserting(&self) -> bool {
+        self.mode == Mode::Insert
+    }
+
+    /// Whether the cursor is drawn over a character rather than between two.
+    pub fn has_block_cursor(&self) -> bool {
+        !self.is_inserting()
+    }
+
+    /// Describes the mode, with the keys of the command being typed.
+    pub fn status(&self) -> String {
+        let pending: String = self.pending.iter().collect();
+        let status = match &self.mode {
+            Mode::Normal => format!("-- NORMAL -- {}", pending),
+            Mode::Insert => "-- INSERT --".to_string(),
+            Mode::Visual { lines: false, .. } => format!("-- VISUAL -- {}", pending),
+            Mode::Visual { lines: true, .. } => format!("-- VISUAL LINE -- {}", pending),
+            Mode::Command(line) => format!(":{}", line),
+        };
+        status.trim_end().to_string()
+    }
+
+    /// Handles `key` according to the mode.
+    pub fn key(&mut self, key: Key, content: &mut TextContent) -> Action {
+        match self.mode.clone() {
+            Mode::Insert => self.insert_key(key, content),
+            Mode::Command(line) => self.command_line_key(key, line, content),
+            Mode::Normal | Mode::Visual { .. } => {
+                let action = self.normal_key(key, content);
+                if let Mode::Visual {
+                    anchor,
+                    cursor,
+                    lines,
+                } = self.mode
+                {
+                    content.select_region(anchor, cursor, lines);
+                } else if self.mode == Mode::Normal {
+                    content.cursor_on_char();
+                }
+                action
+            }
+        }
+    }
+
+    fn insert_key(&mut self, key: Key, content: &mut TextContent) -> Action {
+        if let Some(change) = self.change.as_mut() {
+            change.push(key);
+        }
+        if key == Key::Escape {
+            self.mode = Mode::Normal;
+            if let Some(change) = self.change.take() {
+                self.last_change = change;
+            }
+            let cursor = content.get_cursor();
+            content.set_cursor(content.motion_target(cursor, Motion::Left, 1));
+            return Action::Handled;
+        }
+        if !self.repeating {
+            return Action::Pass;
+        }
+        match key {
+            Key::Char(ch) => content.append(ch.to_string()),
+            Key::Return => content.break_line(),
+            Key::Backspace => content.remove(),
+            Key::Tab => content.indent(),
+            Key::Escape => {}
+        }
+        Action::Handled
+    }
+
+    fn command_line_key(
+        &mut self,
+        key: Key,
+        mut line: String,
+        content: &mut TextContent,
+    ) -> Action {
+        match key {
+            Key::Char(ch) => line.push(ch),
+            Key::Backspace if line.is_empty() => {
+                self.mode = Mode::Normal;
+                return Action::Handled;
+            }
+            Key::Backspace => {
+                line.pop();
+            }
+            Key::Escape => {
+                self.mode = Mode::Normal;
+                return Action::Handled;
+            }
+            Key::Return => {
+                self.mode = Mode::Normal;
+                return self.run_command_line(line.trim(), content);
+            }
+            Key::Tab => {}
+        }
+        self.mode = Mode::Command(line);
+        Action::Handled
+    }
+
+    fn run_command_line(&mut self, line: &str, content: &mut TextContent) -> Action {
+        match line {
+            "w" => Action::Write,
+            "q" => Action::Quit,
+            "q!" => Action::ForceQuit,
+            "wq" | "x" => Action::WriteQuit,
+            "" => Action::Handled,
+            line => match line.parse::<usize>() {
+                Ok(n) => {
+                    let target = content.motion_target(
+                        content.get_cursor(),
+                        Motion::Line(n.saturating_sub(1)),
+                        1,
+                    );
+                    content.set_cursor(target);
+                    Action::Handled
+                }
+                Err(_) => Action::Unknown(line.to_string()),
+            },
+        }
+    }
+
+    fn normal_key(&mut self, key: Key, content: &mut TextContent) -> Action {
+        let ch = match key {
+            Key::Char(ch) => ch,
+            Key::Escape if !self.pending.is_empty() => {
+                self.pending.clear();
+                return Action::Handled;
+            }
+            Key::Escape => {
+                if let Mode::Visual { cursor, .. } = self.mode {
+                    self.mode = Mode::Normal;
+                    content.set_cursor(cursor);
+                }
+                return Action::Handled;
+            }
+            // The keys without a text are motions when nothing is typed yet.
+            Key::Return if self.pending.is_empty() => 'j',
+            Key::Backspace if self.pending.is_empty() => 'h',
+            _ => return Action::Handled,
+        };
+        self.pending.push(ch);
+        let last_line = content.line_count() - 1;
+        let visual = matches!(self.mode, Mode::Visual { .. });
+        match parse(&self.pending, last_line, visual) {
+            Parse::Incomplete => Action::Handled,
+            Parse::Invalid => {
+                self.pending.clear();
+                Action::Handled
+            }
+            Parse::Complete {
+                register,
+                count,
+                command,
+            } => {
+                let keys = std::mem::take(&mut self.pending);
+                if key == Key::Return {
+                    self.run(register, count, Command::Move(Motion::NextLine), content);
+                } else {
+                    self.change = Some(keys.iter().map(|ch| Key::Char(*ch)).collect());
+                    self.run(register, count, command, content);
+                }
+                Action::Handled
+            }
+        }
+    }
+
+    /// Runs a complete normal mode command, or applies an operator to the
+    /// selection in the visual mode.
+    fn run(
+        &mut self,
+        register: Option<char>,
+        count: Option<usize>,
+        command: Command,
+        content: &mut TextContent,
+    ) {
+        let n = count.unwrap_or(1).min(MAX_COUNT);
+        if let Mode::Visual {
+            anchor,
+            cursor,
+            lines,
+        } = self.mode
+        {
+            let (start, end) = (anchor.min(cursor), anchor.max(cursor));
+            let region = if lines {
+                content.motion_region(start, Motion::Line(end.0), 1)
+            } else {
+                Region {
+                    start,
+                    end: content.motion_target(end, Motion::Right, 1),
+                    lines: false,
+                }
+            };
+            let operator = match command {
+                Command::Operate(operator, _) | Command::OperateLines(operator) => Some(operator),
+                Command::DeleteChar => Some(Operator::Delete),
+                _ => None,
+            };
+            // Changes made in the visual mode are not repeated by `.`.
+            self.change = None;
+            let command = match (operator, command) {
+                (Some(operator), _) => {
+                    self.mode = Mode::Normal;
+                    self.operate(operator, region, register, content);
+                    return;
+                }
+                (None, Command::Operate(..)) => return,
+                (None, command) => command,
+            };
+            match command {
+                Command::Move(motion) => {
+                    let cursor = content.motion_target(cursor, motion, n);
+                    self.mode = Mode::Visual {
+                        anchor,
+                        cursor,
+                        lines,
+                    };
+                }
+                Command::Visual { lines: to_lines } if to_lines != lines => {
+                    self.mode = Mode::Visual {
+                        anchor,
+                        cursor,
+                        lines: to_lines,
+                    };
+                }
+                Command::Visual { .. } => {
+                    self.mode = Mode::Normal;
+                    content.set_cursor(cursor);
+                }
+                _ => {}
+            }
+            return;
+        }
+        let cursor = content.get_cursor();
+        let mut changed = true;
+        match command {
+            Command::Move(motion) => {
+                content.set_cursor(content.motion_target(cursor, motion, n));
+                changed = false;
+            }
+            Command::Operate(Operator::Change, Motion::WordStart)
+                if content
+                    .char_at(cursor)
+                    .is_some_and(|ch| !ch.is_whitespace()) =>
+            {
+                let region = content.change_word_region(cursor, n);
+                self.operate(Operator::Change, region, register, content);
+            }
+            Command::Operate(operator, motion) => {
+                let region = content.motion_region(cursor, motion, n);
+                changed = operator != Operator::Yank;
+                self.operate(operator, region, register, content);
+            }
+            Command::OperateLines(operator) => {
+                let region = content.motion_region(cursor, Motion::Down, n - 1);
+                changed = operator != Operator::Yank;
+                self.operate(operator, region, register, content);
+            }
+            Command::Insert(ch) => {
+                match ch {
+                    'a' => content.set_cursor(content.motion_target(cursor, Motion::Right, 1)),
+                    'I' => content.set_cursor(content.first_non_blank(cursor.0)),
+                    'A' => content.snap_cursor_end_of_line(),
+                    'o' => content.open_line(false),
+                    'O' => content.open_line(true),
+                    _ => {}
+                }
+                self.mode = Mode::Insert;
+            }
+            Command::DeleteChar => {
+                let region = content.motion_region(cursor, Motion::Right, n);
+                self.operate(Operator::Delete, region, register, content);
+            }
+            Command::Put { before } => {
+                let register = self.registers.get(&register.unwrap_or(UNNAMED_REGISTER));
+                if let Some(Register { text, lines }) = register {
+                    content.put(text, *lines, before, n);
+                }
+            }
+            Command::Join => {
+                for _ in 0..n.max(2) - 1 {
+                    content.join_lines();
+                }
+            }
+            Command::Repeat => {
+                changed = false;
+                self.repeat(n, content);
+            }
+            Command::Visual { lines } => {
+                changed = false;
+                self.mode = Mode::Visual {
+                    anchor: cursor,
+                    cursor,
+                    lines,
+                };
+            }
+            Command::OpenCommandLine => {
+                changed = false;
+                self.mode = Mode::Command(String::new());
+            }
+        }
+        if changed && self.mode == Mode::Normal {
+            if let Some(change) = self.change.take() {
+                self.last_change = change;
+            }
+        }
+    }
+
+    /// Applies `operator` to `region`, keeping its text in the unnamed
+    /// register and in `register` if given.
+    fn operate(
+        &mut self,
+        operator: Operator,
+        region: Region,
+        register: Option<char>,
+        content: &mut TextContent,
+    ) {
+        let text = match operator {
+            Operator::Delete => content.delete_region(region),
+            Operator::Change => {
+                self.mode = Mode::Insert;
+                content.change_region(region)
+            }
+            Operator::Yank => {
+                content.set_cursor(region.start);
+                content.region_text(region)
+            }
+        };
+        if text.is_empty() && !region.lines {
+            return;
+        }
+        let value = Register {
+            text,
+            lines: region.lines,
+        };
+        if let Some(register) = register.filter(|r| *r != UNNAMED_REGISTER) {
+            self.registers.insert(register, value.clone());
+        }
+        self.registers.insert(UNNAMED_REGISTER, value);
+    }
+
+    /// Makes the last change again `count` times.
+    fn repeat(&mut self, count: usize, content: &mut TextContent) {
+        let keys = self.last_change.clone();
+        self.repeating = true;
+        for _ in 0..count.min(MAX_COUNT) {
+            for key in &keys {
+                self.key(*key, content);
+            }
+            // A change ending in the insert mode is left with Escape.
+            if self.is_inserting() {
+                self.key(Key::Escape, content);
+            }
+        }
+        self.repeating = false;
+        self.last_change = keys;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn content(text: &str, cursor: (usize, usize)) -> TextContent {
+        let mut content = TextContent::new();
+        content.paste(text.to_string());
+        content.set_cursor(cursor);
+        content
+    }
+
+    fn type_keys(vim: &mut Vim, content: &mut TextContent, keys: &str) {
+        for ch in keys.chars() {
+            let key = match ch {
+                '\x1b' => Key::Escape,
+                '\n' => Key::Return,
+                ch => Key::Char(ch),
+            };
+            // Typed text is inserted by the editor itself.
+            if vim.key(key, content) == Action::Pass {
+                content.append(ch.to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn parse_commands() {
+        let complete = |register, count, command| Parse::Complete {
+            register,
+            count,
+            command,
+        };
+        assert_eq!(
+            parse(&['3', 'd', '2', 'w'], 9, false),
+            complete(
+                None,
+                Some(6),
+                Command::Operate(Operator::Delete, Motion::WordStart)
+            )
+        );
+        assert_eq!(
+            parse(&['"', 'a', 'y', 'y'], 9, false),
+            complete(Some('a'), None, Command::OperateLines(Operator::Yank))
+        );
+        assert_eq!(
+            parse(&['1', '0', 'G'], 9, false),
+            complete(None, None, Command::Move(Motion::Line(9)))
+        );
+        assert_eq!(
+            parse(&['G'], 9, false),
+            complete(None, None, Command::Move(Motion::Line(9)))
+        );
+        assert_eq!(
+            parse(&['0'], 9, false),
+            complete(None, None, Command::Move(Motion::LineStart))
+        );
+        assert_eq!(parse(&['d', 'g'], 9, false), Parse::Incomplete);
+        assert_eq!(parse(&['2', 'd'], 9, false), Parse::Incomplete);
+        assert_eq!(parse(&['d', 'x'], 9, false), Parse::Invalid);
+        let huge: Vec<char> = "99999999999999999999d99999999999w".chars().collect();
+        assert_eq!(
+            parse(&huge, 9, false),
+            complete(
+                None,
+                Some(usize::MAX),
+                Command::Operate(Operator::Delete, Motion::WordStart)
+            )
+        );
+    }
+
+    #[test]
+    fn huge_counts() {
+        let mut vim = Vim::new();
+        let mut c = content(
+            "one two
+three",
+            (0, 0),
+        );
+        type_keys(&mut vim, &mut c, "99999999999999999999j");
+        assert_eq!(c.get_cursor(), (1, 0));
+        type_keys(&mut vim, &mut c, "gg99999999999999999999$");
+        assert_eq!(c.get_cursor(), (1, 4));
+        type_keys(&mut vim, &mut c, "gg99999999999999999999w");
+        assert_eq!(c.get_cursor(), (1, 4));
+        type_keys(&mut vim, &mut c, "ggx99999999999999999999.");
+        assert_eq!(c.get_text(), vec!["", "three"]);
+    }
+
+    #[test]
+    fn operators_and_motions() {
+        let mut vim = Vim::new();
+        let mut c = content("let a = b;\nfoo(bar)\nlast", (0, 4));
+        type_keys(&mut vim, &mut c, "dw");
+        assert_eq!(c.get_text()[0], "let = b;");
+        type_keys(&mut vim, &mut c, "$");
+        assert_eq!(c.get_cursor(), (0, 7));
+        type_keys(&mut vim, &mut c, "0cwvar\x1b");
+        assert_eq!(c.get_text()[0], "var = b;");
+        assert_eq!(c.get_cursor(), (0, 2));
+        type_keys(&mut vim, &mut c, "jyyGp");
+        assert_eq!(
+            c.get_text(),
+            vec!["var = b;", "foo(bar)", "last", "foo(bar)"]
+        );
+        assert_eq!(c.get_cursor(), (3, 0));
+        type_keys(&mut vim, &mut c, "gg\"adde\"ap");
+        assert_eq!(
+            c.get_text(),
+            vec!["foo(bar)", "var = b;", "last", "foo(bar)"]
+        );
+        type_keys(&mut vim, &mut c, "j2dd");
+        assert_eq!(c.get_text(), vec!["foo(bar)", "var = b;"]);
+        assert_eq!(c.get_cursor(), (1, 0));
+    }
+
+    #[test]
+    fn repeat_and_visual() {
+        let mut vim = Vim::new();
+        let mut c = content("one two three four", (0, 0));
+        type_keys(&mut vim, &mut c, "cwX\x1bw.");
+        assert_eq!(c.get_text()[0], "X X three four");
+        type_keys(&mut vim, &mut c, "w2.");
+        assert_eq!(c.get_text()[0], "X X X four");
+        type_keys(&mut vim, &mut c, "0vy");
+        assert!(!vim.is_inserting() && vim.mode == Mode::Normal);
+        type_keys(&mut vim, &mut c, "$p");
+        assert_eq!(c.get_text()[0], "X X X fourX");
+        let mut c = content("a\nb\nc", (0, 0));
+        type_keys(&mut vim, &mut c, "Vjd");
+        assert_eq!(c.get_text(), vec!["c"]);
+        type_keys(&mut vim, &mut c, ":1\n");
+        assert_eq!(vim.key(Key::Char(':'), &mut c), Action::Handled);
+        type_keys(&mut vim, &mut c, "wq");
+        assert_eq!(vim.status(), ":wq");
+        assert_eq!(vim.key(Key::Return, &mut c), Action::WriteQuit);
+    }
+}
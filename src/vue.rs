@@ -2,19 +2,30 @@ use std::{collections::HashMap, ffi::NulError, path::Path};
 
 use sdl2::{
     pixels::{Color, PixelFormatEnum},
-    rect::Rect,
+    rect::{Point, Rect},
     render::{Canvas, TextureValueError},
     ttf::{Font, FontError, Sdl2TtfContext},
     video::Window,
 };
 
-use self::{info_bar::InfoBar, text_area_container::TextAreaContainer};
+use crate::{
+    git::Status,
+    gutter::{Marker, MarkerKind},
+};
+
+use self::{completion::Completion, info_bar::InfoBar, split_view::SplitView};
+pub(crate) use line_numbers::LineNumberMode;
+pub(crate) use split_view::Orientation;
 
+mod completion;
 mod info_bar;
 mod line_numbers;
+mod minimap;
 mod scroll_bar;
+mod split_view;
 mod text_area;
 mod text_area_container;
+mod tooltip;
 
 type RepositionFun = fn((u32, u32), (i32, i32)) -> (i32, i32);
 type ResizeFun = fn((u32, u32)) -> (u32, u32);
@@ -25,6 +36,17 @@ const BACKGROUND_COLOR: Color = Color::RGB(31, 31, 31);
 const TEXT_COLOR: Color = Color::RGB(204, 204, 204);
 const GREY_TEXT_COLOR: Color = Color::RGB(110, 118, 129);
 const SCROLL_BAR_COLOR: Color = Color::RGBA(155, 155, 155, 100);
+const SELECTION_COLOR: Color = Color::RGBA(38, 79, 120, 255);
+const BLOCK_CURSOR_COLOR: Color = Color::RGBA(204, 204, 204, 110);
+const BRACKET_COLOR: Color = Color::RGB(136, 136, 136);
+const MINIMAP_TEXT_COLOR: Color = Color::RGBA(204, 204, 204, 90);
+const MINIMAP_VIEWPORT_COLOR: Color = Color::RGBA(121, 121, 121, 50);
+const ERROR_COLOR: Color = Color::RGB(241, 76, 76);
+const WARNING_COLOR: Color = Color::RGB(204, 167, 0);
+const BOOKMARK_COLOR: Color = Color::RGB(55, 148, 255);
+const ADDED_COLOR: Color = Color::RGB(46, 160, 67);
+const MODIFIED_COLOR: Color = Color::RGB(0, 120, 212);
+const REMOVED_COLOR: Color = Color::RGB(248, 81, 73);
 const TEXT_FONT: &str = "__TEXT_FONT__";
 const UI_FONT: &str = "__UI_FONT__";
 
@@ -65,6 +87,34 @@ impl From<String> for VueError {
     }
 }
 
+/// What the editor needs to show of the current file.
+pub(crate) struct ContentSnapshot {
+    pub lines: Vec<String>,
+    pub size: (usize, usize),
+    /// The primary cursor comes first.
+    pub cursors: Vec<(usize, usize)>,
+    /// Selected part of each line as `(line, start, end)`.
+    pub selections: Vec<(usize, usize, usize)>,
+    pub tab_width: usize,
+    /// Brackets to highlight, as the positions of their characters.
+    pub brackets: Vec<(usize, usize)>,
+    /// Foldable regions as `(first, last, folded)` lines.
+    pub folds: Vec<(usize, usize, bool)>,
+    /// Gutter markers, sorted by line.
+    pub markers: Vec<Marker>,
+    pub git_status: Option<Status>,
+    /// The commit that last changed the cursor line.
+    pub blame: Option<String>,
+    /// Parts of lines to underline as `(line, start, end, kind)`.
+    pub underlines: Vec<(usize, usize, usize, MarkerKind)>,
+    /// Message about the cursor line, shown in the info bar.
+    pub message: Option<String>,
+    /// The mode of the vim keys, or the emacs command being typed.
+    pub mode: Option<String>,
+    /// Whether the cursors cover a character instead of standing before it.
+    pub block_cursor: bool,
+}
+
 trait VueComponent {
     fn on_resize(&mut self, container_size: (u32, u32), container_position: (i32, i32)) {
         self.set_size(self.get_resize_fun()(container_size));
@@ -116,8 +166,13 @@ impl<'a> Fonts<'a> {
 pub(crate) struct Vue<'a> {
     canvas: Canvas<Window>,
     fonts: Fonts<'a>,
-    text_area_container: TextAreaContainer,
+    split_view: SplitView,
     info_bar: InfoBar,
+    completion: Completion,
+    /// Position of the mouse, when it may be over something with a tooltip.
+    hovered: Option<(i32, i32)>,
+    /// Text shown under the cursor, until the next input.
+    info: Option<String>,
 }
 
 impl<'a> Vue<'a> {
@@ -135,13 +190,17 @@ impl<'a> Vue<'a> {
             );
             f
         };
-        let text_area_container = TextAreaContainer::new(|(w, h)| (w, h - 30), |_, pos| pos);
+        let split_view = SplitView::new(|(w, h)| (w, h - 30), |_, pos| pos);
         let info_bar = InfoBar::new(|(w, _)| (w, 30), |(_, h), _| (0, (h - 30) as i32));
+        let completion = Completion::new(|size| size, |_, pos| pos);
         let mut v = Vue {
             canvas,
             fonts,
-            text_area_container,
+            split_view,
             info_bar,
+            completion,
+            hovered: None,
+            info: None,
         };
         v.resize();
         v
@@ -151,24 +210,88 @@ impl<'a> Vue<'a> {
         self.canvas.set_draw_color(BACKGROUND_COLOR);
         self.canvas.clear();
         let (w, h) = self.canvas.window().size();
-        self.text_area_container.on_resize((w, h), (0, 0));
+        self.split_view.on_resize((w, h), (0, 0));
         self.info_bar.on_resize((w, h), (0, 0));
+        self.completion.on_resize((w, h), (0, 0));
     }
 
     pub fn cursor_index(&self, x: i32, y: i32) -> Option<(usize, usize)> {
-        self.text_area_container.cursor_index(x, y)
+        self.split_view.focused().cursor_index(x, y)
     }
 
     pub fn send_cursor_update(&mut self) {
-        self.text_area_container.send_cursor_update()
+        self.split_view.focused_mut().send_cursor_update()
+    }
+
+    pub fn is_wrapping(&self) -> bool {
+        self.split_view.focused().is_wrapping()
+    }
+
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.split_view.focused_mut().set_wrap(wrap)
+    }
+
+    pub fn shows_blame(&self) -> bool {
+        self.split_view.focused().shows_blame()
+    }
+
+    /// Returns whether each pane shows the blame, numbered like the views.
+    pub fn panes_showing_blame(&self) -> Vec<bool> {
+        self.split_view.shows_blame()
+    }
+
+    pub fn show_blame(&mut self, inline_blame: bool) {
+        self.split_view.focused_mut().show_blame(inline_blame)
+    }
+
+    pub fn line_number_mode(&self) -> LineNumberMode {
+        self.split_view.focused().line_number_mode()
+    }
+
+    pub fn set_line_number_mode(&mut self, mode: LineNumberMode) {
+        self.split_view.focused_mut().set_line_number_mode(mode)
+    }
+
+    pub fn shows_line_numbers(&self) -> bool {
+        self.split_view.focused().shows_line_numbers()
+    }
+
+    pub fn show_line_numbers(&mut self, visible: bool) {
+        self.split_view.focused_mut().show_line_numbers(visible)
+    }
+
+    /// Returns the cursor `n` visual rows below `cursor`.
+    pub fn move_by_rows(&self, cursor: (usize, usize), n: isize) -> (usize, usize) {
+        self.split_view.focused().move_by_rows(cursor, n)
+    }
+
+    /// Returns the first line of the region whose fold marker is at the
+    /// given position.
+    pub fn click_fold_marker(&self, x: i32, y: i32) -> Option<usize> {
+        self.split_view.focused().click_fold_marker(x, y)
     }
 
     pub fn scroll_text_area(&mut self, x: f32, y: f32) {
-        self.text_area_container.scroll(x, y)
+        self.split_view.focused_mut().scroll(x, y)
+    }
+
+    /// Returns the scroll offset of each pane, numbered like the views.
+    pub fn text_area_scroll_offsets(&self) -> Vec<(f32, f32)> {
+        self.split_view.scroll_offsets()
+    }
+
+    pub fn set_text_area_scroll_offset(&mut self, offset: (f32, f32)) {
+        self.split_view.focused_mut().set_scroll_offset(offset)
+    }
+
+    /// Returns the position and the size of the window.
+    pub fn window_geometry(&self) -> ((i32, i32), (u32, u32)) {
+        let window = self.canvas.window();
+        (window.position(), window.size())
     }
 
     pub fn click_text_area_scroll_bar(&mut self, x: i32, y: i32) -> bool {
-        self.text_area_container.click_scroll_bar(x, y)
+        self.split_view.click_scroll_bar(x, y)
     }
 
     pub fn hold_text_area_scroll_bar(
@@ -179,23 +302,84 @@ impl<'a> Vue<'a> {
         xrel: i32,
         yrel: i32,
     ) -> bool {
-        self.text_area_container
-            .hold_scroll_bar(origin, x, y, xrel, yrel)
+        self.split_view.hold_scroll_bar(origin, x, y, xrel, yrel)
     }
 
-    pub fn refresh(
-        &mut self,
-        content: Vec<String>,
-        content_size: (usize, usize),
-        cursor: (usize, usize),
-    ) {
+    pub fn focused_pane(&self) -> usize {
+        self.split_view.focused_pane()
+    }
+
+    /// Gives the focus to the pane at the given position and returns it.
+    pub fn focus_pane_at(&mut self, x: i32, y: i32) -> Option<usize> {
+        self.split_view.focus_at(x, y)
+    }
+
+    pub fn focus_next_pane(&mut self, n: isize) -> usize {
+        self.split_view.focus_next(n)
+    }
+
+    /// Splits the focused pane, and returns the new pane which gets the
+    /// focus.
+    pub fn split(&mut self, orientation: Orientation) -> usize {
+        self.split_view.split(orientation)
+    }
+
+    /// Closes the focused pane unless it is the last one, and returns it.
+    pub fn close_pane(&mut self) -> Option<usize> {
+        self.split_view.close()
+    }
+
+    /// Tells where the mouse is, to show the tooltip under it.
+    pub fn hover(&mut self, position: Option<(i32, i32)>) {
+        self.hovered = position;
+    }
+
+    /// Shows `info` under the cursor, or hides it.
+    pub fn show_info(&mut self, info: Option<String>) {
+        self.info = info;
+    }
+
+    /// Shows the list of completions under the cursor, hidden when `items`
+    /// is empty.
+    pub fn show_completion(&mut self, items: Vec<String>) {
+        self.completion.show(items);
+    }
+
+    pub fn hide_completion(&mut self) {
+        self.completion.hide()
+    }
+
+    pub fn is_completing(&self) -> bool {
+        self.completion.is_visible()
+    }
+
+    pub fn select_next_completion(&mut self, n: isize) {
+        self.completion.select_next(n)
+    }
+
+    pub fn selected_completion(&self) -> Option<&str> {
+        self.completion.selected()
+    }
+
+    pub fn click_divider(&mut self, x: i32, y: i32) -> bool {
+        self.split_view.click_divider(x, y)
+    }
+
+    pub fn hold_divider(&mut self, x: i32, y: i32) -> bool {
+        self.split_view.hold_divider(x, y)
+    }
+
+    pub fn release_divider(&mut self) {
+        self.split_view.release_divider()
+    }
+
+    /// Draws each pane with the snapshot of the same index.
+    pub fn refresh(&mut self, mut snapshots: Vec<ContentSnapshot>) {
         self.canvas.set_draw_color(BACKGROUND_COLOR);
         self.canvas.clear();
-        self.text_area_container
+        self.split_view
             .refresh(
-                content,
-                content_size,
-                cursor,
+                &mut snapshots,
                 &mut self.canvas,
                 self.fonts.get(TEXT_FONT).unwrap(),
                 self.fonts.get(UI_FONT).unwrap(),
@@ -203,8 +387,37 @@ impl<'a> Vue<'a> {
             .unwrap_or_else(|e| {
                 eprintln!("Error: {:?}", e);
             });
+        let snapshot = &snapshots[self.split_view.focused_pane()];
         self.info_bar
-            .refresh(cursor, &mut self.canvas, self.fonts.get(UI_FONT).unwrap())
+            .refresh(snapshot, &mut self.canvas, self.fonts.get(UI_FONT).unwrap())
+            .unwrap_or_else(|e| eprintln!("Error: {:?}", e));
+        if let Some((x, y)) = self.hovered {
+            if let Some(text) = self.split_view.tooltip_at(x, y) {
+                let (w, h) = self.canvas.window().size();
+                tooltip::draw_tooltip(
+                    text,
+                    Point::new(x, y),
+                    Rect::new(0, 0, w, h),
+                    &mut self.canvas,
+                    self.fonts.get(UI_FONT).unwrap(),
+                )
+                .unwrap_or_else(|e| eprintln!("Error: {:?}", e));
+            }
+        }
+        let (w, h) = self.canvas.window().size();
+        let (x, y) = self.split_view.focused().cursor_anchor();
+        if let Some(info) = &self.info {
+            tooltip::draw_tooltip(
+                info,
+                Point::new(x, y),
+                Rect::new(0, 0, w, h),
+                &mut self.canvas,
+                self.fonts.get(UI_FONT).unwrap(),
+            )
+            .unwrap_or_else(|e| eprintln!("Error: {:?}", e));
+        }
+        self.completion
+            .refresh((x, y), &mut self.canvas, self.fonts.get(TEXT_FONT).unwrap())
             .unwrap_or_else(|e| eprintln!("Error: {:?}", e));
         self.canvas.present();
     }
@@ -0,0 +1,143 @@
+use sdl2::{rect::Rect, render::Canvas, ttf::Font, video::Window};
+
+use super::{
+    str_rect, RepositionFun, ResizeFun, VueComponent, VueError, BAR_COLOR, OUTLINE_COLOR,
+    SELECTION_COLOR, TEXT_COLOR,
+};
+
+const PADDING: u32 = 4;
+/// Most items shown at once, the list scrolls to keep the selected one.
+const MAX_SHOWN_ITEMS: usize = 10;
+
+/// A list of texts to complete the word before the cursor with, shown under
+/// the cursor.
+pub(crate) struct Completion {
+    /// Where the list may be drawn.
+    area: Rect,
+    pub resize_fun: ResizeFun,
+    pub reposition_fun: RepositionFun,
+    items: Vec<String>,
+    selected: usize,
+    /// Index of the first item shown.
+    first: usize,
+}
+
+impl Completion {
+    pub fn new(resize_fun: ResizeFun, reposition_fun: RepositionFun) -> Self {
+        Completion {
+            area: Rect::new(0, 0, 0, 0),
+            resize_fun,
+            reposition_fun,
+            items: Vec::new(),
+            selected: 0,
+            first: 0,
+        }
+    }
+
+    /// Shows `items`, hides the list when there are none.
+    pub fn show(&mut self, items: Vec<String>) {
+        self.items = items;
+        self.selected = 0;
+        self.first = 0;
+    }
+
+    pub fn hide(&mut self) {
+        self.items.clear();
+    }
+
+    pub fn is_visible(&self) -> bool {
+        !self.items.is_empty()
+    }
+
+    /// Selects the item `n` places after the selected one, wrapping around.
+    pub fn select_next(&mut self, n: isize) {
+        if self.items.is_empty() {
+            return;
+        }
+        let count = self.items.len() as isize;
+        self.selected = (self.selected as isize + n).rem_euclid(count) as usize;
+        if self.selected < self.first {
+            self.first = self.selected;
+        } else if self.selected >= self.first + MAX_SHOWN_ITEMS {
+            self.first = self.selected + 1 - MAX_SHOWN_ITEMS;
+        }
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        self.items.get(self.selected).map(|item| item.as_str())
+    }
+
+    /// Draws the list with its top left corner at `anchor`, or above it when
+    /// there is no room.
+    pub fn refresh(
+        &self,
+        anchor: (i32, i32),
+        canvas: &mut Canvas<Window>,
+        font: &Font,
+    ) -> Result<(), VueError> {
+        if self.items.is_empty() {
+            return Ok(());
+        }
+        let shown = &self.items[self.first..(self.first + MAX_SHOWN_ITEMS).min(self.items.len())];
+        let line_height = font.height() as u32;
+        let mut width = 0;
+        for item in shown.iter().filter(|item| !item.is_empty()) {
+            width = width.max(str_rect(font, item)?.width());
+        }
+        let list = {
+            let (w, h) = (
+                width + 2 * PADDING,
+                shown.len() as u32 * line_height + 2 * PADDING,
+            );
+            let (x, mut y) = anchor;
+            if y + h as i32 > self.area.bottom() {
+                // Above the cursor line when there is no room under it.
+                y -= h as i32 + line_height as i32;
+            }
+            let x = x.min(self.area.right() - w as i32).max(self.area.x());
+            Rect::new(x, y.max(self.area.y()), w, h)
+        };
+        canvas.set_clip_rect(self.area);
+        canvas.set_draw_color(BAR_COLOR);
+        canvas.fill_rect(list)?;
+        canvas.set_draw_color(OUTLINE_COLOR);
+        canvas.draw_rect(list)?;
+        let creator = canvas.texture_creator();
+        for (i, item) in shown.iter().enumerate() {
+            let y = list.y() + (PADDING + i as u32 * line_height) as i32;
+            if self.first + i == self.selected {
+                canvas.set_draw_color(SELECTION_COLOR);
+                canvas.fill_rect(Rect::new(list.x() + 1, y, list.width() - 2, line_height))?;
+            }
+            if item.is_empty() {
+                continue;
+            }
+            let surface = font.render(item).blended(TEXT_COLOR)?;
+            let texture = surface.as_texture(&creator)?;
+            let mut rect = str_rect(font, item)?;
+            rect.offset(list.x() + PADDING as i32, y);
+            canvas.copy(&texture, None, rect)?;
+        }
+        Ok(())
+    }
+}
+
+impl VueComponent for Completion {
+    fn set_position(&mut self, pos: (i32, i32)) {
+        let (x, y) = pos;
+        self.area = Rect::new(x, y, self.area.width(), self.area.height());
+    }
+
+    fn set_size(&mut self, size: (u32, u32)) {
+        let (w, h) = size;
+        self.area = Rect::new(self.area.x(), self.area.y(), w, h);
+    }
+
+    fn get_reposition_fun(&self) -> RepositionFun {
+        self.reposition_fun
+    }
+
+    fn get_resize_fun(&self) -> ResizeFun {
+        self.resize_fun
+    }
+}
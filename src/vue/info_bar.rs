@@ -1,8 +1,8 @@
 use sdl2::{rect::Rect, render::Canvas, ttf::Font, video::Window};
 
 use super::{
-    char_size, str_rect, RepositionFun, ResizeFun, VueComponent, VueError, BAR_COLOR,
-    OUTLINE_COLOR, TEXT_COLOR,
+    char_size, str_rect, ContentSnapshot, RepositionFun, ResizeFun, VueComponent, VueError,
+    BAR_COLOR, OUTLINE_COLOR, TEXT_COLOR,
 };
 
 pub(crate) struct InfoBar {
@@ -20,9 +20,26 @@ impl InfoBar {
         }
     }
 
+    /// Draws `label` from `left`, and returns where the next one goes.
+    fn draw_label(
+        &self,
+        label: &str,
+        left: i32,
+        canvas: &mut Canvas<Window>,
+        font: &Font,
+    ) -> Result<i32, VueError> {
+        let creator = canvas.texture_creator();
+        let surface = font.render(label).blended(TEXT_COLOR)?;
+        let texture = surface.as_texture(&creator)?;
+        let mut rect = str_rect(font, label)?.centered_on(self.area.center());
+        rect.set_x(left);
+        canvas.copy(&texture, None, rect)?;
+        Ok(rect.right() + 4 * char_size(font).0 as i32)
+    }
+
     pub fn draw_bar(
         &self,
-        cursor: (usize, usize),
+        snapshot: &ContentSnapshot,
         canvas: &mut Canvas<Window>,
         font: &Font,
     ) -> Result<(), VueError> {
@@ -31,7 +48,7 @@ impl InfoBar {
         canvas.set_draw_color(OUTLINE_COLOR);
         canvas.draw_rect(self.area)?;
         let (l, c) = {
-            let (l, c) = cursor;
+            let (l, c) = snapshot.cursors[0];
             (l + 1, c + 1)
         };
         let cursor_str = format!("Ln {}, Col {}", l, c);
@@ -46,17 +63,44 @@ impl InfoBar {
             rect
         };
         canvas.copy(&texture, None, rect)?;
+        let mut left = self.area.x() + char_size(font).0 as i32;
+        if let Some(mode) = &snapshot.mode {
+            left = self.draw_label(mode, left, canvas, font)?;
+        }
+        if let Some(status) = &snapshot.git_status {
+            let dirty = if status.dirty { "*" } else { "" };
+            let branch_str = format!("{}{}", status.branch, dirty);
+            left = self.draw_label(&branch_str, left, canvas, font)?;
+        }
+        if let Some(message) = (snapshot.message.as_deref())
+            .and_then(|m| m.lines().next())
+            .filter(|m| !m.is_empty())
+        {
+            let surface = font.render(message).blended(TEXT_COLOR)?;
+            let texture = surface.as_texture(&creator)?;
+            let mut message_rect = str_rect(font, message)?.centered_on(self.area.center());
+            message_rect.set_x(left);
+            // The cursor position stays readable over long messages.
+            canvas.set_clip_rect(Rect::new(
+                left,
+                self.area.y(),
+                (rect.x() - left).max(0) as u32,
+                self.area.height(),
+            ));
+            canvas.copy(&texture, None, message_rect)?;
+            canvas.set_clip_rect(self.area);
+        }
         Ok(())
     }
 
     pub fn refresh(
         &self,
-        cursor: (usize, usize),
+        snapshot: &ContentSnapshot,
         canvas: &mut Canvas<Window>,
         font: &Font,
     ) -> Result<(), VueError> {
         canvas.set_clip_rect(self.area);
-        self.draw_bar(cursor, canvas, font)?;
+        self.draw_bar(snapshot, canvas, font)?;
         Ok(())
     }
 }
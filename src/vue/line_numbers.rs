@@ -1,28 +1,242 @@
-use sdl2::{rect::Rect, render::Canvas, ttf::Font, video::Window};
+use sdl2::{
+    rect::{Point, Rect},
+    render::Canvas,
+    ttf::Font,
+    video::Window,
+};
+
+use crate::gutter::{Marker, MarkerKind};
 
 use super::{
-    str_rect_at_line, text_area_container::TOP_MARGIN, RepositionFun, ResizeFun, VueComponent,
-    VueError, BACKGROUND_COLOR, GREY_TEXT_COLOR, TEXT_COLOR,
+    char_size, str_rect_at_line, text_area_container::TOP_MARGIN, RepositionFun, ResizeFun,
+    VueComponent, VueError, ADDED_COLOR, BACKGROUND_COLOR, BOOKMARK_COLOR, ERROR_COLOR,
+    GREY_TEXT_COLOR, MODIFIED_COLOR, REMOVED_COLOR, TEXT_COLOR, WARNING_COLOR,
 };
 
+/// What the number of each line is relative to.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum LineNumberMode {
+    Absolute,
+    /// Distance to the line of the cursor, in visible lines.
+    Relative,
+    /// Relative, except for the line of the cursor.
+    Hybrid,
+}
+
 pub(crate) struct LineNumbers {
     area: Rect,
     pub resize_fun: ResizeFun,
     pub reposition_fun: RepositionFun,
+    mode: LineNumberMode,
+    visible: bool,
+    /// First line of each foldable region, with whether it is folded.
+    folds: Vec<(usize, bool)>,
+    /// Clickable area of each fold marker drawn, with its line.
+    fold_markers: Vec<(Rect, usize)>,
+    /// Markers of the lines, sorted by line.
+    line_markers: Vec<Marker>,
+    /// Area of the markers drawn for each line having messages, with them.
+    tooltips: Vec<(Rect, String)>,
 }
 
+const MARKER_SIZE: u32 = 6;
+/// Space on the right of the numbers, used by the fold markers.
+const PADDING: u32 = 10;
+/// Width of the column of line markers on the left of the numbers.
+const LINE_MARKER_COLUMN: u32 = 16;
+const CHANGE_BAR_WIDTH: u32 = 3;
+const ICON_SIZE: u32 = 8;
+
 impl LineNumbers {
     pub fn new(resize_fun: ResizeFun, reposition_fun: RepositionFun) -> Self {
         LineNumbers {
             area: Rect::new(0, 0, 0, 0),
             resize_fun,
             reposition_fun,
+            mode: LineNumberMode::Absolute,
+            visible: true,
+            folds: Vec::new(),
+            fold_markers: Vec::new(),
+            line_markers: Vec::new(),
+            tooltips: Vec::new(),
+        }
+    }
+
+    pub fn get_mode(&self) -> LineNumberMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: LineNumberMode) {
+        self.mode = mode;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    /// Returns the width needed to show the numbers of `line_count` lines,
+    /// zero when hidden.
+    pub fn width(&self, line_count: usize, font: &Font) -> u32 {
+        if !self.visible {
+            return 0;
+        }
+        let digits = line_count.to_string().len().max(2) as u32;
+        LINE_MARKER_COLUMN + digits * char_size(font).0 + PADDING
+    }
+
+    pub fn set_folds(&mut self, folds: &[(usize, usize, bool)]) {
+        self.folds = folds
+            .iter()
+            .map(|(first, _, folded)| (*first, *folded))
+            .collect();
+    }
+
+    pub fn set_markers(&mut self, markers: &[Marker]) {
+        self.line_markers = markers.to_vec();
+    }
+
+    /// Returns the messages of the line markers at the given position.
+    pub fn tooltip_at(&self, x: i32, y: i32) -> Option<&str> {
+        let point = Point::new(x, y);
+        if !self.area.contains_point(point) {
+            return None;
+        }
+        self.tooltips
+            .iter()
+            .find(|(rect, _)| rect.contains_point(point))
+            .map(|(_, message)| message.as_str())
+    }
+
+    /// Returns the line of the fold marker at the given position.
+    pub fn marker_at(&self, x: i32, y: i32) -> Option<usize> {
+        let point = Point::new(x, y);
+        if !self.area.contains_point(point) {
+            return None;
         }
+        self.fold_markers
+            .iter()
+            .find(|(rect, _)| rect.contains_point(point))
+            .map(|(_, l)| *l)
+    }
+
+    /// Draws a square on the right of the line number, filled when the
+    /// region is folded.
+    fn draw_marker(
+        &mut self,
+        line: usize,
+        row: Rect,
+        canvas: &mut Canvas<Window>,
+    ) -> Result<(), String> {
+        let Some((_, folded)) = self.folds.iter().find(|(first, _)| *first == line) else {
+            return Ok(());
+        };
+        let rect = {
+            let mut rect = Rect::new(0, 0, MARKER_SIZE, MARKER_SIZE);
+            rect.center_on(Point::new(
+                self.area.right() - PADDING as i32 / 2,
+                row.center().y(),
+            ));
+            rect
+        };
+        canvas.set_draw_color(GREY_TEXT_COLOR);
+        if *folded {
+            canvas.fill_rect(rect)?;
+        } else {
+            canvas.draw_rect(rect)?;
+        }
+        self.fold_markers.push((
+            Rect::new(
+                self.area.right() - PADDING as i32,
+                row.y(),
+                PADDING,
+                row.height(),
+            ),
+            line,
+        ));
+        Ok(())
+    }
+
+    /// Draws a bar on the left for the changes of the line, and an icon for
+    /// its most important other marker.
+    fn draw_line_markers(
+        &mut self,
+        line: usize,
+        row: Rect,
+        canvas: &mut Canvas<Window>,
+    ) -> Result<(), String> {
+        let start = self.line_markers.partition_point(|m| m.line < line);
+        let end = self.line_markers.partition_point(|m| m.line <= line);
+        if start == end {
+            return Ok(());
+        }
+        let x = self.area.x();
+        let mut icon = None;
+        for marker in self.line_markers[start..end].iter() {
+            match marker.kind {
+                MarkerKind::Added | MarkerKind::Modified => {
+                    let color = if marker.kind == MarkerKind::Added {
+                        ADDED_COLOR
+                    } else {
+                        MODIFIED_COLOR
+                    };
+                    canvas.set_draw_color(color);
+                    canvas.fill_rect(Rect::new(x, row.y(), CHANGE_BAR_WIDTH, row.height()))?;
+                }
+                MarkerKind::Removed => {
+                    canvas.set_draw_color(REMOVED_COLOR);
+                    canvas.fill_rect(Rect::new(
+                        x,
+                        row.y() - CHANGE_BAR_WIDTH as i32 / 2,
+                        2 * CHANGE_BAR_WIDTH,
+                        CHANGE_BAR_WIDTH,
+                    ))?;
+                }
+                kind => {
+                    let priority = |k| match k {
+                        MarkerKind::Error => 0,
+                        MarkerKind::Warning => 1,
+                        _ => 2,
+                    };
+                    if icon.is_none_or(|icon| priority(kind) < priority(icon)) {
+                        icon = Some(kind);
+                    }
+                }
+            }
+        }
+        if let Some(kind) = icon {
+            let rect = {
+                let mut rect = Rect::new(0, 0, ICON_SIZE, ICON_SIZE);
+                let center_x = x + (CHANGE_BAR_WIDTH + LINE_MARKER_COLUMN) as i32 / 2;
+                rect.center_on(Point::new(center_x, row.center().y()));
+                rect
+            };
+            canvas.set_draw_color(match kind {
+                MarkerKind::Error => ERROR_COLOR,
+                MarkerKind::Warning => WARNING_COLOR,
+                _ => BOOKMARK_COLOR,
+            });
+            canvas.fill_rect(rect)?;
+        }
+        let messages: Vec<&str> = self.line_markers[start..end]
+            .iter()
+            .filter_map(|m| m.message.as_deref())
+            .collect();
+        if !messages.is_empty() {
+            self.tooltips.push((
+                Rect::new(x, row.y(), LINE_MARKER_COLUMN, row.height()),
+                messages.join("\n"),
+            ));
+        }
+        Ok(())
     }
 
     fn draw(
         &mut self,
-        line_n: usize,
+        rows: &[Option<usize>],
         current_line: usize,
         scroll_offset: f32,
         canvas: &mut Canvas<Window>,
@@ -32,8 +246,21 @@ impl LineNumbers {
         let creator = canvas.texture_creator();
         canvas.set_draw_color(BACKGROUND_COLOR);
         canvas.fill_rect(self.area)?;
-        for n in 0..line_n {
-            let text = (n + 1).to_string();
+        self.fold_markers.clear();
+        self.tooltips.clear();
+        let current = rows.iter().flatten().position(|n| *n == current_line);
+        for (i, (r, n)) in rows
+            .iter()
+            .enumerate()
+            .filter_map(|(r, n)| n.map(|n| (r, n)))
+            .enumerate()
+        {
+            let number = match (self.mode, current) {
+                (LineNumberMode::Absolute, _) | (_, None) => n + 1,
+                (LineNumberMode::Hybrid, Some(current)) if current == i => n + 1,
+                (_, Some(current)) => current.abs_diff(i),
+            };
+            let text = number.to_string();
             let color = {
                 if n == current_line {
                     TEXT_COLOR
@@ -43,33 +270,41 @@ impl LineNumbers {
             };
             let surface = font.render(&text).blended(color)?;
             let texture = surface.as_texture(&creator)?;
+            let container = {
+                let mut rect = str_rect_at_line(content_font, &text, r)?;
+                rect.offset(0, self.area.y() + TOP_MARGIN as i32 + scroll_offset as i32);
+                rect
+            };
             let rect = {
-                let mut rect = str_rect_at_line(font, &text, n)?;
-                let container = str_rect_at_line(content_font, &text, n)?;
+                let mut rect = str_rect_at_line(font, &text, r)?;
                 rect.center_on(container.center());
-                rect.offset(
-                    self.area.right() - rect.w - 10,
-                    self.area.y() + TOP_MARGIN as i32 + scroll_offset as i32,
-                );
+                rect.set_x(self.area.right() - rect.w - PADDING as i32);
                 rect
             };
             canvas.copy(&texture, None, rect)?;
+            self.draw_marker(n, container, canvas)?;
+            self.draw_line_markers(n, container, canvas)?;
         }
         Ok(())
     }
 
     pub fn refresh(
         &mut self,
-        line_n: usize,
+        rows: &[Option<usize>],
         current_line: usize,
         scroll_offset: f32,
         canvas: &mut Canvas<Window>,
         font: &Font,
         content_font: &Font,
     ) -> Result<(), VueError> {
+        if !self.visible {
+            self.fold_markers.clear();
+            self.tooltips.clear();
+            return Ok(());
+        }
         canvas.set_clip_rect(self.area);
         self.draw(
-            line_n,
+            rows,
             current_line,
             scroll_offset,
             canvas,
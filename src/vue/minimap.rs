@@ -0,0 +1,168 @@
+use sdl2::{
+    rect::{Point, Rect},
+    render::Canvas,
+    video::Window,
+};
+
+use crate::text_zone::expand_tabs;
+
+use super::{
+    RepositionFun, ResizeFun, VueComponent, BACKGROUND_COLOR, MINIMAP_TEXT_COLOR,
+    MINIMAP_VIEWPORT_COLOR,
+};
+
+/// Height in pixels of a line when the whole text fits in the minimap.
+const LINE_HEIGHT: f32 = 2.0;
+
+/// Scaled-down view of the whole text, one pixel per character.
+pub(crate) struct Minimap {
+    area: Rect,
+    pub resize_fun: ResizeFun,
+    pub reposition_fun: RepositionFun,
+    /// The text and the size the rectangles were computed for.
+    lines: Vec<String>,
+    tab_width: usize,
+    size: (u32, u32),
+    /// Rectangles drawing the words, relative to the minimap.
+    rects: Vec<Rect>,
+    visible_lines: (usize, usize),
+}
+
+impl Minimap {
+    pub fn new(resize_fun: ResizeFun, reposition_fun: RepositionFun) -> Self {
+        Minimap {
+            area: Rect::new(0, 0, 0, 0),
+            resize_fun,
+            reposition_fun,
+            lines: Vec::new(),
+            tab_width: 0,
+            size: (0, 0),
+            rects: Vec::new(),
+            visible_lines: (0, 0),
+        }
+    }
+
+    /// Height of a line, smaller than `LINE_HEIGHT` when the text is too
+    /// long to fit.
+    fn line_height(&self) -> f32 {
+        let count = self.lines.len().max(1) as f32;
+        (self.area.height() as f32 / count).min(LINE_HEIGHT)
+    }
+
+    /// Computes the rectangles of the words, keeping a single line for each
+    /// row of pixels.
+    fn update(&mut self, lines: &[String], tab_width: usize) {
+        if self.lines == lines && self.tab_width == tab_width && self.size == self.area.size() {
+            return;
+        }
+        self.lines = lines.to_vec();
+        self.tab_width = tab_width;
+        self.size = self.area.size();
+        self.rects.clear();
+        let line_height = self.line_height();
+        let mut last_y = None;
+        for (l, line) in self.lines.iter().enumerate() {
+            let y = (l as f32 * line_height) as i32;
+            if last_y == Some(y) {
+                continue;
+            }
+            last_y = Some(y);
+            let mut start = None;
+            let chars: Vec<char> = expand_tabs(line, tab_width).chars().collect();
+            for (c, ch) in chars.iter().chain([' '].iter()).enumerate() {
+                match (start, ch.is_whitespace()) {
+                    (None, false) => start = Some(c),
+                    (Some(s), true) => {
+                        if s as u32 >= self.size.0 {
+                            break;
+                        }
+                        let w = (c - s) as u32;
+                        let h = line_height.max(1.0) as u32;
+                        self.rects.push(Rect::new(s as i32, y, w, h));
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn draw(&self, canvas: &mut Canvas<Window>) -> Result<(), String> {
+        canvas.set_draw_color(BACKGROUND_COLOR);
+        canvas.fill_rect(self.area)?;
+        let rects: Vec<Rect> = self
+            .rects
+            .iter()
+            .map(|r| r.right_shifted(self.area.x()).bottom_shifted(self.area.y()))
+            .collect();
+        canvas.set_draw_color(MINIMAP_TEXT_COLOR);
+        canvas.fill_rects(&rects)?;
+        let line_height = self.line_height();
+        let (first, last) = self.visible_lines;
+        let viewport = Rect::new(
+            self.area.x(),
+            self.area.y() + (first as f32 * line_height) as i32,
+            self.area.width(),
+            (((last + 1 - first) as f32 * line_height) as u32).max(1),
+        );
+        canvas.set_draw_color(MINIMAP_VIEWPORT_COLOR);
+        canvas.fill_rect(viewport)?;
+        Ok(())
+    }
+
+    pub fn refresh(
+        &mut self,
+        lines: &[String],
+        tab_width: usize,
+        visible_lines: (usize, usize),
+        canvas: &mut Canvas<Window>,
+    ) -> Result<(), String> {
+        self.update(lines, tab_width);
+        self.visible_lines = visible_lines;
+        canvas.set_clip_rect(self.area);
+        self.draw(canvas)
+    }
+
+    fn line_at(&self, y: i32) -> usize {
+        let line = ((y - self.area.y()) as f32 / self.line_height()) as usize;
+        line.min(self.lines.len().saturating_sub(1))
+    }
+
+    /// Returns the line clicked on.
+    pub fn click(&self, x: i32, y: i32) -> Option<usize> {
+        if self.area.contains_point(Point::new(x, y)) {
+            Some(self.line_at(y))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the line under the mouse when dragging from the minimap.
+    pub fn hold(&self, origin: (i32, i32), y: i32) -> Option<usize> {
+        if self.area.contains_point(origin) {
+            Some(self.line_at(y.max(self.area.y())))
+        } else {
+            None
+        }
+    }
+}
+
+impl VueComponent for Minimap {
+    fn set_position(&mut self, pos: (i32, i32)) {
+        let (x, y) = pos;
+        self.area = Rect::new(x, y, self.area.width(), self.area.height());
+    }
+
+    fn set_size(&mut self, size: (u32, u32)) {
+        let (w, h) = size;
+        self.area = Rect::new(self.area.x(), self.area.y(), w, h);
+    }
+
+    fn get_reposition_fun(&self) -> RepositionFun {
+        self.reposition_fun
+    }
+
+    fn get_resize_fun(&self) -> ResizeFun {
+        self.resize_fun
+    }
+}
@@ -0,0 +1,373 @@
+use sdl2::{
+    rect::{Point, Rect},
+    render::Canvas,
+    ttf::Font,
+    video::Window,
+};
+
+use super::{
+    text_area_container::TextAreaContainer, ContentSnapshot, RepositionFun, ResizeFun,
+    VueComponent, VueError, GREY_TEXT_COLOR, OUTLINE_COLOR,
+};
+
+const DIVIDER_WIDTH: u32 = 4;
+/// Smallest width or height a divider can be dragged to leave to a pane.
+const MIN_PANE_SIZE: u32 = 120;
+
+/// How a split lays out its two halves.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Orientation {
+    /// Side by side, with a vertical divider.
+    Horizontal,
+    /// One above the other, with a horizontal divider.
+    Vertical,
+}
+
+enum Node {
+    Pane(usize),
+    Split(Box<Split>),
+}
+
+struct Split {
+    orientation: Orientation,
+    /// Share of the area given to the first half.
+    ratio: f32,
+    first: Node,
+    second: Node,
+    area: Rect,
+    divider: Rect,
+    dragged: bool,
+}
+
+impl Split {
+    /// Returns the areas of the first half, the divider and the second half.
+    fn areas(&self, area: Rect) -> (Rect, Rect, Rect) {
+        let (x, y) = (area.x(), area.y());
+        let (w, h) = area.size();
+        match self.orientation {
+            Orientation::Horizontal => {
+                let w = w.saturating_sub(DIVIDER_WIDTH);
+                let first = (w as f32 * self.ratio) as u32;
+                (
+                    Rect::new(x, y, first, h),
+                    Rect::new(x + first as i32, y, DIVIDER_WIDTH, h),
+                    Rect::new(x + (first + DIVIDER_WIDTH) as i32, y, w - first, h),
+                )
+            }
+            Orientation::Vertical => {
+                let h = h.saturating_sub(DIVIDER_WIDTH);
+                let first = (h as f32 * self.ratio) as u32;
+                (
+                    Rect::new(x, y, w, first),
+                    Rect::new(x, y + first as i32, w, DIVIDER_WIDTH),
+                    Rect::new(x, y + (first + DIVIDER_WIDTH) as i32, w, h - first),
+                )
+            }
+        }
+    }
+
+    /// Moves the divider onto `point`, leaving each half at least
+    /// `MIN_PANE_SIZE` when the area allows it.
+    fn drag_to(&mut self, point: Point) {
+        let (start, length, position) = match self.orientation {
+            Orientation::Horizontal => (self.area.x(), self.area.width(), point.x()),
+            Orientation::Vertical => (self.area.y(), self.area.height(), point.y()),
+        };
+        let length = length.saturating_sub(DIVIDER_WIDTH).max(1) as f32;
+        let min = (MIN_PANE_SIZE as f32 / length).min(0.5);
+        self.ratio = ((position - start) as f32 / length).clamp(min, 1.0 - min);
+    }
+}
+
+impl Node {
+    fn layout(&mut self, area: Rect, panes: &mut [TextAreaContainer]) {
+        match self {
+            Node::Pane(i) => panes[*i].on_resize(area.size(), (area.x(), area.y())),
+            Node::Split(split) => {
+                let (first, divider, second) = split.areas(area);
+                split.area = area;
+                split.divider = divider;
+                split.first.layout(first, panes);
+                split.second.layout(second, panes);
+            }
+        }
+    }
+
+    fn first_pane(&self) -> usize {
+        match self {
+            Node::Pane(i) => *i,
+            Node::Split(split) => split.first.first_pane(),
+        }
+    }
+
+    /// Replaces `pane` by a split between it and `new_pane`.
+    fn split(&mut self, pane: usize, new_pane: usize, orientation: Orientation) -> bool {
+        match self {
+            Node::Pane(i) if *i == pane => {
+                *self = Node::Split(Box::new(Split {
+                    orientation,
+                    ratio: 0.5,
+                    first: Node::Pane(pane),
+                    second: Node::Pane(new_pane),
+                    area: Rect::new(0, 0, 0, 0),
+                    divider: Rect::new(0, 0, 0, 0),
+                    dragged: false,
+                }));
+                true
+            }
+            Node::Pane(_) => false,
+            Node::Split(split) => {
+                split.first.split(pane, new_pane, orientation)
+                    || split.second.split(pane, new_pane, orientation)
+            }
+        }
+    }
+
+    /// Removes `pane`, giving its place to the other half of its split.
+    /// Returns the first pane of that half.
+    fn remove(&mut self, pane: usize) -> Option<usize> {
+        let Node::Split(split) = self else {
+            return None;
+        };
+        let remaining = if matches!(split.first, Node::Pane(i) if i == pane) {
+            &mut split.second
+        } else if matches!(split.second, Node::Pane(i) if i == pane) {
+            &mut split.first
+        } else {
+            return split
+                .first
+                .remove(pane)
+                .or_else(|| split.second.remove(pane));
+        };
+        *self = std::mem::replace(remaining, Node::Pane(0));
+        Some(self.first_pane())
+    }
+
+    /// Renumbers the panes after the removal of pane `removed`.
+    fn renumber(&mut self, removed: usize) {
+        match self {
+            Node::Pane(i) if *i > removed => *i -= 1,
+            Node::Pane(_) => {}
+            Node::Split(split) => {
+                split.first.renumber(removed);
+                split.second.renumber(removed);
+            }
+        }
+    }
+
+    fn click_divider(&mut self, point: Point) -> bool {
+        match self {
+            Node::Pane(_) => false,
+            Node::Split(split) => {
+                split.dragged = split.divider.contains_point(point);
+                split.dragged
+                    || split.first.click_divider(point)
+                    || split.second.click_divider(point)
+            }
+        }
+    }
+
+    fn hold_divider(&mut self, point: Point) -> bool {
+        match self {
+            Node::Pane(_) => false,
+            Node::Split(split) if split.dragged => {
+                split.drag_to(point);
+                true
+            }
+            Node::Split(split) => {
+                split.first.hold_divider(point) || split.second.hold_divider(point)
+            }
+        }
+    }
+
+    fn release_divider(&mut self) {
+        if let Node::Split(split) = self {
+            split.dragged = false;
+            split.first.release_divider();
+            split.second.release_divider();
+        }
+    }
+
+    fn draw_dividers(&self, canvas: &mut Canvas<Window>) -> Result<(), String> {
+        if let Node::Split(split) = self {
+            canvas.set_draw_color(OUTLINE_COLOR);
+            canvas.fill_rect(split.divider)?;
+            split.first.draw_dividers(canvas)?;
+            split.second.draw_dividers(canvas)?;
+        }
+        Ok(())
+    }
+}
+
+/// Text area containers laid out in nested splits, one of them having the
+/// focus. Panes are numbered in the order they were created.
+pub(crate) struct SplitView {
+    area: Rect,
+    pub resize_fun: ResizeFun,
+    pub reposition_fun: RepositionFun,
+    root: Node,
+    panes: Vec<TextAreaContainer>,
+    focused: usize,
+}
+
+impl SplitView {
+    pub fn new(resize_fun: ResizeFun, reposition_fun: RepositionFun) -> Self {
+        SplitView {
+            area: Rect::new(0, 0, 0, 0),
+            resize_fun,
+            reposition_fun,
+            root: Node::Pane(0),
+            panes: vec![TextAreaContainer::new(|size| size, |_, pos| pos)],
+            focused: 0,
+        }
+    }
+
+    pub fn focused(&self) -> &TextAreaContainer {
+        &self.panes[self.focused]
+    }
+
+    pub fn focused_mut(&mut self) -> &mut TextAreaContainer {
+        &mut self.panes[self.focused]
+    }
+
+    /// Returns whether each pane shows the blame of the cursor line.
+    pub fn shows_blame(&self) -> Vec<bool> {
+        self.panes.iter().map(|p| p.shows_blame()).collect()
+    }
+
+    /// Returns the scroll offset of each pane.
+    pub fn scroll_offsets(&self) -> Vec<(f32, f32)> {
+        self.panes.iter().map(|p| p.scroll_offset()).collect()
+    }
+
+    pub fn focused_pane(&self) -> usize {
+        self.focused
+    }
+
+    /// Gives the focus to the pane at the given position and returns it.
+    pub fn focus_at(&mut self, x: i32, y: i32) -> Option<usize> {
+        let pane = self.panes.iter().position(|p| p.contains_point(x, y))?;
+        self.focused = pane;
+        Some(pane)
+    }
+
+    /// Gives the focus to the pane `n` places after the focused one, in the
+    /// order they were created.
+    pub fn focus_next(&mut self, n: isize) -> usize {
+        let count = self.panes.len() as isize;
+        self.focused = (self.focused as isize + n).rem_euclid(count) as usize;
+        self.focused
+    }
+
+    /// Splits the focused pane in two, the new half getting the focus, and
+    /// returns the new pane.
+    pub fn split(&mut self, orientation: Orientation) -> usize {
+        let mut pane = TextAreaContainer::new(|size| size, |_, pos| pos);
+        pane.set_wrap(self.focused().is_wrapping());
+        pane.show_blame(self.focused().shows_blame());
+        pane.set_line_number_mode(self.focused().line_number_mode());
+        pane.show_line_numbers(self.focused().shows_line_numbers());
+        self.panes.push(pane);
+        let new_pane = self.panes.len() - 1;
+        self.root.split(self.focused, new_pane, orientation);
+        self.focused = new_pane;
+        self.affect_children();
+        new_pane
+    }
+
+    /// Closes the focused pane, unless it is the last one, and returns it.
+    /// The focus goes to the pane taking its place.
+    pub fn close(&mut self) -> Option<usize> {
+        let closed = self.focused;
+        let next = self.root.remove(closed)?;
+        self.panes.remove(closed);
+        self.root.renumber(closed);
+        self.focused = if next > closed { next - 1 } else { next };
+        self.affect_children();
+        Some(closed)
+    }
+
+    pub fn click_divider(&mut self, x: i32, y: i32) -> bool {
+        self.root.click_divider(Point::new(x, y))
+    }
+
+    pub fn hold_divider(&mut self, x: i32, y: i32) -> bool {
+        let held = self.root.hold_divider(Point::new(x, y));
+        if held {
+            self.affect_children();
+        }
+        held
+    }
+
+    pub fn release_divider(&mut self) {
+        self.root.release_divider();
+    }
+
+    pub fn tooltip_at(&self, x: i32, y: i32) -> Option<&str> {
+        self.panes.iter().find_map(|p| p.tooltip_at(x, y))
+    }
+
+    pub fn click_scroll_bar(&mut self, x: i32, y: i32) -> bool {
+        self.panes
+            .iter_mut()
+            .any(|p| p.click_minimap(x, y) || p.click_scroll_bar(x, y))
+    }
+
+    pub fn hold_scroll_bar(
+        &mut self,
+        origin: (i32, i32),
+        x: i32,
+        y: i32,
+        xrel: i32,
+        yrel: i32,
+    ) -> bool {
+        self.panes
+            .iter_mut()
+            .any(|p| p.hold_minimap(origin, y) || p.hold_scroll_bar(origin, x, y, xrel, yrel))
+    }
+
+    /// Draws each pane with its snapshot, the panes being numbered like the
+    /// snapshots. The lines of the snapshots are taken by the panes.
+    pub fn refresh(
+        &mut self,
+        snapshots: &mut [ContentSnapshot],
+        canvas: &mut Canvas<Window>,
+        content_font: &Font,
+        line_number_font: &Font,
+    ) -> Result<(), VueError> {
+        for (pane, snapshot) in self.panes.iter_mut().zip(snapshots) {
+            pane.refresh(snapshot, canvas, content_font, line_number_font)?;
+        }
+        canvas.set_clip_rect(self.area);
+        self.root.draw_dividers(canvas)?;
+        if self.panes.len() > 1 {
+            canvas.set_draw_color(GREY_TEXT_COLOR);
+            canvas.draw_rect(self.focused().area())?;
+        }
+        Ok(())
+    }
+}
+
+impl VueComponent for SplitView {
+    fn set_position(&mut self, pos: (i32, i32)) {
+        let (x, y) = pos;
+        self.area = Rect::new(x, y, self.area.width(), self.area.height());
+    }
+
+    fn set_size(&mut self, size: (u32, u32)) {
+        let (w, h) = size;
+        self.area = Rect::new(self.area.x(), self.area.y(), w, h);
+    }
+
+    fn get_resize_fun(&self) -> ResizeFun {
+        self.resize_fun
+    }
+
+    fn get_reposition_fun(&self) -> RepositionFun {
+        self.reposition_fun
+    }
+
+    fn affect_children(&mut self) {
+        self.root.layout(self.area, &mut self.panes);
+    }
+}
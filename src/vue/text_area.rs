@@ -6,15 +6,33 @@ use sdl2::{
 };
 
 use crate::{
+    gutter::MarkerKind,
+    text_zone::{char_column, char_width, expand_tabs, visual_column, DEFAULT_TAB_WIDTH},
     timer::{self, Timer},
     vue::percent_length,
 };
 
 use super::{
-    char_size, percent_position, str_rect_at_line, text_area_container::TOP_MARGIN, RepositionFun,
-    ResizeFun, VueComponent, VueError, TEXT_COLOR,
+    char_size, percent_position, str_rect_at_line, text_area_container::TOP_MARGIN,
+    ContentSnapshot, RepositionFun, ResizeFun, VueComponent, VueError, BLOCK_CURSOR_COLOR,
+    BRACKET_COLOR, ERROR_COLOR, GREY_TEXT_COLOR, SELECTION_COLOR, TEXT_COLOR, WARNING_COLOR,
 };
 
+/// Thickness of the line under diagnostics.
+const UNDERLINE_HEIGHT: u32 = 2;
+
+/// Width kept free on the right of wrapped lines, under the vertical scroll
+/// bar.
+const WRAP_MARGIN: u32 = 20;
+
+/// Part of a line shown on one visual row, as a range of characters.
+#[derive(Clone, Copy)]
+struct Row {
+    line: usize,
+    start: usize,
+    end: usize,
+}
+
 pub(crate) struct TextArea {
     area: Rect,
     pub resize_fun: ResizeFun,
@@ -24,6 +42,19 @@ pub(crate) struct TextArea {
     scroll_offset: (f32, f32),
     cursor_update: bool,
     cursor_timer: Timer,
+    lines: Vec<String>,
+    tab_width: usize,
+    wrap: bool,
+    /// Whether to show who last changed the cursor line after it.
+    inline_blame: bool,
+    rows: Vec<Row>,
+    /// Indexes in `rows` of the first and last rows of each line, `None` for
+    /// folded lines.
+    line_rows: Vec<Option<(usize, usize)>>,
+    /// First lines of the folded regions.
+    folded: Vec<usize>,
+    /// The primary cursor, as last drawn.
+    cursor: (usize, usize),
 }
 
 impl TextArea {
@@ -37,12 +68,176 @@ impl TextArea {
             scroll_offset: (0.0, 0.0),
             cursor_update: false,
             cursor_timer: Timer::new(),
+            lines: Vec::new(),
+            tab_width: DEFAULT_TAB_WIDTH,
+            wrap: false,
+            inline_blame: false,
+            rows: Vec::new(),
+            line_rows: Vec::new(),
+            cursor: (0, 0),
+            folded: Vec::new(),
+        }
+    }
+
+    pub fn is_wrapping(&self) -> bool {
+        self.wrap
+    }
+
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+        self.scroll_offset.0 = 0.0;
+        self.cursor_update = true;
+    }
+
+    pub fn shows_blame(&self) -> bool {
+        self.inline_blame
+    }
+
+    pub fn show_blame(&mut self, inline_blame: bool) {
+        self.inline_blame = inline_blame;
+    }
+
+    /// Splits the lines into rows, at the last space that lets them fit in
+    /// the width of the area when wrapping, leaving out the folded lines.
+    fn layout(&mut self, folds: &[(usize, usize, bool)]) {
+        let columns = match self.content_font_size {
+            (w, _) if self.wrap && w > 0 => {
+                (self.area.width().saturating_sub(WRAP_MARGIN) / w).max(1) as usize
+            }
+            _ => usize::MAX,
+        };
+        let mut hidden = vec![false; self.lines.len()];
+        self.folded.clear();
+        for (first, last, folded) in folds {
+            if *folded && *last < hidden.len() {
+                hidden[first + 1..=*last].fill(true);
+                self.folded.push(*first);
+            }
+        }
+        self.rows.clear();
+        self.line_rows.clear();
+        for (line, text) in self.lines.iter().enumerate() {
+            if hidden[line] {
+                self.line_rows.push(None);
+                continue;
+            }
+            let first_row = self.rows.len();
+            let chars: Vec<char> = text.chars().collect();
+            let mut start = 0;
+            let mut start_column = 0;
+            let mut column = 0;
+            let mut break_at = None;
+            for (i, ch) in chars.iter().enumerate() {
+                column += char_width(*ch, column, self.tab_width);
+                while column - start_column > columns && i > start {
+                    let end = break_at.take().filter(|b| *b > start).unwrap_or(i);
+                    self.rows.push(Row { line, start, end });
+                    start = end;
+                    start_column = visual_column(chars.iter().copied(), start, self.tab_width);
+                }
+                if ch.is_whitespace() {
+                    break_at = Some(i + 1);
+                }
+            }
+            self.rows.push(Row {
+                line,
+                start,
+                end: chars.len(),
+            });
+            self.line_rows.push(Some((first_row, self.rows.len() - 1)));
+        }
+    }
+
+    /// Returns the index of the row showing `cursor`.
+    fn row_of(&self, cursor: (usize, usize)) -> Option<usize> {
+        let (l, c) = cursor;
+        let (first, last) = (*self.line_rows.get(l)?)?;
+        (first..=last).rev().find(|r| self.rows[*r].start <= c)
+    }
+
+    /// Whether `r` is the last row of its line.
+    fn ends_line(&self, r: usize) -> bool {
+        self.rows
+            .get(r + 1)
+            .is_none_or(|next| next.line != self.rows[r].line)
+    }
+
+    /// Returns the cursor `n` rows below `cursor`, or above it if `n` is
+    /// negative, keeping its column on screen.
+    pub fn move_by_rows(&self, cursor: (usize, usize), n: isize) -> (usize, usize) {
+        let Some(r) = self.row_of(cursor) else {
+            return cursor;
+        };
+        let row = self.rows[r];
+        let column = self.visual_column(cursor) - self.visual_column((row.line, row.start));
+        let target = (r as isize + n).clamp(0, self.rows.len() as isize - 1) as usize;
+        self.index_in_row(target, column)
+    }
+
+    /// Returns the cursor displayed at `column` of row `r`.
+    fn index_in_row(&self, r: usize, column: usize) -> (usize, usize) {
+        let row = self.rows[r];
+        let line = &self.lines[row.line];
+        let column = column + self.visual_column((row.line, row.start));
+        let c = char_column(line.chars(), column, self.tab_width);
+        let end = if self.ends_line(r) {
+            line.chars().count()
+        } else {
+            row.end - 1
+        };
+        (row.line, c.clamp(row.start, end))
+    }
+
+    /// Returns the first and last lines shown, even partly.
+    pub fn visible_lines(&self) -> (usize, usize) {
+        let (_, h) = self.content_font_size;
+        if self.rows.is_empty() || h == 0 {
+            return (0, 0);
         }
+        let top = -self.scroll_offset.1 - TOP_MARGIN as f32;
+        let bottom = top + self.area.height() as f32;
+        let last = self.rows.len() - 1;
+        let row = |y: f32| (y.max(0.0) as usize / h as usize).min(last);
+        (self.rows[row(top)].line, self.rows[row(bottom)].line)
+    }
+
+    /// Returns the vertical scroll percent that centers `line` in the area.
+    pub fn scroll_percent_centering(&self, line: usize) -> f32 {
+        let Some((r, _)) = self.line_rows[..=line.min(self.line_rows.len() - 1)]
+            .iter()
+            .rev()
+            .flatten()
+            .next()
+        else {
+            return 0.0;
+        };
+        let (_, h) = self.content_font_size;
+        let (_, scrollable) = self.get_scrollable_area();
+        let range = scrollable.saturating_sub(self.area.height()).max(1);
+        let y = (TOP_MARGIN + *r as u32 * h + h / 2) as f32 - self.area.height() as f32 / 2.0;
+        (y * 100.0 / range as f32).clamp(0.0, 100.0)
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// Returns the line shown on each row, for the first row of each line
+    /// only.
+    pub fn row_lines(&self) -> Vec<Option<usize>> {
+        self.rows
+            .iter()
+            .enumerate()
+            .map(|(r, row)| {
+                (self.line_rows[row.line].map(|(first, _)| first) == Some(r)).then_some(row.line)
+            })
+            .collect()
     }
 
     fn get_content_area(&self) -> Rect {
         let (w, h) = {
-            let (l, c) = self.content_size;
+            let (_, c) = self.content_size;
+            let l = self.rows.len().max(1);
             let (w, h) = self.content_font_size;
             (w * c as u32, h * l as u32)
         };
@@ -58,6 +253,13 @@ impl TextArea {
         self.scroll_offset
     }
 
+    /// Scrolls to `offset`, as returned by `get_scroll_offset`, keeping it
+    /// until the cursor moves.
+    pub fn set_scroll_offset(&mut self, offset: (f32, f32)) {
+        self.scroll_offset = (offset.0.min(0.0), offset.1.min(0.0));
+        self.cursor_update = false;
+    }
+
     pub fn get_scroll_percent(&self) -> (f32, f32) {
         let (w, h) = {
             let (w1, h1) = self.get_scrollable_area();
@@ -98,6 +300,7 @@ impl TextArea {
     pub fn get_scrollable_area(&self) -> (u32, u32) {
         let (w1, h1) = self.get_content_area().size();
         let (w2, h2) = self.area.size();
+        let w1 = if self.wrap { 0 } else { w1 };
         (
             w1 + percent_length(w2, 90),
             h1 + TOP_MARGIN + percent_length(h2, 80),
@@ -126,6 +329,7 @@ impl TextArea {
         let bounds = self.cursor_bounds();
         let (x, y) = self.cursor_position(cursor);
         match x {
+            _ if self.wrap => {}
             x if x < bounds.x => {
                 self.scroll_offset.0 = (self.scroll_offset.0 + bounds.x as f32 - x as f32).min(0.0)
             }
@@ -164,21 +368,32 @@ impl TextArea {
     }
 
     fn draw_content(
-        &mut self,
+        &self,
         content: &Vec<String>,
         canvas: &mut Canvas<Window>,
         font: &Font,
     ) -> Result<(), VueError> {
         let creator = canvas.texture_creator();
-        for (line, text) in content.iter().enumerate() {
-            if text.is_empty() {
+        for (r, row) in self.rows.iter().enumerate() {
+            if row.start == row.end {
                 continue;
             }
-            let surface = font.render(text).blended(TEXT_COLOR)?;
+            let text = {
+                let line = &content[row.line];
+                let start = self.visual_column((row.line, row.start));
+                let end = self.visual_column((row.line, row.end));
+                let text: String = expand_tabs(line, self.tab_width)
+                    .chars()
+                    .skip(start)
+                    .take(end - start)
+                    .collect();
+                text
+            };
+            let surface = font.render(&text).blended(TEXT_COLOR)?;
             let texture = surface.as_texture(&creator)?;
             let rect = {
                 let area = self.get_content_area();
-                let mut rect = str_rect_at_line(font, text, line)?;
+                let mut rect = str_rect_at_line(font, &text, r)?;
                 rect.offset(area.x(), area.y());
                 rect
             };
@@ -187,35 +402,203 @@ impl TextArea {
         Ok(())
     }
 
-    fn cursor_position(&self, cursor: (usize, usize)) -> (i32, i32) {
+    /// Marks the end of the first line of each folded region.
+    fn draw_folds(&self, canvas: &mut Canvas<Window>, font: &Font) -> Result<(), VueError> {
+        let creator = canvas.texture_creator();
+        for l in self.folded.iter() {
+            let Some(Some((_, r))) = self.line_rows.get(*l) else {
+                continue;
+            };
+            let surface = font.render("...").blended(GREY_TEXT_COLOR)?;
+            let texture = surface.as_texture(&creator)?;
+            let (x, y) = self.position_in_row(*r, self.rows[*r].end);
+            let (w, h) = surface.size();
+            canvas.copy(
+                &texture,
+                None,
+                Rect::new(x + self.content_font_size.0 as i32, y, w, h),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes `blame` after the end of the cursor line, leaving room for
+    /// the mark of a folded region.
+    fn draw_blame(
+        &self,
+        cursor: (usize, usize),
+        blame: &str,
+        canvas: &mut Canvas<Window>,
+        font: &Font,
+    ) -> Result<(), VueError> {
+        let Some(Some((_, r))) = self.line_rows.get(cursor.0) else {
+            return Ok(());
+        };
+        let surface = font.render(blame).blended(GREY_TEXT_COLOR)?;
+        let creator = canvas.texture_creator();
+        let texture = surface.as_texture(&creator)?;
+        let (x, y) = self.position_in_row(*r, self.rows[*r].end);
+        let (w, h) = surface.size();
+        canvas.copy(
+            &texture,
+            None,
+            Rect::new(x + 6 * self.content_font_size.0 as i32, y, w, h),
+        )?;
+        Ok(())
+    }
+
+    fn draw_selections(
+        &self,
+        selections: &[(usize, usize, usize)],
+        canvas: &mut Canvas<Window>,
+    ) -> Result<(), String> {
+        canvas.set_draw_color(SELECTION_COLOR);
+        for (l, start, end) in selections {
+            if start == end {
+                continue;
+            }
+            let (Some(first), Some(last)) = (self.row_of((*l, *start)), self.row_of((*l, *end)))
+            else {
+                continue;
+            };
+            for r in first..=last {
+                let row = self.rows[r];
+                let (x, y) = self.position_in_row(r, (*start).max(row.start));
+                let (end_x, _) = self.position_in_row(r, (*end).min(row.end));
+                let (_, h) = self.content_font_size;
+                canvas.fill_rect(Rect::new(x, y, (end_x - x) as u32, h))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Underlines the parts of lines given as `(line, start, end, kind)`, a
+    /// single character for empty parts.
+    fn draw_underlines(
+        &self,
+        underlines: &[(usize, usize, usize, MarkerKind)],
+        canvas: &mut Canvas<Window>,
+    ) -> Result<(), String> {
+        for (l, start, end, kind) in underlines {
+            let end = (*end).max(start + 1);
+            let (Some(first), Some(last)) = (self.row_of((*l, *start)), self.row_of((*l, end)))
+            else {
+                continue;
+            };
+            canvas.set_draw_color(match kind {
+                MarkerKind::Error => ERROR_COLOR,
+                _ => WARNING_COLOR,
+            });
+            for r in first..=last {
+                let row = self.rows[r];
+                let (x, y) = self.position_in_row(r, (*start).max(row.start));
+                let (end_x, _) = self.position_in_row(r, end.min(row.end));
+                let (w, h) = self.content_font_size;
+                let width = (end_x - x).max(w as i32) as u32;
+                let y = y + (h - UNDERLINE_HEIGHT) as i32;
+                canvas.fill_rect(Rect::new(x, y, width, UNDERLINE_HEIGHT))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_brackets(
+        &self,
+        brackets: &[(usize, usize)],
+        canvas: &mut Canvas<Window>,
+    ) -> Result<(), String> {
+        canvas.set_draw_color(BRACKET_COLOR);
+        for (l, c) in brackets {
+            let (x, y) = self.cursor_position((*l, *c));
+            let (w, h) = self.content_font_size;
+            canvas.draw_rect(Rect::new(x, y, w, h))?;
+        }
+        Ok(())
+    }
+
+    fn visual_column(&self, cursor: (usize, usize)) -> usize {
         let (l, c) = cursor;
+        match self.lines.get(l) {
+            Some(line) => visual_column(line.chars(), c, self.tab_width),
+            None => c,
+        }
+    }
+
+    fn cursor_position(&self, cursor: (usize, usize)) -> (i32, i32) {
+        match self.row_of(cursor) {
+            Some(r) => self.position_in_row(r, cursor.1),
+            None if cursor.0 < self.line_rows.len() => {
+                let (_, last) = self.line_rows[..cursor.0]
+                    .iter()
+                    .rev()
+                    .flatten()
+                    .next()
+                    .unwrap();
+                let row = self.rows[*last];
+                self.position_in_row(*last, row.end)
+            }
+            None => {
+                let (l, c) = cursor;
+                let r = self.rows.len() + l - self.line_rows.len();
+                let (w, h) = self.content_font_size;
+                let draw_area = self.get_content_area();
+                (
+                    (w * c as u32) as i32 + draw_area.x,
+                    (h * r as u32) as i32 + draw_area.y,
+                )
+            }
+        }
+    }
+
+    /// Returns the position of the `c`-th character of the line of row `r`,
+    /// drawn on that row.
+    fn position_in_row(&self, r: usize, c: usize) -> (i32, i32) {
+        let row = self.rows[r];
+        let c = self.visual_column((row.line, c)) - self.visual_column((row.line, row.start));
         let (w, h) = self.content_font_size;
         let draw_area = self.get_content_area();
         let (x, y) = {
             let x = (w * c as u32) as i32;
-            let y = (h * l as u32) as i32;
+            let y = (h * r as u32) as i32;
             (x + draw_area.x, y + draw_area.y)
         };
         (x, y)
     }
 
+    /// Returns the position just under the primary cursor, where popups
+    /// about it are shown.
+    pub fn cursor_anchor(&self) -> (i32, i32) {
+        let (x, y) = self.cursor_position(self.cursor);
+        (x, y + self.content_font_size.1 as i32)
+    }
+
+    /// Draws the cursors as thin bars before their character, or as blocks
+    /// over it when `block` is set.
     fn draw_cursor(
         &mut self,
-        cursor: (usize, usize),
+        cursors: &[(usize, usize)],
+        block: bool,
         canvas: &mut Canvas<Window>,
     ) -> Result<(), String> {
         let blink = self.cursor_timer.switch_every_n_millis(2000);
         if blink {
             return Ok(());
         }
-        let (x, y) = self.cursor_position(cursor);
-        let rect = {
+        for cursor in cursors {
+            let (x, y) = self.cursor_position(*cursor);
             let (w, h) = self.content_font_size;
-            let w = w / 4;
-            Rect::new(x - (w / 2) as i32, y, w, h)
-        };
-        canvas.set_draw_color(TEXT_COLOR);
-        canvas.draw_rect(rect)?;
+            if block {
+                canvas.set_draw_color(BLOCK_CURSOR_COLOR);
+                canvas.fill_rect(Rect::new(x, y, w, h))?;
+                continue;
+            }
+            let rect = {
+                let w = w / 4;
+                Rect::new(x - (w / 2) as i32, y, w, h)
+            };
+            canvas.set_draw_color(TEXT_COLOR);
+            canvas.draw_rect(rect)?;
+        }
         Ok(())
     }
 
@@ -233,21 +616,32 @@ impl TextArea {
         self.cursor_update = false;
     }
 
+    /// Draws the text of `snapshot`, taking its lines.
     pub fn refresh(
         &mut self,
-        content: Vec<String>,
-        content_size: (usize, usize),
-        cursor: (usize, usize),
+        snapshot: &mut ContentSnapshot,
         canvas: &mut Canvas<Window>,
         content_font: &Font,
     ) -> Result<(), VueError> {
+        let cursor = snapshot.cursors[0];
+        self.cursor = cursor;
         self.content_font_size = char_size(content_font);
-        self.content_size = content_size;
+        self.content_size = snapshot.size;
+        self.lines = std::mem::take(&mut snapshot.lines);
+        self.tab_width = snapshot.tab_width;
+        self.layout(&snapshot.folds);
         if self.cursor_update {
             self.on_cursor_update(cursor);
         }
-        self.draw_content(&content, canvas, content_font)?;
-        self.draw_cursor(cursor, canvas)?;
+        self.draw_selections(&snapshot.selections, canvas)?;
+        self.draw_brackets(&snapshot.brackets, canvas)?;
+        self.draw_content(&self.lines, canvas, content_font)?;
+        self.draw_underlines(&snapshot.underlines, canvas)?;
+        self.draw_folds(canvas, content_font)?;
+        if let Some(blame) = snapshot.blame.as_deref().filter(|_| self.inline_blame) {
+            self.draw_blame(cursor, blame, canvas, content_font)?;
+        }
+        self.draw_cursor(&snapshot.cursors, snapshot.block_cursor, canvas)?;
         Ok(())
     }
 
@@ -255,9 +649,13 @@ impl TextArea {
         if self.area.contains_point(Point::new(x, y)) {
             let (w, h) = self.content_font_size;
             let area = self.get_content_area();
-            let l = ((y - area.y) / h as i32) as usize;
-            let c = ((x - area.x) / w as i32) as usize;
-            Some((l, c))
+            let r = ((y - area.y) / h as i32).max(0) as usize;
+            let c = ((x - area.x) / w as i32).max(0) as usize;
+            if r < self.rows.len() {
+                Some(self.index_in_row(r, c))
+            } else {
+                Some((self.line_rows.len() + r - self.rows.len(), c))
+            }
         } else {
             None
         }
@@ -283,3 +681,75 @@ impl VueComponent for TextArea {
         self.resize_fun
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a text area showing `text` with characters 10 pixels wide,
+    /// wrapping lines at `columns` characters.
+    fn text_area(text: &str, columns: Option<u32>) -> TextArea {
+        let mut area = TextArea::new(|size| size, |_, pos| pos);
+        area.area = Rect::new(0, 0, WRAP_MARGIN + 10 * columns.unwrap_or(1), 100);
+        area.content_font_size = (10, 20);
+        area.lines = text.split('\n').map(String::from).collect();
+        area.wrap = columns.is_some();
+        area
+    }
+
+    fn rows(area: &TextArea) -> Vec<(usize, usize, usize)> {
+        (area.rows.iter())
+            .map(|row| (row.line, row.start, row.end))
+            .collect()
+    }
+
+    #[test]
+    fn wrapped_rows() {
+        let mut area = text_area("hello world foo\nabcdefghij", Some(8));
+        area.layout(&[]);
+        assert_eq!(
+            rows(&area),
+            vec![(0, 0, 6), (0, 6, 12), (0, 12, 15), (1, 0, 8), (1, 8, 10)]
+        );
+        assert_eq!(area.line_rows, vec![Some((0, 2)), Some((3, 4))]);
+        assert_eq!(area.row_lines(), vec![Some(0), None, None, Some(1), None]);
+
+        area.wrap = false;
+        area.layout(&[]);
+        assert_eq!(rows(&area), vec![(0, 0, 15), (1, 0, 10)]);
+    }
+
+    #[test]
+    fn row_mapping() {
+        let mut area = text_area("hello world foo\nabcdefghij", Some(8));
+        area.layout(&[]);
+        assert_eq!(area.row_of((0, 5)), Some(0));
+        assert_eq!(area.row_of((0, 6)), Some(1));
+        assert_eq!(area.row_of((0, 15)), Some(2));
+        assert_eq!(area.row_of((2, 0)), None);
+        assert_eq!(area.move_by_rows((0, 7), 1), (0, 13));
+        assert_eq!(area.move_by_rows((0, 13), 1), (1, 1));
+        assert_eq!(area.move_by_rows((1, 9), -1), (1, 1));
+        // The column stays in the row, before its last space.
+        assert_eq!(area.move_by_rows((0, 11), -1), (0, 5));
+        assert_eq!(area.move_by_rows((1, 0), 1), (1, 8));
+        assert_eq!(area.move_by_rows((1, 9), 3), (1, 9));
+    }
+
+    #[test]
+    fn folded_rows() {
+        let mut area = text_area("a {\n\tb\n}\nc", None);
+        area.layout(&[(0, 1, true)]);
+        assert_eq!(rows(&area), vec![(0, 0, 3), (2, 0, 1), (3, 0, 1)]);
+        assert_eq!(
+            area.line_rows,
+            vec![Some((0, 0)), None, Some((1, 1)), Some((2, 2))]
+        );
+        assert_eq!(area.folded, vec![0]);
+        assert_eq!(area.row_of((1, 1)), None);
+        assert_eq!(area.move_by_rows((2, 1), -1), (0, 1));
+        area.layout(&[(0, 1, false)]);
+        assert_eq!(rows(&area).len(), 4);
+        assert!(area.folded.is_empty());
+    }
+}
@@ -1,11 +1,15 @@
 use sdl2::{rect::Rect, render::Canvas, ttf::Font, video::Window};
 
 use super::{
-    line_numbers::LineNumbers, scroll_bar::ScrollBar, text_area::TextArea, RepositionFun,
-    ResizeFun, VueComponent, VueError,
+    line_numbers::{LineNumberMode, LineNumbers},
+    minimap::Minimap,
+    scroll_bar::ScrollBar,
+    text_area::TextArea,
+    ContentSnapshot, RepositionFun, ResizeFun, VueComponent, VueError,
 };
 
 pub(super) const TOP_MARGIN: u32 = 10;
+const MINIMAP_WIDTH: u32 = 80;
 
 pub(crate) struct TextAreaContainer {
     area: Rect,
@@ -15,12 +19,18 @@ pub(crate) struct TextAreaContainer {
     line_numbers: LineNumbers,
     vertical_scroll_bar: ScrollBar,
     horizontal_scroll_bar: ScrollBar,
+    minimap: Minimap,
+    /// Width of the line numbers, the text area taking the rest.
+    gutter_width: u32,
 }
 
 impl TextAreaContainer {
     pub fn new(resize_fun: ResizeFun, reposition_fun: RepositionFun) -> Self {
-        let text_area = TextArea::new(|(w, h)| (w - 40, h - 30), |_, (x, y)| (x + 40, y));
-        let line_numbers = LineNumbers::new(|(_, h)| (40, h), |_, (x, y)| (x, y));
+        let text_area = TextArea::new(
+            |(w, h)| (w.saturating_sub(MINIMAP_WIDTH), h - 30),
+            |_, pos| pos,
+        );
+        let line_numbers = LineNumbers::new(|size| size, |_, pos| pos);
         let vertical_scroll_bar = ScrollBar::new(
             |(_, h)| (20, h - TOP_MARGIN),
             |(w, _), (x, y)| (x + (w as i32 - 20), y),
@@ -31,6 +41,15 @@ impl TextAreaContainer {
             |(_, h), (x, y)| (x, y + (h - 20) as i32),
             false,
         );
+        let minimap = Minimap::new(
+            |(_, h)| (MINIMAP_WIDTH, h - TOP_MARGIN - 20),
+            |(w, _), (x, y)| {
+                (
+                    x + w as i32 - 20 - MINIMAP_WIDTH as i32,
+                    y + TOP_MARGIN as i32,
+                )
+            },
+        );
         TextAreaContainer {
             area: Rect::new(0, 0, 0, 0),
             resize_fun,
@@ -39,14 +58,32 @@ impl TextAreaContainer {
             line_numbers,
             vertical_scroll_bar,
             horizontal_scroll_bar,
+            minimap,
+            gutter_width: 40,
         }
     }
 
+    pub fn area(&self) -> Rect {
+        self.area
+    }
+
+    pub fn contains_point(&self, x: i32, y: i32) -> bool {
+        self.area.contains_point((x, y))
+    }
+
     pub fn scroll(&mut self, x: f32, y: f32) {
         self.text_area.scroll_y(20.0 * y);
         self.text_area.scroll_x(20.0 * -x);
     }
 
+    pub fn scroll_offset(&self) -> (f32, f32) {
+        self.text_area.get_scroll_offset()
+    }
+
+    pub fn set_scroll_offset(&mut self, offset: (f32, f32)) {
+        self.text_area.set_scroll_offset(offset)
+    }
+
     pub fn cursor_index(&self, x: i32, y: i32) -> Option<(usize, usize)> {
         self.text_area.index_of_position(x, y)
     }
@@ -55,6 +92,76 @@ impl TextAreaContainer {
         self.text_area.send_cursor_update();
     }
 
+    pub fn is_wrapping(&self) -> bool {
+        self.text_area.is_wrapping()
+    }
+
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.text_area.set_wrap(wrap);
+    }
+
+    pub fn shows_blame(&self) -> bool {
+        self.text_area.shows_blame()
+    }
+
+    pub fn show_blame(&mut self, inline_blame: bool) {
+        self.text_area.show_blame(inline_blame);
+    }
+
+    pub fn move_by_rows(&self, cursor: (usize, usize), n: isize) -> (usize, usize) {
+        self.text_area.move_by_rows(cursor, n)
+    }
+
+    pub fn line_number_mode(&self) -> LineNumberMode {
+        self.line_numbers.get_mode()
+    }
+
+    pub fn set_line_number_mode(&mut self, mode: LineNumberMode) {
+        self.line_numbers.set_mode(mode);
+    }
+
+    pub fn shows_line_numbers(&self) -> bool {
+        self.line_numbers.is_visible()
+    }
+
+    pub fn show_line_numbers(&mut self, visible: bool) {
+        self.line_numbers.set_visible(visible);
+    }
+
+    pub fn click_fold_marker(&self, x: i32, y: i32) -> Option<usize> {
+        self.line_numbers.marker_at(x, y)
+    }
+
+    pub fn cursor_anchor(&self) -> (i32, i32) {
+        self.text_area.cursor_anchor()
+    }
+
+    pub fn tooltip_at(&self, x: i32, y: i32) -> Option<&str> {
+        self.line_numbers.tooltip_at(x, y)
+    }
+
+    pub fn click_minimap(&mut self, x: i32, y: i32) -> bool {
+        match self.minimap.click(x, y) {
+            Some(line) => {
+                let percent = self.text_area.scroll_percent_centering(line);
+                self.text_area.set_y_scroll_percent(percent);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn hold_minimap(&mut self, origin: (i32, i32), y: i32) -> bool {
+        match self.minimap.hold(origin, y) {
+            Some(line) => {
+                let percent = self.text_area.scroll_percent_centering(line);
+                self.text_area.set_y_scroll_percent(percent);
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn click_scroll_bar(&mut self, x: i32, y: i32) -> bool {
         let b1 = match self.vertical_scroll_bar.click_scroll(x, y) {
             Some(p) => {
@@ -64,6 +171,7 @@ impl TextAreaContainer {
             None => false,
         };
         let b2 = match self.horizontal_scroll_bar.click_scroll(x, y) {
+            _ if self.text_area.is_wrapping() => false,
             Some(p) => {
                 self.text_area.set_x_scroll_percent(p);
                 true
@@ -95,6 +203,7 @@ impl TextAreaContainer {
             .horizontal_scroll_bar
             .hold_scroll(origin, x, y, xrel, yrel)
         {
+            _ if self.text_area.is_wrapping() => false,
             Some(p) => {
                 self.text_area.set_x_scroll_percent(p);
                 true
@@ -106,34 +215,47 @@ impl TextAreaContainer {
 
     pub fn refresh(
         &mut self,
-        content: Vec<String>,
-        content_size: (usize, usize),
-        cursor: (usize, usize),
+        snapshot: &mut ContentSnapshot,
         canvas: &mut Canvas<Window>,
         content_font: &Font,
         line_number_font: &Font,
     ) -> Result<(), VueError> {
+        let cursor = snapshot.cursors[0];
+        let gutter_width = self.line_numbers.width(snapshot.size.0, line_number_font);
+        if gutter_width != self.gutter_width {
+            self.gutter_width = gutter_width;
+            self.affect_children();
+        }
         canvas.set_clip_rect(self.area);
-        self.text_area
-            .refresh(content, content_size, cursor, canvas, content_font)?;
+        self.text_area.refresh(snapshot, canvas, content_font)?;
+        self.line_numbers.set_folds(&snapshot.folds);
+        self.line_numbers.set_markers(&snapshot.markers);
         self.line_numbers.refresh(
-            content_size.0,
+            &self.text_area.row_lines(),
             cursor.0,
             self.text_area.get_scroll_offset().1,
             canvas,
             line_number_font,
             content_font,
         )?;
+        self.minimap.refresh(
+            self.text_area.lines(),
+            snapshot.tab_width,
+            self.text_area.visible_lines(),
+            canvas,
+        )?;
         self.vertical_scroll_bar.refresh(
             self.text_area.get_scroll_percent().1,
             self.text_area.get_shown_percent().1,
             canvas,
         )?;
-        self.horizontal_scroll_bar.refresh(
-            self.text_area.get_scroll_percent().0,
-            self.text_area.get_shown_percent().0,
-            canvas,
-        )?;
+        if !self.text_area.is_wrapping() {
+            self.horizontal_scroll_bar.refresh(
+                self.text_area.get_scroll_percent().0,
+                self.text_area.get_shown_percent().0,
+                canvas,
+            )?;
+        }
         Ok(())
     }
 }
@@ -160,9 +282,13 @@ impl VueComponent for TextAreaContainer {
     fn affect_children(&mut self) {
         let size = self.area.size();
         let pos = (self.area.x, self.area.y);
-        self.text_area.on_resize(size, pos);
-        self.line_numbers.on_resize(size, pos);
+        let (w, h) = size;
+        let gutter_width = self.gutter_width.min(w);
+        self.text_area
+            .on_resize((w - gutter_width, h), (pos.0 + gutter_width as i32, pos.1));
+        self.line_numbers.on_resize((gutter_width, h), pos);
         self.vertical_scroll_bar.on_resize(size, pos);
         self.horizontal_scroll_bar.on_resize(size, pos);
+        self.minimap.on_resize(size, pos);
     }
 }
@@ -0,0 +1,57 @@
+use sdl2::{
+    rect::{Point, Rect},
+    render::Canvas,
+    ttf::Font,
+    video::Window,
+};
+
+use super::{str_rect, VueError, BAR_COLOR, OUTLINE_COLOR, TEXT_COLOR};
+
+const PADDING: u32 = 6;
+/// Offset from the mouse, so that the tooltip does not hide what it is about.
+const OFFSET: i32 = 12;
+
+/// Draws `text` in a box next to `point`, kept inside `bounds`.
+pub(super) fn draw_tooltip(
+    text: &str,
+    point: Point,
+    bounds: Rect,
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+) -> Result<(), VueError> {
+    let line_height = font.height() as u32;
+    let lines: Vec<&str> = text.lines().collect();
+    let mut width = 0;
+    for line in lines.iter().filter(|line| !line.is_empty()) {
+        width = width.max(str_rect(font, line)?.width());
+    }
+    let area = {
+        let (w, h) = (
+            width + 2 * PADDING,
+            lines.len() as u32 * line_height + 2 * PADDING,
+        );
+        let x = (point.x() + OFFSET).min(bounds.right() - w as i32);
+        let y = (point.y() + OFFSET).min(bounds.bottom() - h as i32);
+        Rect::new(x.max(bounds.x()), y.max(bounds.y()), w, h)
+    };
+    canvas.set_clip_rect(bounds);
+    canvas.set_draw_color(BAR_COLOR);
+    canvas.fill_rect(area)?;
+    canvas.set_draw_color(OUTLINE_COLOR);
+    canvas.draw_rect(area)?;
+    let creator = canvas.texture_creator();
+    for (i, line) in lines.iter().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let surface = font.render(line).blended(TEXT_COLOR)?;
+        let texture = surface.as_texture(&creator)?;
+        let mut rect = str_rect(font, line)?;
+        rect.offset(
+            area.x() + PADDING as i32,
+            area.y() + (PADDING + i as u32 * line_height) as i32,
+        );
+        canvas.copy(&texture, None, rect)?;
+    }
+    Ok(())
+}
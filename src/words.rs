@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use crate::{diff::diff, text_zone::is_word_char};
+
+/// Most completions offered at once.
+const MAX_COMPLETIONS: usize = 50;
+/// Words shorter than this are not worth completing.
+const MIN_WORD_LENGTH: usize = 3;
+
+/// The words of a text by line, updated from the lines that changed.
+pub(crate) struct WordIndex {
+    lines: Vec<String>,
+    line_words: Vec<Vec<String>>,
+    /// Number of occurrences of each word.
+    counts: HashMap<String, usize>,
+}
+
+fn words_of(line: &str) -> Vec<String> {
+    line.split(|ch: char| !is_word_char(ch))
+        .filter(|word| word.chars().count() >= MIN_WORD_LENGTH)
+        .filter(|word| !word.starts_with(|ch: char| ch.is_ascii_digit()))
+        .map(String::from)
+        .collect()
+}
+
+/// Returns whether the characters of `prefix` appear in order in `word`,
+/// ignoring case.
+fn fuzzy_match(prefix: &str, word: &str) -> bool {
+    let mut chars = word.chars().flat_map(char::to_lowercase);
+    prefix
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|p| chars.any(|ch| ch == p))
+}
+
+impl WordIndex {
+    pub fn new() -> Self {
+        WordIndex {
+            lines: Vec::new(),
+            line_words: Vec::new(),
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Indexes `lines`, reading again only the lines changed since the last
+    /// time.
+    pub fn update(&mut self, lines: &[String]) {
+        if self.lines == lines {
+            return;
+        }
+        for hunk in diff(&self.lines, lines).into_iter().rev() {
+            let new_words: Vec<Vec<String>> = lines[hunk.new_start..hunk.new_start + hunk.new_len]
+                .iter()
+                .map(|line| words_of(line))
+                .collect();
+            for word in new_words.iter().flatten() {
+                *self.counts.entry(word.clone()).or_insert(0) += 1;
+            }
+            let old_range = hunk.old_start..hunk.old_start + hunk.old_len;
+            for word in self.line_words.splice(old_range, new_words).flatten() {
+                let count = self.counts.get_mut(&word).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    self.counts.remove(&word);
+                }
+            }
+        }
+        self.lines = lines.to_vec();
+    }
+
+    /// Returns the distance from line `l` to the closest line holding each
+    /// word.
+    fn distances(&self, l: usize) -> HashMap<&str, usize> {
+        let mut distances = HashMap::new();
+        for (i, words) in self.line_words.iter().enumerate() {
+            for word in words {
+                let distance = distances.entry(word.as_str()).or_insert(usize::MAX);
+                *distance = (*distance).min(i.abs_diff(l));
+            }
+        }
+        distances
+    }
+}
+
+/// Returns the words completing `prefix` on line `l` of the text indexed by
+/// `current`, or in the `others`. Words starting with the prefix come before
+/// those only containing its characters, then the closest to the line
+/// first, the words of other texts last.
+pub fn complete(prefix: &str, l: usize, current: &WordIndex, others: &[&WordIndex]) -> Vec<String> {
+    if prefix.is_empty() {
+        return Vec::new();
+    }
+    let lowercase = prefix.to_lowercase();
+    let mut ranked: HashMap<&str, (bool, usize)> = HashMap::new();
+    let candidates = current.distances(l).into_iter().chain(
+        others
+            .iter()
+            .flat_map(|index| index.counts.keys().map(|word| (word.as_str(), usize::MAX))),
+    );
+    for (word, distance) in candidates {
+        // The word being typed is not a completion, unless it also appears
+        // somewhere else.
+        if word == prefix && current.counts.get(word).is_none_or(|n| *n < 2) {
+            continue;
+        }
+        if !fuzzy_match(prefix, word) {
+            continue;
+        }
+        let fuzzy = !word.to_lowercase().starts_with(&lowercase);
+        let rank = ranked.entry(word).or_insert((fuzzy, distance));
+        rank.1 = rank.1.min(distance);
+    }
+    let mut words: Vec<(&str, (bool, usize))> = ranked.into_iter().collect();
+    words.sort_by(|(a, a_rank), (b, b_rank)| a_rank.cmp(b_rank).then(a.cmp(b)));
+    words
+        .into_iter()
+        .take(MAX_COMPLETIONS)
+        .map(|(word, _)| word.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(text: &str) -> WordIndex {
+        let mut index = WordIndex::new();
+        index.update(&text.split('\n').map(String::from).collect::<Vec<_>>());
+        index
+    }
+
+    #[test]
+    fn incremental_update() {
+        let mut updated = index("let value = 1;\nlet other = value;\nfn main()");
+        let text = "let value = 1;\nfn main()\nlet total = value + other;";
+        updated.update(&text.split('\n').map(String::from).collect::<Vec<_>>());
+        let fresh = index(text);
+        assert_eq!(updated.counts, fresh.counts);
+        assert_eq!(updated.line_words, fresh.line_words);
+        assert_eq!(updated.counts["value"], 2);
+        assert!(updated.counts.contains_key("total"));
+        assert!(!updated.counts.contains_key("fn"));
+    }
+
+    #[test]
+    fn ranking() {
+        let current = index("value_far\n\n\n\nvalue_near\nval\n\nvault_lid");
+        let other = index("value_other\nvalue_near");
+        assert_eq!(
+            complete("val", 5, &current, &[&other]),
+            vec!["value_near", "value_far", "value_other", "vault_lid"]
+        );
+        assert_eq!(complete("VN", 0, &current, &[]), vec!["value_near"]);
+        assert!(complete("", 0, &current, &[]).is_empty());
+    }
+}